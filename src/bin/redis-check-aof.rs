@@ -0,0 +1,87 @@
+//! Checks an append-only file without starting the server, reading it with
+//! the same parser the server loads it with. Given a manifest it checks
+//! every file the manifest lists. `--fix` truncates a file to its last
+//! valid command, which only the last file may need: the others were
+//! complete when the next one was started.
+//!
+//! Usage: redis-check-aof [--fix] <file.manifest|file.aof>
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::process::exit;
+use redis_starter_rust::resp_parser::infra::aof::check;
+use redis_starter_rust::resp_parser::infra::aof::manifest::{FileType, Manifest};
+use redis_starter_rust::resp_parser::infra::memory::storage::unix_time_ms;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let (fix, path) = match arguments.as_slice() {
+        [path] => (false, path),
+        [flag, path] if flag == "--fix" => (true, path),
+        _ => {
+            eprintln!("Usage: redis-check-aof [--fix] <file.manifest|file.aof>");
+            exit(1);
+        },
+    };
+    let path = Path::new(path);
+
+    if !path.to_string_lossy().ends_with(".manifest") {
+        println!("Start checking Old-Style AOF");
+        check_file(path, fix);
+        return;
+    }
+
+    println!("Start checking Multi Part AOF");
+    let manifest = match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| Manifest::parse(&text)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            println!("Invalid AOF manifest file {}: {}", path.display(), e);
+            exit(1);
+        },
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let files: Vec<_> = manifest.files().collect();
+    for (index, file) in files.iter().enumerate() {
+        let kind = if file.kind == FileType::Base { "BASE" } else { "INCR" };
+        println!("Start to check {} AOF {}", kind, file.name);
+        check_file(&dir.join(&file.name), fix && index + 1 == files.len());
+    }
+    println!("All AOF files and manifest are valid");
+}
+
+/// Checks one file, truncating it when `fix` is set and it is cut short
+/// or corrupt. Exits if it is left invalid.
+fn check_file(path: &Path, fix: bool) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Cannot open file {}: {}", path.display(), e);
+            exit(1);
+        },
+    };
+    let report = check::check(&data, unix_time_ms());
+    report.print(&name);
+    if report.error.is_none() {
+        println!("AOF {} is valid", name);
+        return;
+    }
+    if !fix || !report.is_fixable() {
+        println!("AOF {} is not valid. Use the --fix option to try fixing it.", name);
+        exit(1);
+    }
+
+    println!("This will shrink the AOF {} from {} bytes, with {} bytes, to {} bytes", name, report.size, report.size - report.valid_up_to, report.valid_up_to);
+    if let Err(e) = truncate(path, report.valid_up_to as u64) {
+        println!("Failed to truncate AOF {}: {}", name, e);
+        exit(1);
+    }
+    println!("Successfully truncated AOF {}", name);
+}
+
+fn truncate(path: &Path, length: u64) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(length)?;
+    file.sync_all()
+}
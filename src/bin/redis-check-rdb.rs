@@ -0,0 +1,34 @@
+//! Checks an RDB file without starting the server, reading it with the
+//! same reader the server loads snapshots with.
+//!
+//! Usage: redis-check-rdb <rdb-file-name>
+
+use std::fs;
+use std::process::exit;
+use redis_starter_rust::resp_parser::infra::memory::storage::unix_time_ms;
+use redis_starter_rust::resp_parser::infra::rdb::check;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let [path] = arguments.as_slice() else {
+        eprintln!("Usage: redis-check-rdb <rdb-file-name>");
+        exit(1);
+    };
+
+    println!("[offset 0] Checking RDB file {}", path);
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Cannot check RDB that is not a file: {}", e);
+            exit(1);
+        },
+    };
+    let report = check::check(&data, unix_time_ms());
+    report.print();
+    if let Some(length) = report.length.filter(|length| *length < data.len()) {
+        println!("[info] {} bytes follow the end of the RDB data", data.len() - length);
+    }
+    if report.error.is_some() {
+        exit(1);
+    }
+}
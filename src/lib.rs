@@ -0,0 +1,5 @@
+//! The protocol, keyspace and persistence code of the server, shared by
+//! the `redis-starter-rust` binary and the offline `redis-check-rdb` and
+//! `redis-check-aof` tools in `src/bin`.
+
+pub mod resp_parser;
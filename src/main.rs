@@ -1,98 +1,597 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::AsyncReadExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::WriteHalf;
 use tokio::net::TcpListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::UnboundedReceiver;
+use crate::resp_parser::domain::cluster::ClusterState;
 use crate::resp_parser::domain::command_handler::CommandHandler;
-use crate::resp_parser::domain::stream_chunking_service::{StreamChunkingService, StreamChunkingServiceError};
-use crate::resp_parser::infra::new_line_stream_chunking_service::NewLineStreamChunkingService;
+use crate::resp_parser::domain::response_builder::ResponseBuilder;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::{ProtocolVersion, RespResponse};
+use crate::resp_parser::domain::stream_chunking_service::{RawCommand, StreamChunkingService, StreamChunkingServiceError};
+use crate::resp_parser::domain::config::Config;
+use crate::resp_parser::infra::aof::{self, appender::Appender, manifest::Manifest, AofError};
+use crate::resp_parser::infra::config_file;
+use crate::resp_parser::infra::rdb;
+use crate::resp_parser::infra::rdb::snapshotter::Snapshotter;
+use crate::resp_parser::infra::resp_stream_chunking_service::RespStreamChunkingService;
 use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::domain::pubsub_message::PubSubMessage;
+use crate::resp_parser::infra::memory::channel_registry::ChannelRegistry;
+use crate::resp_parser::infra::memory::client_registry::ClientRegistry;
 use crate::resp_parser::infra::memory::command_repository::CommandRepository;
+use crate::resp_parser::infra::memory::config_registry::ConfigRegistry;
+use crate::resp_parser::infra::memory::keyspace_notifier::KeyspaceNotifier;
 use crate::resp_parser::infra::memory::query_repository::QueryRepository;
+use crate::resp_parser::infra::memory::server_stats::ServerStats;
 use crate::resp_parser::infra::memory::storage::Storage;
+use crate::resp_parser::infra::memory::tracking_table::TrackingTable;
+use crate::resp_parser::infra::replication::{replica::ReplicaLink, Attachment, LinkState, MasterAddress, Replication, Resync};
 
-mod resp_parser;
+use redis_starter_rust::resp_parser;
+
+/// Replies are flushed mid-batch once this many bytes are pending.
+const OUTPUT_FLUSH_THRESHOLD: usize = 64 * 1024;
+/// Pushed messages a client leaves unread before it is disconnected, like
+/// the pubsub class of Redis' client-output-buffer-limit.
+const OUTPUT_BUFFER_LIMIT: usize = 32 * 1024 * 1024;
+/// Bytes a replica leaves unread before it is disconnected, like the
+/// replica class of client-output-buffer-limit.
+const REPLICA_OUTPUT_BUFFER_LIMIT: usize = 256 * 1024 * 1024;
+/// How often a master pings its replicas, so they can tell a quiet master
+/// from a lost one.
+const REPLICA_PING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// State shared by every connection.
+#[derive(Clone)]
+struct Server {
+    storage: Storage,
+    channel_registry: ChannelRegistry,
+    shard_channel_registry: ChannelRegistry,
+    cluster: ClusterState,
+    keyspace_notifier: KeyspaceNotifier,
+    client_registry: ClientRegistry,
+    tracking_table: TrackingTable,
+    config_registry: ConfigRegistry,
+    stats: ServerStats,
+    snapshotter: Snapshotter,
+    appender: Appender,
+    replication: Replication,
+}
+
+impl Server {
+    fn create_handler(&self) -> CommandHandler {
+        CommandHandler::new(
+            CommandRepository::new(
+                self.storage.clone(),
+                self.keyspace_notifier.clone(),
+                self.tracking_table.clone(),
+                self.stats.clone(),
+                self.appender.clone(),
+                self.replication.clone(),
+            ),
+            QueryRepository::new(self.storage.clone(), self.keyspace_notifier.clone(), self.tracking_table.clone(), self.stats.clone()),
+            self.channel_registry.clone(),
+            self.shard_channel_registry.clone(),
+            self.cluster.clone(),
+            self.keyspace_notifier.clone(),
+            self.tracking_table.clone(),
+            self.config_registry.clone(),
+            self.stats.clone(),
+            self.snapshotter.clone(),
+            self.appender.clone(),
+            self.replication.clone(),
+        )
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    let config = config_file::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let channel_registry = ChannelRegistry::default();
+    let client_registry = ClientRegistry::default();
+    let keyspace_notifier = KeyspaceNotifier::new(channel_registry.clone());
+    keyspace_notifier.set_flags(config.notify_keyspace_events);
     let storage = Storage::default();
-    let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
+    let config_registry = ConfigRegistry::new(config.clone());
+    let server = Server {
+        snapshotter: Snapshotter::new(storage.clone(), config_registry.clone()),
+        appender: Appender::new(storage.clone(), config_registry.clone()),
+        replication: Replication::new(storage.clone()),
+        storage,
+        keyspace_notifier,
+        channel_registry,
+        shard_channel_registry: ChannelRegistry::sharded(),
+        cluster: ClusterState::new(config.cluster_enabled),
+        tracking_table: TrackingTable::new(client_registry.clone()),
+        client_registry,
+        config_registry,
+        stats: ServerStats::default(),
+    };
+    server.appender.set_fsync(config.appendfsync);
+    server.replication.set_backlog_size(config.repl_backlog_size);
+    server.replication.set_read_only(config.replica_read_only);
+    server.replication.set_serve_stale_data(config.replica_serve_stale_data);
+    server.tracking_table.set_max_keys(config.tracking_table_max_keys);
+    load_data(&config, &server).await;
+    if let Some((host, port)) = config.replicaof.clone() {
+        server.replication.set_master(Some(MasterAddress { host, port }));
+    }
+    let next_client_id = Arc::new(AtomicU64::new(1));
+
+    let mut listeners = Vec::new();
+    for address in &config.bind {
+        // A leading '-' marks an address that may be unavailable.
+        let optional = address.starts_with('-');
+        let host = match address.trim_start_matches('-') {
+            "*" => "0.0.0.0",
+            "::*" => "::",
+            host => host,
+        };
+        match TcpListener::bind((host, config.port)).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if optional => println!("Skipping {}:{}: {}", host, config.port, e),
+            Err(e) => {
+                eprintln!("Could not create server TCP listening socket {}:{}: {}", host, config.port, e);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    tokio::spawn(expire_keys(server.clone()));
+    tokio::spawn(save_snapshots(server.clone()));
+    tokio::spawn(flush_append_only_file(server.clone()));
+    tokio::spawn(shutdown_on_signal(server.clone()));
+    tokio::spawn(replicate(server.clone()));
+    tokio::spawn(ping_replicas(server.clone()));
 
+    let accept_loops: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| tokio::spawn(accept_connections(listener, server.clone(), next_client_id.clone())))
+        .collect();
+    for accept_loop in accept_loops {
+        let _ = accept_loop.await;
+    }
+}
+
+/// Loads the append-only file when it is on, as it has the latest writes,
+/// and the RDB snapshot otherwise. An append-only file turned on for the
+/// first time starts from the snapshot.
+async fn load_data(config: &Config, server: &Server) {
+    if !config.appendonly {
+        load_snapshot(config, &server.storage).await;
+        return;
+    }
+    let manifest = aof::find_manifest(config).unwrap_or_else(|e| {
+        eprintln!("Can't read the AOF manifest: {}. Exiting.", e);
+        std::process::exit(1);
+    });
+    let opened = match manifest {
+        Some(manifest) => {
+            load_append_only_file(config, &manifest, server).await;
+            server.appender.open(manifest).await.map_err(|e| e.to_string())
+        },
+        None => {
+            load_snapshot(config, &server.storage).await;
+            server.appender.start().await
+        },
+    };
+    if let Err(e) = opened {
+        eprintln!("Can't open the append-only file: {}. Exiting.", e);
+        std::process::exit(1);
+    }
+}
+
+async fn load_append_only_file(config: &Config, manifest: &Manifest, server: &Server) {
+    let started_at = Instant::now();
+    let handler = server.create_handler();
+    let dir = Path::new(&config.dir).join(&config.appenddirname);
+    match aof::load(&dir, manifest, &server.storage, &handler, config.aof_load_truncated).await {
+        Ok(commands) => {
+            // Replayed commands are not client traffic.
+            server.stats.reset();
+            println!("Done loading AOF, commands replayed: {}.", commands);
+            println!("DB loaded from append only file: {:.3} seconds", started_at.elapsed().as_secs_f64());
+        },
+        Err(e @ (AofError::Truncated(_) | AofError::UnfinishedTransaction(_))) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "You can: 1) Make a backup of your AOF file, then use ./redis-check-aof --fix <filename>. \
+                2) Alternatively you can set the 'aof-load-truncated' configuration option to yes and restart the server.",
+            );
+            std::process::exit(1);
+        },
+        Err(e @ (AofError::Format { .. } | AofError::Command { .. })) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Make a backup of your AOF file, then use ./redis-check-aof --fix {}",
+                dir.join(aof::manifest::file_name(&config.appendfilename)).display(),
+            );
+            std::process::exit(1);
+        },
+        Err(e) => {
+            eprintln!("Fatal error loading the append only file ({}): {}. Exiting.", dir.display(), e);
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Loads `dir/dbfilename` when it exists. A snapshot that cannot be read
+/// fully stops the server, so it never runs on partial data.
+async fn load_snapshot(config: &Config, storage: &Storage) {
+    let path = Path::new(&config.dir).join(&config.dbfilename);
+    if !path.exists() {
+        return;
+    }
+    let started_at = Instant::now();
+    match rdb::load(&path, storage).await {
+        Ok(loaded) => {
+            println!("Done loading RDB, keys loaded: {}, keys expired: {}.", loaded.keys, loaded.expired);
+            println!("DB loaded from disk: {:.3} seconds", started_at.elapsed().as_secs_f64());
+        },
+        Err(e) => {
+            eprintln!("Fatal error loading the DB ({}): {}. Exiting.", path.display(), e);
+            std::process::exit(1);
+        },
+    }
+}
+
+async fn accept_connections(listener: TcpListener, server: Server, next_client_id: Arc<AtomicU64>) {
     loop {
-        let (stream, _) = listener.accept().await.unwrap();
-        let storage_clone = Arc::clone(&storage);
+        let (stream, peer) = listener.accept().await.unwrap();
+        let server_clone = server.clone();
+        let client_id = next_client_id.fetch_add(1, Ordering::Relaxed);
         tokio::spawn(async move {
-            handle_connection(stream, storage_clone).await;
+            handle_connection(stream, peer.ip().to_string(), server_clone, client_id).await;
         });
     }
 }
 
-async fn handle_connection(mut stream: tokio::net::TcpStream, storage: Storage) {
-    let mut buffer = [0; 512];
-    let mut chunking_service = NewLineStreamChunkingService::new();
+async fn handle_connection(mut stream: tokio::net::TcpStream, peer_ip: String, server: Server, client_id: u64) {
+    let mut buffer = [0; 16 * 1024];
+    let mut chunking_service = RespStreamChunkingService::new();
+    let (subscriber, mut messages) = tokio::sync::mpsc::unbounded_channel();
+    server.client_registry.register(client_id, subscriber.clone()).await;
+    server.stats.client_connected();
+    let mut session = Session::new(client_id, subscriber);
+    session.set_peer_ip(peer_ip);
+    let mut output = BytesMut::new();
+    // Writes streamed to the connection once PSYNC made it a replica's.
+    let mut replication_stream = None;
+    let (mut reader, mut writer) = stream.split();
 
-    loop {
-        match stream.read(&mut buffer).await {
+    'connection: loop {
+        // Requests are only read once the previous replies are written, so a
+        // client that stops reading stops being served. Pushed messages keep
+        // being buffered up to OUTPUT_BUFFER_LIMIT.
+        // Subscribers and replicas are exempt from the idle timeout, like
+        // in Redis.
+        let timeout = server.config_registry.read(|config| config.timeout).await;
+        let idle = timeout > 0 && !session.is_subscribed() && !session.is_replica();
+        let read = tokio::select! {
+            read = reader.read(&mut buffer), if output.is_empty() => read,
+            _ = tokio::time::sleep(Duration::from_secs(timeout)), if idle => {
+                println!("Closing idle client {}", client_id);
+                break;
+            },
+            written = writer.write_buf(&mut output), if !output.is_empty() => {
+                match written {
+                    Ok(bytes_written) => server.stats.net_output(bytes_written),
+                    Err(e) => {
+                        println!("Failed to write to connection: {}", e);
+                        break;
+                    },
+                }
+                continue;
+            },
+            Some(message) = messages.recv() => {
+                push_message(message, &session, &mut output);
+                while let Ok(message) = messages.try_recv() {
+                    push_message(message, &session, &mut output);
+                }
+                if output.len() > OUTPUT_BUFFER_LIMIT {
+                    println!("Client {} exceeded the output buffer limit, closing", client_id);
+                    break;
+                }
+                continue;
+            }
+            data = next_replicated(&mut replication_stream) => {
+                // The stream ends when the replica is detached, e.g. once
+                // this server becomes a replica itself.
+                let Some(data) = data else {
+                    println!("Closing the connection of replica {}", client_id);
+                    break;
+                };
+                output.extend_from_slice(&data);
+                if output.len() > REPLICA_OUTPUT_BUFFER_LIMIT {
+                    println!("Replica {} exceeded the output buffer limit, closing", client_id);
+                    break;
+                }
+                continue;
+            }
+        };
+        match read {
             Ok(0) => {
                 println!("Connection closed");
-                return;
+                break;
             }
             Ok(bytes_read) => {
                 println!("Received {} bytes", &buffer[..bytes_read].len());
-                match chunking_service.next(&buffer[..bytes_read]) {
-                    Ok(commands) => {
-                        if commands.is_empty() {
-                            continue;
-                        }
-                        println!("Received {} commands", commands.len());
-                        for cmd in commands {
-                            println!("{}", cmd);
-                            let command = RespCommand::parse(cmd);
-                            match command {
-                                Ok(resp_command) => {
-                                    match process_command(resp_command, &storage).await {
-                                        Ok(response) => {
-                                            write_response(&mut stream, &response).await;
-                                        },
-                                        Err(e) => {
-                                            let error_response = format!("-ERR {}\r\n", e);
-                                            write_response(&mut stream, &error_response).await;
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    let error_response = format!("-ERR {}\r\n", e);
-                                    write_response(&mut stream, &error_response).await;
-                                }
+                server.stats.net_input(bytes_read);
+                let mut input = &buffer[..bytes_read];
+                // Keep parsing until the buffer is exhausted, so a framing
+                // error after valid commands is reported in the same batch.
+                loop {
+                    match chunking_service.next(input) {
+                        Ok(commands) => {
+                            println!("Received {} commands", commands.len());
+                            if run_commands(commands, &server, &mut session, &mut writer, &mut output).await {
+                                break 'connection;
+                            }
+                            if let Some(attachment) = session.take_attachment() {
+                                replication_stream = Some(send_resync(attachment, &mut output));
                             }
+                        },
+                        Err(StreamChunkingServiceError::IncompleteCommand) => break,
+                        Err(StreamChunkingServiceError::InvalidFormat(reason)) => {
+                            println!("Invalid format: {}", reason);
+                            let message = RespError::protocol(reason).to_string();
+                            server.stats.error_replied(&message);
+                            RespResponse::Error(message).encode(session.protocol(), &mut output);
+                            let _ = write_response(&mut writer, &server, &mut output).await;
+                            break 'connection;
                         }
-                    },
-                    Err(StreamChunkingServiceError::IncompleteCommand) => {
-                        continue;
-                    },
-                    Err(StreamChunkingServiceError::InvalidFormat) => {
-                        println!("Invalid format");
-                        return;
                     }
+                    input = &[];
                 }
             }
             Err(e) => {
                 println!("Failed to read from connection: {}", e);
+                break;
             }
         }
     }
+
+    server.create_handler().close_session(&mut session).await;
+    server.client_registry.unregister(client_id).await;
+    server.stats.client_disconnected();
 }
 
-async fn process_command(command: RespCommand, storage: &Storage) -> Result<String, String> {
-    let handler = CommandHandler::new(
-        CommandRepository::new(storage.clone()),
-        QueryRepository::new(storage.clone()),
+/// Runs a batch of parsed commands, buffering their replies. Returns true
+/// once the connection should be closed.
+async fn run_commands(
+    commands: Vec<RawCommand>,
+    server: &Server,
+    session: &mut Session,
+    writer: &mut WriteHalf<'_>,
+    output: &mut BytesMut,
+) -> bool {
+    for cmd in commands {
+        let is_quit = match RespCommand::parse(cmd) {
+            Ok(resp_command) => {
+                let is_quit = matches!(resp_command, RespCommand::Quit);
+                let response = process_command(resp_command, server, session)
+                    .await
+                    .unwrap_or_else(|e| {
+                        // Command errors were counted by the handler already.
+                        let message = e.to_string();
+                        server.stats.error_replied(&message);
+                        RespResponse::Error(message)
+                    });
+                response.encode(session.protocol(), output);
+                is_quit
+            },
+            Err(e) => {
+                session.mark_transaction_dirty();
+                let message = e.to_string();
+                server.stats.error_replied(&message);
+                RespResponse::Error(message).encode(session.protocol(), output);
+                false
+            }
+        };
+        // Large replies are flushed early to bound the buffer.
+        let should_flush = is_quit || output.len() >= OUTPUT_FLUSH_THRESHOLD;
+        if should_flush && (write_response(writer, server, output).await.is_err() || is_quit) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Queues what a replica needs to catch up after the reply to PSYNC: the
+/// snapshot of a full resynchronization or the backlog it missed. Returns
+/// the stream of writes that follows.
+fn send_resync(attachment: Attachment, output: &mut BytesMut) -> UnboundedReceiver<Bytes> {
+    match attachment.resync {
+        Resync::Full { rdb, .. } => {
+            // Sent like a bulk string, but without the trailing CRLF.
+            output.extend_from_slice(format!("${}\r\n", rdb.len()).as_bytes());
+            output.extend_from_slice(&rdb);
+        },
+        Resync::Partial { backlog, .. } => output.extend_from_slice(&backlog),
+    }
+    attachment.stream
+}
+
+/// The next writes for a replica's connection, or never for a client's.
+async fn next_replicated(stream: &mut Option<UnboundedReceiver<Bytes>>) -> Option<Bytes> {
+    match stream {
+        Some(stream) => stream.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Encodes a pub/sub or invalidation message into the connection's output.
+fn push_message(message: PubSubMessage, session: &Session, output: &mut BytesMut) {
+    // RESP2 connections only see invalidations while subscribed,
+    // which is how REDIRECT targets receive them, and cannot be told
+    // their REDIRECT target is gone.
+    if session.protocol() == ProtocolVersion::Resp2 {
+        match message {
+            PubSubMessage::Invalidate { .. } if !session.is_subscribed() => return,
+            PubSubMessage::TrackingRedirBroken { .. } => return,
+            _ => {},
+        }
+    }
+    ResponseBuilder::new()
+        .create_message(message, session.protocol())
+        .encode(session.protocol(), output);
+}
+
+/// Actively removes expired keys so they do not linger until next access.
+async fn expire_keys(server: Server) {
+    let command_repository = CommandRepository::new(
+        server.storage.clone(),
+        server.keyspace_notifier.clone(),
+        server.tracking_table.clone(),
+        server.stats.clone(),
+        server.appender.clone(),
+        server.replication.clone(),
+    );
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        let _gate = server.storage.shared_gate().await;
+        command_repository.expire_cycle().await;
+    }
+}
+
+/// Runs the save policies and scheduled background saves.
+async fn save_snapshots(server: Server) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        server.snapshotter.cron().await;
+    }
+}
+
+/// Writes logged commands no client is waiting for, e.g. deletions of
+/// expired keys, runs the `everysec` fsync and starts automatic rewrites.
+async fn flush_append_only_file(server: Server) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    loop {
+        interval.tick().await;
+        server.appender.cron();
+        // A rewrite must not start in the middle of a transaction, which
+        // would split it across files.
+        let _gate = server.storage.shared_gate().await;
+        server.appender.rewrite_if_grown().await;
+    }
+}
+
+/// Runs the link to the master this server replicates, retrying every
+/// second while it fails, until REPLICAOF changes the master.
+async fn replicate(server: Server) {
+    let mut master = server.replication.watch_master();
+    let mut replica_link = ReplicaLink::new(
+        server.replication.clone(),
+        server.storage.clone(),
+        server.create_handler(),
+        server.tracking_table.clone(),
+        server.appender.clone(),
     );
-    let handler_result = handler.handle_command(command).await;
-    let response_factory = resp_parser::domain::response_builder::ResponseBuilder::new();
+    loop {
+        let address = master.borrow_and_update().clone();
+        let Some(address) = address else {
+            if master.changed().await.is_err() {
+                return;
+            }
+            continue;
+        };
+        let port = server.config_registry.read(|config| config.port).await;
+        let link = async {
+            if let Err(e) = replica_link.run(&address, port).await {
+                println!("{}", e);
+            }
+            server.replication.set_link_state(LinkState::Connecting);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        };
+        tokio::select! {
+            _ = link => {},
+            _ = master.changed() => {},
+        }
+    }
+}
+
+/// Pings the replicas periodically. The ping is part of the stream, so it
+/// also moves the replication offset.
+async fn ping_replicas(server: Server) {
+    let mut interval = tokio::time::interval(REPLICA_PING_INTERVAL);
+    loop {
+        interval.tick().await;
+        // A transaction is streamed without anything in between.
+        let _gate = server.storage.shared_gate().await;
+        server.replication.feed(None, &[b"PING"]);
+    }
+}
+
+/// Exits on SIGINT or SIGTERM, first fsyncing the append-only file and
+/// saving a final snapshot when save policies are configured. If that save
+/// fails the server keeps running, as exiting would lose the data.
+async fn shutdown_on_signal(server: Server) {
+    let mut terminate = signal(SignalKind::terminate()).expect("installing the SIGTERM handler");
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => println!("Received SIGINT scheduling shutdown..."),
+            _ = terminate.recv() => println!("Received SIGTERM scheduling shutdown..."),
+        }
+        if server.config_registry.read(|config| !config.save.is_empty()).await {
+            println!("Saving the final RDB snapshot before exiting.");
+            while server.snapshotter.state().in_progress {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let _gate = server.storage.exclusive_gate().await;
+            if let Err(e) = server.snapshotter.save().await {
+                eprintln!("Error trying to save the DB, can't exit: {}", e);
+                continue;
+            }
+        }
+        server.appender.stop();
+        println!("Redis is now ready to exit, bye bye...");
+        std::process::exit(0);
+    }
+}
+
+async fn process_command(command: RespCommand, server: &Server, session: &mut Session) -> Result<RespResponse, RespError> {
+    let handler = server.create_handler();
+    // SAVE blocks the server like in Redis, so its snapshot is also the
+    // latest state any client has seen. PSYNC on a replica snapshots the
+    // data at an offset, which only moves between the commands it applies.
+    let exclusive = match command {
+        RespCommand::Exec | RespCommand::Save => true,
+        RespCommand::Psync { .. } => server.replication.is_replica(),
+        _ => false,
+    };
+    let handler_result = if exclusive {
+        let _gate = server.storage.exclusive_gate().await;
+        handler.handle_command(command, session).await
+    } else if matches!(command, RespCommand::Wait { .. } | RespCommand::WaitAof { .. }) {
+        // Blocks until replicas acknowledge, which must not hold up writes
+        // or the acknowledgements themselves.
+        handler.handle_command(command, session).await
+    } else {
+        let _gate = server.storage.shared_gate().await;
+        handler.handle_command(command, session).await
+    };
+    // Writes reach the append-only file before their reply is sent.
+    server.appender.flush();
+    let response_factory = ResponseBuilder::new();
     response_factory.create(handler_result)
-        .map(|resp_response| resp_response.to_resp())
 }
 
-async fn write_response(stream: &mut tokio::net::TcpStream, response: &str) {
-    use tokio::io::AsyncWriteExt;
-    stream.write_all(response.as_bytes()).await.unwrap();
+/// Writes the encoded replies and clears the buffer so it can be reused.
+async fn write_response(writer: &mut WriteHalf<'_>, server: &Server, output: &mut BytesMut) -> std::io::Result<()> {
+    writer.write_all(output).await?;
+    server.stats.net_output(output.len());
+    output.clear();
+    Ok(())
 }
\ No newline at end of file
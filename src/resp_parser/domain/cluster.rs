@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::resp_parser::domain::resp_error::RespError;
+
+pub const CLUSTER_SLOTS: u16 = 16384;
+
+/// CRC16-CCITT (XMODEM), the checksum Redis Cluster uses for key slots.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Slot of a key or shard channel, honouring `{hash tags}`.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|b| *b == b'{').and_then(|start| {
+        key[start + 1..]
+            .iter()
+            .position(|b| *b == b'}')
+            .filter(|len| *len > 0)
+            .map(|len| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) % CLUSTER_SLOTS
+}
+
+/// Which node serves each slot. Outside cluster mode every slot is local.
+/// In cluster mode a slot is local until CLUSTER SETSLOT hands it to
+/// another node, and CLUSTER ADDSLOTS takes it back. There is no cluster
+/// bus, so nodes are known by their `host:port` only.
+#[derive(Clone, Default)]
+pub struct ClusterState {
+    enabled: bool,
+    /// Slots served by other nodes, mapped to their `host:port`.
+    remote_slots: Arc<Mutex<HashMap<u16, String>>>,
+}
+
+impl ClusterState {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The `redis_mode` INFO and HELLO report.
+    pub fn mode(&self) -> &'static str {
+        if self.enabled { "cluster" } else { "standalone" }
+    }
+
+    /// The `MOVED` redirection for a slot this node does not serve.
+    pub fn redirection(&self, slot: u16) -> Option<RespError> {
+        if !self.enabled {
+            return None;
+        }
+        self.remote_slots
+            .lock()
+            .unwrap()
+            .get(&slot)
+            .map(|address| RespError::Moved { slot, address: address.clone() })
+    }
+
+    /// Hands `slot` to the node at `address`.
+    pub fn assign(&self, slot: u16, address: String) {
+        self.remote_slots.lock().unwrap().insert(slot, address);
+    }
+
+    /// Serves `slots` on this node, or reports the first one it serves
+    /// already, in which case none is taken.
+    pub fn add_slots(&self, slots: &[u16]) -> Result<(), u16> {
+        let mut remote_slots = self.remote_slots.lock().unwrap();
+        if let Some(slot) = slots.iter().find(|slot| !remote_slots.contains_key(slot)) {
+            return Err(*slot);
+        }
+        for slot in slots {
+            remote_slots.remove(slot);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_hash_slot() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(key_hash_slot(b"foo"), 12182);
+        assert_eq!(key_hash_slot(b"{user1000}.following"), key_hash_slot(b"{user1000}.followers"));
+        assert_eq!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"foo{}{bar}"));
+        assert_ne!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"bar"));
+    }
+
+    #[test]
+    fn test_redirection() {
+        let standalone = ClusterState::default();
+        standalone.assign(12182, "10.0.0.2:6379".to_string());
+        assert_eq!(standalone.redirection(12182), None);
+
+        let cluster = ClusterState::new(true);
+        cluster.assign(12182, "10.0.0.2:6379".to_string());
+        assert_eq!(cluster.redirection(12182).map(|error| error.to_string()), Some("MOVED 12182 10.0.0.2:6379".to_string()));
+        assert_eq!(cluster.redirection(1), None);
+
+        assert_eq!(cluster.add_slots(&[12182, 1]), Err(1));
+        assert!(cluster.redirection(12182).is_some());
+        assert_eq!(cluster.add_slots(&[12182]), Ok(()));
+        assert_eq!(cluster.redirection(12182), None);
+    }
+}
@@ -1,14 +1,93 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+use bytes::Bytes;
+use crate::resp_parser::domain::cluster::{key_hash_slot, ClusterState};
+use crate::resp_parser::domain::command_table::CommandFlag;
 use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::{ProtocolVersion, RespResponse};
+use crate::resp_parser::domain::response_builder;
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::infra::memory::channel_registry::ChannelRegistry;
+use crate::resp_parser::infra::rdb::snapshotter::Snapshotter;
+use crate::resp_parser::infra::aof::appender::Appender;
 use crate::resp_parser::infra::memory::command_repository::CommandRepository;
+use crate::resp_parser::infra::memory::config_registry::ConfigRegistry;
+use crate::resp_parser::infra::memory::keyspace_notifier::KeyspaceNotifier;
 use crate::resp_parser::infra::memory::query_repository::QueryRepository;
+use crate::resp_parser::infra::memory::server_stats::ServerStats;
+use crate::resp_parser::infra::memory::tracking_table::TrackingTable;
+use crate::resp_parser::infra::memory::storage::DATABASES;
+use crate::resp_parser::infra::replication::Replication;
+
+pub mod cluster;
+pub mod connection;
+pub mod introspection;
+pub mod keyspace;
+pub mod pubsub;
+pub mod replication;
+pub mod server;
+pub mod transactions;
+
+/// The future of a command run by its executor, boxed so that executors
+/// can be trait objects.
+pub type Execution<'a> = Pin<Box<dyn Future<Output = CommandHandlerResult> + Send + 'a>>;
+
+/// Runs one command and builds its reply. Every command table entry holds
+/// the executor of its command, which the handler dispatches to.
+pub trait CommandExecutor: Sync {
+    /// Runs `command`, which is always the command of the executor's entry.
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a>;
+
+    /// The reply to a result of `execute`, once errors and `QUEUED` are
+    /// ruled out. Most commands reply `+OK`.
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::ok_reply(result)
+    }
+
+    /// Whether MULTI queues the command. The commands controlling the
+    /// transaction run right away.
+    fn is_queued(&self) -> bool {
+        true
+    }
+}
 
 pub struct CommandHandler {
     command_repository: CommandRepository,
     query_repository: QueryRepository,
+    channel_registry: ChannelRegistry,
+    shard_channel_registry: ChannelRegistry,
+    cluster: ClusterState,
+    keyspace_notifier: KeyspaceNotifier,
+    tracking_table: TrackingTable,
+    config_registry: ConfigRegistry,
+    stats: ServerStats,
+    snapshotter: Snapshotter,
+    appender: Appender,
+    replication: Replication,
 }
 
 pub enum CommandHandlerResultStatus {
-    Ok(Option<String>),
+    Ok(Option<Bytes>),
+    Integer(i64),
+    /// An array of integers, e.g. the counts of WAITAOF.
+    Integers(Vec<i64>),
+    List(Vec<Vec<u8>>),
+    /// Channel or pattern name paired with a count, e.g. PUBSUB NUMSUB.
+    Counts(Vec<(Vec<u8>, i64)>),
+    /// One confirmation per (un)subscribed name with the session's subscription count.
+    Subscriptions(Vec<(Option<Vec<u8>>, usize)>),
+    Hello {
+        client_id: u64,
+        protocol: i64,
+        mode: &'static str,
+        role: &'static str,
+    },
+    Queued,
+    /// Results of the queued commands, or `None` when a watched key changed.
+    Transaction(Option<Vec<CommandHandlerResult>>),
+    Error(RespError),
 }
 
 pub struct CommandHandlerResult {
@@ -34,39 +113,763 @@ impl CommandHandlerResult {
 }
 
 impl CommandHandler {
-    pub fn new(command_repository: CommandRepository, query_repository: QueryRepository) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        command_repository: CommandRepository,
+        query_repository: QueryRepository,
+        channel_registry: ChannelRegistry,
+        shard_channel_registry: ChannelRegistry,
+        cluster: ClusterState,
+        keyspace_notifier: KeyspaceNotifier,
+        tracking_table: TrackingTable,
+        config_registry: ConfigRegistry,
+        stats: ServerStats,
+        snapshotter: Snapshotter,
+        appender: Appender,
+        replication: Replication,
+    ) -> Self {
         CommandHandler {
             command_repository,
             query_repository,
+            channel_registry,
+            shard_channel_registry,
+            cluster,
+            keyspace_notifier,
+            tracking_table,
+            config_registry,
+            stats,
+            snapshotter,
+            appender,
+            replication,
         }
     }
 
-    pub async fn handle_command(&self, command: RespCommand) -> CommandHandlerResult {
-        match &command {
-            RespCommand::Ping { message: _ } => {
-                CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
-            },
-            RespCommand::Echo { message } => {
-                let message = message
-                    .as_ref()
-                    .map(|bytes| String::from_utf8_lossy(&bytes).to_string());
-                CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(message))
-            },
-            RespCommand::Set { key, value } => {
-                self.command_repository.set(key.clone(), value.clone()).await;
-                CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+    /// Runs a command and records it in the command statistics.
+    pub async fn handle_command(&self, command: RespCommand, session: &mut Session) -> CommandHandlerResult {
+        let name = command.name();
+        if let Some(error) = self.rejection(&command, session) {
+            self.stats.command_rejected(name);
+            return self.record_error(Self::error(command, error));
+        }
+        let started_at = Instant::now();
+        let offset = self.replication.offset();
+        // The REPLCONF GETACK of WAIT is not a write of the client.
+        let waits = matches!(command, RespCommand::Wait { .. } | RespCommand::WaitAof { .. });
+        let result = self.dispatch(command, session).await;
+        if !waits && self.replication.offset() != offset {
+            session.set_write_offset(self.replication.offset());
+        }
+        // Queued commands are counted when EXEC runs them.
+        if !matches!(result.get_status(), CommandHandlerResultStatus::Queued) {
+            self.record_call(name, started_at, &result);
+        }
+        self.record_error(result)
+    }
+
+    /// Why the command may not run in the session's current state, if so.
+    fn rejection(&self, command: &RespCommand, session: &mut Session) -> Option<RespError> {
+        // RESP3 connections can keep running any command while subscribed.
+        if session.protocol() == ProtocolVersion::Resp2
+            && session.is_subscribed()
+            && !command.is_allowed_in_subscriber_mode() {
+            let message = format!(
+                "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                command.name(),
+            );
+            return Some(RespError::Err(message));
+        }
+        // MULTI and WATCH report their own, more specific, errors.
+        if session.is_in_transaction()
+            && !matches!(command, RespCommand::Multi | RespCommand::Watch { .. })
+            && command.spec().has_flag(CommandFlag::NoMulti) {
+            session.mark_transaction_dirty();
+            return Some(RespError::err("Command not allowed inside a transaction"));
+        }
+        // A replica only takes writes from its master, and with
+        // replica-serve-stale-data off answers little while the link is
+        // down.
+        if !session.is_master_link() {
+            if command.spec().has_flag(CommandFlag::Write) && self.replication.is_read_only() {
+                session.mark_transaction_dirty();
+                return Some(RespError::ReadOnly);
+            }
+            if !command.spec().has_flag(CommandFlag::Stale) && self.replication.is_stale() {
+                session.mark_transaction_dirty();
+                return Some(RespError::MasterDown);
+            }
+        }
+        None
+    }
+
+    fn record_call(&self, name: &'static str, started_at: Instant, result: &CommandHandlerResult) {
+        let failed = matches!(result.get_status(), CommandHandlerResultStatus::Error(_));
+        self.stats.command_called(name, started_at.elapsed().as_micros() as u64, failed);
+    }
+
+    fn record_error(&self, result: CommandHandlerResult) -> CommandHandlerResult {
+        if let CommandHandlerResultStatus::Error(error) = result.get_status() {
+            self.stats.error_replied(&error.to_string());
+        }
+        result
+    }
+
+    /// Runs the command with the executor of its table entry, or queues it
+    /// inside MULTI. CLIENT CACHING applies to the command that follows it,
+    /// or to the whole transaction when MULTI does.
+    async fn dispatch(&self, command: RespCommand, session: &mut Session) -> CommandHandlerResult {
+        let executor = command.executor();
+        if !executor.is_queued() {
+            return executor.execute(self, command, session).await;
+        }
+        if session.is_in_transaction() {
+            session.queue(command.clone());
+            return CommandHandlerResult::new(command, CommandHandlerResultStatus::Queued);
+        }
+        let caching = matches!(command, RespCommand::ClientCaching { .. });
+        let result = executor.execute(self, command, session).await;
+        if !caching {
+            session.clear_caching();
+        }
+        result
+    }
+
+    /// Runs a command with the executor of its table entry and nothing
+    /// else, e.g. one EXEC dequeued.
+    async fn execute(&self, command: RespCommand, session: &mut Session) -> CommandHandlerResult {
+        command.executor().execute(self, command, session).await
+    }
+
+    /// Releases everything the session holds in the shared storage.
+    pub async fn close_session(&self, session: &mut Session) {
+        session.take_transaction();
+        self.unwatch_all(session).await;
+        for channel in session.channels() {
+            session.unsubscribe(&channel);
+            self.channel_registry.unsubscribe(&channel, session.client_id()).await;
+        }
+        for pattern in session.patterns() {
+            session.punsubscribe(&pattern);
+            self.channel_registry.punsubscribe(&pattern, session.client_id()).await;
+        }
+        for channel in session.shard_channels() {
+            session.sunsubscribe(&channel);
+            self.shard_channel_registry.unsubscribe(&channel, session.client_id()).await;
+        }
+        if session.tracking().is_some() {
+            session.set_tracking(None);
+            self.tracking_table.disable(session.client_id()).await;
+        }
+        if session.is_replica() {
+            self.replication.detach(session.client_id());
+        }
+    }
+
+    async fn unwatch_all(&self, session: &mut Session) {
+        let watched_keys = session.take_watched_keys();
+        if watched_keys.is_empty() {
+            return;
+        }
+        self.command_repository
+            .unwatch(watched_keys.into_iter().map(|watched| (watched.db, watched.key)))
+            .await;
+    }
+
+    /// Shard channels must all live in one slot served by this node.
+    fn shard_redirection(&self, channels: &[Vec<u8>]) -> Option<RespError> {
+        if !self.cluster.is_enabled() || channels.is_empty() {
+            return None;
+        }
+        let slot = key_hash_slot(&channels[0]);
+        if channels.iter().any(|channel| key_hash_slot(channel) != slot) {
+            return Some(RespError::CrossSlot);
+        }
+        self.cluster.redirection(slot)
+    }
+
+    fn is_valid_client_name(name: &[u8]) -> bool {
+        name.iter().all(|c| (b'!'..=b'~').contains(c))
+    }
+
+    fn db_index(index: i64) -> Option<usize> {
+        usize::try_from(index).ok().filter(|index| *index < DATABASES)
+    }
+
+    fn error(command: RespCommand, error: RespError) -> CommandHandlerResult {
+        CommandHandlerResult::new(command, CommandHandlerResultStatus::Error(error))
+    }
+
+    /// The result of an executor given a command other than its own, which
+    /// the command table rules out.
+    fn mismatched(command: RespCommand) -> CommandHandlerResult {
+        let message = format!("Mismatched command for {}", command.name().replace('|', " ").to_uppercase());
+        Self::error(command, RespError::Err(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_parser::domain::pubsub_message::PubSubMessage;
+    use crate::resp_parser::domain::resp_command::SetExpiry;
+    use crate::resp_parser::domain::tracking::TrackingOptions;
+    use crate::resp_parser::infra::memory::storage::{unix_time_ms, Storage};
+    use crate::resp_parser::infra::rdb::crc64::crc64;
+    use crate::resp_parser::infra::rdb::SAVE_VERSION;
+    use crate::resp_parser::infra::replication::MasterAddress;
+
+    /// Builds a handler over a storage, with a default for every part a
+    /// test does not set.
+    struct HandlerFixture<'a> {
+        storage: &'a Storage,
+        channel_registry: ChannelRegistry,
+        cluster: ClusterState,
+    }
+
+    impl<'a> HandlerFixture<'a> {
+        fn new(storage: &'a Storage) -> Self {
+            Self {
+                storage,
+                channel_registry: ChannelRegistry::default(),
+                cluster: ClusterState::default(),
+            }
+        }
+
+        fn channel_registry(mut self, channel_registry: ChannelRegistry) -> Self {
+            self.channel_registry = channel_registry;
+            self
+        }
+
+        fn cluster(mut self, cluster: ClusterState) -> Self {
+            self.cluster = cluster;
+            self
+        }
+
+        fn build(self) -> CommandHandler {
+            let storage = self.storage;
+            let keyspace_notifier = KeyspaceNotifier::new(self.channel_registry.clone());
+            let tracking_table = TrackingTable::default();
+            let stats = ServerStats::default();
+            let snapshotter = Snapshotter::new(storage.clone(), ConfigRegistry::default());
+            let appender = Appender::new(storage.clone(), ConfigRegistry::default());
+            let replication = Replication::new(storage.clone());
+            CommandHandler::new(
+                CommandRepository::new(
+                    storage.clone(),
+                    keyspace_notifier.clone(),
+                    tracking_table.clone(),
+                    stats.clone(),
+                    appender.clone(),
+                    replication.clone(),
+                ),
+                QueryRepository::new(storage.clone(), keyspace_notifier.clone(), tracking_table.clone(), stats.clone()),
+                self.channel_registry,
+                ChannelRegistry::sharded(),
+                self.cluster,
+                keyspace_notifier,
+                tracking_table,
+                ConfigRegistry::default(),
+                stats,
+                snapshotter,
+                appender,
+                replication,
+            )
+        }
+    }
+
+    fn handler(storage: &Storage) -> CommandHandler {
+        HandlerFixture::new(storage).build()
+    }
+
+    /// The value of `key` in database 0, read past the handler.
+    async fn stored(storage: Storage, key: &str) -> Result<Option<Bytes>, RespError> {
+        QueryRepository::new(storage, KeyspaceNotifier::default(), TrackingTable::default(), ServerStats::default())
+            .get(0, key.as_bytes().to_vec(), None)
+            .await
+    }
+
+    fn repository(storage: &Storage) -> CommandRepository {
+        let appender = Appender::new(storage.clone(), ConfigRegistry::default());
+        CommandRepository::new(
+            storage.clone(),
+            KeyspaceNotifier::default(),
+            TrackingTable::default(),
+            ServerStats::default(),
+            appender,
+            Replication::new(storage.clone()),
+        )
+    }
+
+    fn new_session() -> Session {
+        let (subscriber, _) = tokio::sync::mpsc::unbounded_channel();
+        Session::new(1, subscriber)
+    }
+
+    fn set(key: &str, value: &str) -> RespCommand {
+        RespCommand::Set { key: key.as_bytes().to_vec(), value: Bytes::copy_from_slice(value.as_bytes()), expiry: None }
+    }
+
+    fn report(result: CommandHandlerResult) -> String {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(Some(text)) => String::from_utf8_lossy(text).to_string(),
+            _ => panic!("Unexpected status"),
+        }
+    }
+
+    fn is_aborted(result: &CommandHandlerResult) -> bool {
+        matches!(result.get_status(), CommandHandlerResultStatus::Transaction(None))
+    }
+
+    async fn watch_and_exec(storage: &Storage, concurrent: RespCommand) -> CommandHandlerResult {
+        let handler = handler(storage);
+        let mut session = new_session();
+        let mut other = new_session();
+        handler.handle_command(RespCommand::Watch { keys: vec![b"balance".to_vec()] }, &mut session).await;
+        handler.handle_command(concurrent, &mut other).await;
+        handler.handle_command(RespCommand::Multi, &mut session).await;
+        handler.handle_command(set("balance", "90"), &mut session).await;
+        handler.handle_command(RespCommand::Exec, &mut session).await
+    }
+
+    #[tokio::test]
+    async fn test_exec_runs_queued_commands() {
+        let storage = Storage::default();
+        let result = watch_and_exec(&storage, set("other", "1")).await;
+        match result.get_status() {
+            CommandHandlerResultStatus::Transaction(Some(results)) => assert_eq!(results.len(), 1),
+            _ => panic!("Unexpected status"),
+        }
+        assert_eq!(stored(storage, "balance").await, Ok(Some(Bytes::from_static(b"90"))));
+    }
+
+    #[tokio::test]
+    async fn test_exec_aborts_when_watched_key_changed() {
+        let storage = Storage::default();
+        let result = watch_and_exec(&storage, set("balance", "100")).await;
+        assert!(is_aborted(&result));
+        assert_eq!(stored(storage, "balance").await, Ok(Some(Bytes::from_static(b"100"))));
+    }
+
+    #[tokio::test]
+    async fn test_exec_aborts_after_flushdb_and_swapdb() {
+        let storage = Storage::default();
+        repository(&storage).set(0, b"balance".to_vec(), Bytes::from_static(b"1"), None, None).await;
+        assert!(is_aborted(&watch_and_exec(&storage, RespCommand::FlushDb).await));
+
+        let storage = Storage::default();
+        repository(&storage).set(1, b"balance".to_vec(), Bytes::from_static(b"1"), None, None).await;
+        assert!(is_aborted(&watch_and_exec(&storage, RespCommand::SwapDb { first: 0, second: 1 }).await));
+
+        let storage = Storage::default();
+        assert!(!is_aborted(&watch_and_exec(&storage, RespCommand::FlushDb).await));
+    }
+
+    #[tokio::test]
+    async fn test_exec_aborts_when_watched_key_expired() {
+        let storage = Storage::default();
+        repository(&storage)
+            .set(0, b"balance".to_vec(), Bytes::from_static(b"1"), Some(unix_time_ms() + 10), None)
+            .await;
+        let handler = handler(&storage);
+        let mut session = new_session();
+        handler.handle_command(RespCommand::Watch { keys: vec![b"balance".to_vec()] }, &mut session).await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        handler.handle_command(RespCommand::Multi, &mut session).await;
+        let result = handler.handle_command(RespCommand::Exec, &mut session).await;
+        assert!(is_aborted(&result));
+    }
+
+    #[tokio::test]
+    async fn test_del_of_an_expired_key_propagates_the_expiry() {
+        let storage = Storage::default();
+        repository(&storage)
+            .set(0, b"balance".to_vec(), Bytes::from_static(b"1"), Some(unix_time_ms() + 10), None)
+            .await;
+        let handler = handler(&storage);
+        let mut attachment = handler.replication.attach(7, "127.0.0.1".to_string(), 6380, "?", -1).await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        let result = handler.handle_command(RespCommand::Del { keys: vec![b"balance".to_vec()] }, &mut new_session()).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Integer(0)));
+        match attachment.stream.try_recv() {
+            Ok(data) => assert!(data.ends_with(b"*2\r\n$3\r\nDEL\r\n$7\r\nbalance\r\n")),
+            Err(_) => panic!("Expected the DEL in the replication stream"),
+        }
+        let info = RespCommand::Info { sections: vec!["stats".to_string()] };
+        assert!(report(handler.handle_command(info, &mut new_session()).await).contains("expired_keys:1"));
+    }
+
+    #[tokio::test]
+    async fn test_expire_cycle_samples_keys_with_an_expiry() {
+        let storage = Storage::default();
+        let repository = repository(&storage);
+        for i in 0..50 {
+            let key = format!("session:{}", i).into_bytes();
+            repository.set(3, key, Bytes::from_static(b"1"), Some(unix_time_ms() + 10), None).await;
+        }
+        repository.set(3, b"token".to_vec(), Bytes::from_static(b"1"), Some(unix_time_ms() + 60_000), None).await;
+        repository.set(3, b"name".to_vec(), Bytes::from_static(b"1"), Some(unix_time_ms() + 10), None).await;
+        repository.set(3, b"name".to_vec(), Bytes::from_static(b"1"), None, None).await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // More than a tenth of each sample expired, so the cycle samples
+        // until every expired key is gone.
+        assert_eq!(repository.expire_cycle().await, 50);
+        let mut storage_lock = storage.write().await;
+        assert_eq!(storage_lock.db(3).entries().len(), 2);
+        assert_eq!(storage_lock.db_mut(3).sample_expiring(20), vec![b"token".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_recreates_dumped_keys() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let mut session = new_session();
+        let restore = |key: &str, ttl: u64, payload: &Bytes, replace: bool| RespCommand::Restore {
+            key: key.as_bytes().to_vec(),
+            ttl,
+            payload: payload.clone(),
+            replace,
+            absttl: true,
+        };
+        let get = |key: &str| RespCommand::Get { key: key.as_bytes().to_vec() };
+        handler.handle_command(set("a", "value"), &mut session).await;
+        let dumped = handler.handle_command(RespCommand::Dump { key: b"a".to_vec() }, &mut session).await;
+        let payload = match dumped.get_status() {
+            CommandHandlerResultStatus::Ok(Some(payload)) => payload.clone(),
+            _ => panic!("Unexpected status"),
+        };
+
+        handler.handle_command(restore("b", 0, &payload, false), &mut session).await;
+        assert_eq!(report(handler.handle_command(get("b"), &mut session).await), "value");
+        let result = handler.handle_command(restore("b", 0, &payload, false), &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::BusyKey)));
+
+        // An expiry already passed leaves the replaced key deleted.
+        handler.handle_command(restore("b", 1, &payload, true), &mut session).await;
+        let result = handler.handle_command(get("b"), &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Ok(None)));
+
+        let corrupt = payload.slice(..payload.len() - 1);
+        let result = handler.handle_command(restore("c", 0, &corrupt, false), &mut session).await;
+        assert!(matches!(
+            result.get_status(),
+            CommandHandlerResultStatus::Error(RespError::Err(message))
+                if message == "DUMP payload version or checksum are wrong"
+        ));
+        let result = handler.handle_command(RespCommand::Dump { key: b"missing".to_vec() }, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_an_lzf_string_longer_than_its_data() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        // An LZF string of 6 compressed bytes claiming to hold 2^62.
+        let mut payload = vec![0, 0xc3, 6, 0x81];
+        payload.extend_from_slice(&(1u64 << 62).to_be_bytes());
+        payload.extend_from_slice(&[0x02, b'a', b'b', b'c', 0x80, 0x02]);
+        payload.extend_from_slice(&(SAVE_VERSION as u16).to_le_bytes());
+        let checksum = crc64(0, &payload);
+        payload.extend_from_slice(&checksum.to_le_bytes());
+
+        let restore = RespCommand::Restore {
+            key: b"key".to_vec(),
+            ttl: 0,
+            payload: Bytes::from(payload),
+            replace: false,
+            absttl: false,
+        };
+        let result = handler.handle_command(restore, &mut new_session()).await;
+        assert!(matches!(
+            result.get_status(),
+            CommandHandlerResultStatus::Error(RespError::Err(message)) if message == "Bad data format"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exec_without_multi() {
+        let storage = Storage::default();
+        let result = handler(&storage).handle_command(RespCommand::Exec, &mut new_session()).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_channel_and_pattern_subscribers() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut subscribed = Session::new(2, subscriber);
+        handler.handle_command(RespCommand::Subscribe { channels: vec![b"news".to_vec()] }, &mut subscribed).await;
+        handler.handle_command(RespCommand::PSubscribe { patterns: vec![b"n*".to_vec()] }, &mut subscribed).await;
+
+        let result = handler.handle_command(
+            RespCommand::Publish { channel: b"news".to_vec(), message: b"hi".to_vec() },
+            &mut new_session(),
+        ).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Integer(2)));
+        assert!(matches!(receiver.try_recv(), Ok(PubSubMessage::Message { .. })));
+        assert!(matches!(receiver.try_recv(), Ok(PubSubMessage::PatternMessage { .. })));
+
+        let result = handler.handle_command(RespCommand::Get { key: b"news".to_vec() }, &mut subscribed).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(_)));
+
+        handler.close_session(&mut subscribed).await;
+        let result = handler.handle_command(
+            RespCommand::Publish { channel: b"news".to_vec(), message: b"hi".to_vec() },
+            &mut new_session(),
+        ).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_spublish_reaches_only_shard_subscribers() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut subscribed = Session::new(2, subscriber);
+        handler.handle_command(RespCommand::Subscribe { channels: vec![b"orders".to_vec()] }, &mut subscribed).await;
+        let ssubscribe = RespCommand::SSubscribe { channels: vec![b"orders".to_vec()] };
+        let result = handler.handle_command(ssubscribe, &mut subscribed).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Subscriptions(counts) if counts[0].1 == 1));
+
+        let result = handler.handle_command(
+            RespCommand::SPublish { channel: b"orders".to_vec(), message: b"hi".to_vec() },
+            &mut new_session(),
+        ).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Integer(1)));
+        assert!(matches!(receiver.try_recv(), Ok(PubSubMessage::ShardMessage { .. })));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shard_channels_are_routed_by_slot_in_cluster_mode() {
+        let storage = Storage::default();
+        let handler = HandlerFixture::new(&storage).cluster(ClusterState::new(true)).build();
+        let mut session = new_session();
+        let setslot = RespCommand::ClusterSetSlot { slot: key_hash_slot(b"orders"), address: "10.0.0.2:6379".to_string() };
+        handler.handle_command(setslot, &mut session).await;
+
+        let spublish = RespCommand::SPublish { channel: b"orders".to_vec(), message: b"hi".to_vec() };
+        let result = handler.handle_command(spublish, &mut session).await;
+        match result.get_status() {
+            CommandHandlerResultStatus::Error(error) => assert_eq!(error.to_string(), "MOVED 105 10.0.0.2:6379"),
+            _ => panic!("Expected MOVED"),
+        }
+        let ssubscribe = RespCommand::SSubscribe { channels: vec![b"{user}.a".to_vec(), b"orders".to_vec()] };
+        let result = handler.handle_command(ssubscribe, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::CrossSlot)));
+        let ssubscribe = RespCommand::SSubscribe { channels: vec![b"{user}.a".to_vec(), b"{user}.b".to_vec()] };
+        let result = handler.handle_command(ssubscribe, &mut new_session()).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Subscriptions(_)));
+
+        let addslots = RespCommand::ClusterAddSlots { slots: vec![key_hash_slot(b"orders")] };
+        handler.handle_command(addslots, &mut session).await;
+        let spublish = RespCommand::SPublish { channel: b"orders".to_vec(), message: b"hi".to_vec() };
+        let result = handler.handle_command(spublish, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_keyspace_notifications_follow_configured_classes() {
+        let storage = Storage::default();
+        let channel_registry = ChannelRegistry::default();
+        let handler = HandlerFixture::new(&storage).channel_registry(channel_registry.clone()).build();
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        channel_registry.psubscribe(b"__key*@0__:*".to_vec(), 2, subscriber).await;
+        let mut session = new_session();
+
+        handler.handle_command(set("cache", "1"), &mut session).await;
+        assert!(receiver.try_recv().is_err());
+
+        let config = RespCommand::ConfigSet { parameters: vec![(b"notify-keyspace-events".to_vec(), b"KE$".to_vec())] };
+        handler.handle_command(config, &mut session).await;
+        handler.handle_command(set("cache", "2"), &mut session).await;
+        match receiver.try_recv() {
+            Ok(PubSubMessage::PatternMessage { channel, payload, .. }) => {
+                assert_eq!(channel, b"__keyspace@0__:cache".to_vec());
+                assert_eq!(payload, b"set".to_vec());
             },
-            RespCommand::Get { key } => {
-                match self.query_repository.get(key.clone()).await {
-                    Some(value) => {
-                        CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(value)))
-                    },
-                    None => {
-                        CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
-                    }
-                }
+            _ => panic!("Expected keyspace notification"),
+        }
+        match receiver.try_recv() {
+            Ok(PubSubMessage::PatternMessage { channel, payload, .. }) => {
+                assert_eq!(channel, b"__keyevent@0__:set".to_vec());
+                assert_eq!(payload, b"cache".to_vec());
             },
-            // Handle other commands here
+            _ => panic!("Expected keyevent notification"),
+        }
+        handler.handle_command(RespCommand::Get { key: b"missing".to_vec() }, &mut session).await;
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tracking_invalidates_keys_read_by_client() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut tracking = Session::new(2, subscriber);
+        let mut writer = new_session();
+
+        let options = TrackingOptions { noloop: true, ..TrackingOptions::default() };
+        handler.handle_command(RespCommand::ClientTracking { enabled: true, options }, &mut tracking).await;
+        handler.handle_command(RespCommand::Get { key: b"user:1".to_vec() }, &mut tracking).await;
+
+        handler.handle_command(set("user:2", "x"), &mut writer).await;
+        assert!(receiver.try_recv().is_err());
+        handler.handle_command(set("user:1", "x"), &mut writer).await;
+        match receiver.try_recv() {
+            Ok(PubSubMessage::Invalidate { keys: Some(keys) }) => assert_eq!(keys, vec![b"user:1".to_vec()]),
+            _ => panic!("Expected invalidation"),
         }
+        // Invalidation is one-shot until the key is read again.
+        handler.handle_command(set("user:1", "y"), &mut writer).await;
+        assert!(receiver.try_recv().is_err());
+
+        handler.handle_command(RespCommand::Get { key: b"user:1".to_vec() }, &mut tracking).await;
+        handler.handle_command(set("user:1", "z"), &mut tracking).await;
+        assert!(receiver.try_recv().is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_tracking_bcast_and_optin() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let (subscriber, mut bcast_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut bcast = Session::new(2, subscriber);
+        let (subscriber, mut optin_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut optin = Session::new(3, subscriber);
+        let mut writer = new_session();
+
+        let options = TrackingOptions { bcast: true, prefixes: vec![b"user:".to_vec()], ..TrackingOptions::default() };
+        handler.handle_command(RespCommand::ClientTracking { enabled: true, options }, &mut bcast).await;
+        let options = TrackingOptions { optin: true, ..TrackingOptions::default() };
+        handler.handle_command(RespCommand::ClientTracking { enabled: true, options }, &mut optin).await;
+        handler.handle_command(RespCommand::Get { key: b"user:1".to_vec() }, &mut optin).await;
+        handler.handle_command(RespCommand::ClientCaching { enabled: true }, &mut optin).await;
+        handler.handle_command(RespCommand::Get { key: b"user:2".to_vec() }, &mut optin).await;
+
+        handler.handle_command(set("user:1", "x"), &mut writer).await;
+        handler.handle_command(set("user:2", "x"), &mut writer).await;
+        handler.handle_command(set("order:1", "x"), &mut writer).await;
+        assert!(matches!(
+            bcast_receiver.try_recv(),
+            Ok(PubSubMessage::Invalidate { keys: Some(keys) }) if keys[0] == b"user:1"
+        ));
+        assert!(matches!(
+            bcast_receiver.try_recv(),
+            Ok(PubSubMessage::Invalidate { keys: Some(keys) }) if keys[0] == b"user:2"
+        ));
+        assert!(bcast_receiver.try_recv().is_err());
+        assert!(matches!(
+            optin_receiver.try_recv(),
+            Ok(PubSubMessage::Invalidate { keys: Some(keys) }) if keys[0] == b"user:2"
+        ));
+        assert!(optin_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hello_switches_protocol_and_lifts_subscriber_restrictions() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let mut session = new_session();
+
+        let hello = |protocol| RespCommand::Hello { protocol: Some(protocol), auth: None, name: Some(b"app".to_vec()) };
+        let result = handler.handle_command(hello(4), &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::NoProto)));
+        assert_eq!(session.protocol(), ProtocolVersion::Resp2);
+
+        let result = handler.handle_command(hello(3), &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Hello { protocol: 3, .. }));
+        assert_eq!(session.name(), Some(b"app".as_slice()));
+
+        handler.handle_command(RespCommand::Subscribe { channels: vec![b"news".to_vec()] }, &mut session).await;
+        let result = handler.handle_command(RespCommand::Get { key: b"news".to_vec() }, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Ok(None)));
+
+        handler.handle_command(RespCommand::Reset, &mut session).await;
+        assert_eq!(session.protocol(), ProtocolVersion::Resp2);
+        assert_eq!(session.name(), None);
+    }
+
+    #[tokio::test]
+    async fn test_info_reports_keyspace_and_command_stats() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let mut session = new_session();
+        handler.handle_command(set("a", "1"), &mut session).await;
+        handler.handle_command(RespCommand::Get { key: b"a".to_vec() }, &mut session).await;
+        handler.handle_command(RespCommand::Get { key: b"b".to_vec() }, &mut session).await;
+        handler.handle_command(RespCommand::Multi, &mut session).await;
+        handler.handle_command(RespCommand::Subscribe { channels: vec![b"news".to_vec()] }, &mut session).await;
+        handler.handle_command(RespCommand::Discard, &mut session).await;
+
+        let info = |sections: &[&str]| RespCommand::Info { sections: sections.iter().map(|s| s.to_string()).collect() };
+        let text = report(handler.handle_command(info(&["stats", "keyspace", "commandstats"]), &mut session).await);
+        assert!(text.starts_with("# Stats\r\n"));
+        assert!(text.contains("keyspace_hits:1\r\nkeyspace_misses:1\r\n"));
+        assert!(text.contains("\r\n# Keyspace\r\ndb0:keys=1,expires=0,avg_ttl=0\r\n"));
+        assert!(text.contains("cmdstat_get:calls=2,"));
+        assert!(text.contains("cmdstat_subscribe:calls=0,usec=0,usec_per_call=0.00,rejected_calls=1,failed_calls=0"));
+        assert!(!text.contains("cmdstat_info"));
+
+        handler.handle_command(RespCommand::ConfigResetStat, &mut session).await;
+        let text = report(handler.handle_command(info(&["commandstats"]), &mut session).await);
+        assert!(text.starts_with("# Commandstats\r\ncmdstat_config|resetstat:calls=1,"));
+        assert!(!text.contains("cmdstat_get"));
+    }
+
+    #[tokio::test]
+    async fn test_replica_takes_writes_only_from_its_master() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        handler.replication.set_master(Some(MasterAddress { host: "127.0.0.1".to_string(), port: 6379 }));
+        let mut session = new_session();
+        let result = handler.handle_command(set("a", "1"), &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::ReadOnly)));
+        handler.handle_command(RespCommand::Multi, &mut session).await;
+        handler.handle_command(set("a", "1"), &mut session).await;
+        let result = handler.handle_command(RespCommand::Exec, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::ExecAbort)));
+
+        // Keys the master set expire on reads only, until the master
+        // deletes them.
+        let (subscriber, _) = tokio::sync::mpsc::unbounded_channel();
+        let mut master = Session::new_master_link(subscriber);
+        let expiry = Some(SetExpiry::Absolute(unix_time_ms() - 1));
+        let expired = RespCommand::Set { key: b"a".to_vec(), value: Bytes::from_static(b"1"), expiry };
+        let result = handler.handle_command(expired, &mut master).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Ok(_)));
+        assert_eq!(handler.command_repository.expire_cycle().await, 0);
+        let result = handler.handle_command(RespCommand::Get { key: b"a".to_vec() }, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Ok(None)));
+        assert!(storage.read().await.db(0).entries().contains_key(b"a".as_slice()));
+
+        // The link never came up, so with stale data off only commands
+        // flagged stale still run.
+        handler.replication.set_serve_stale_data(false);
+        let result = handler.handle_command(RespCommand::Get { key: b"a".to_vec() }, &mut session).await;
+        assert!(matches!(result.get_status(), CommandHandlerResultStatus::Error(RespError::MasterDown)));
+        let info = RespCommand::Info { sections: vec!["replication".to_string()] };
+        assert!(report(handler.handle_command(info, &mut session).await).contains("slave_read_only:1"));
+    }
+
+    #[tokio::test]
+    async fn test_hello_reports_the_replication_role() {
+        let storage = Storage::default();
+        let handler = handler(&storage);
+        let mut session = new_session();
+        let hello = || RespCommand::Hello { protocol: None, auth: None, name: None };
+        let result = handler.handle_command(hello(), &mut session).await;
+        assert!(matches!(
+            result.get_status(),
+            CommandHandlerResultStatus::Hello { role: "master", mode: "standalone", .. }
+        ));
+
+        handler.replication.set_master(Some(MasterAddress { host: "127.0.0.1".to_string(), port: 6379 }));
+        let result = handler.handle_command(hello(), &mut session).await;
+        assert!(matches!(
+            result.get_status(),
+            CommandHandlerResultStatus::Hello { role: "replica", mode: "standalone", .. }
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! Cluster commands: CLUSTER KEYSLOT, ADDSLOTS and SETSLOT.
+
+use crate::resp_parser::domain::cluster::key_hash_slot;
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder;
+use crate::resp_parser::domain::session::Session;
+
+impl CommandHandler {
+    /// The error of the CLUSTER commands outside cluster mode.
+    fn cluster_disabled(&self) -> Option<RespError> {
+        (!self.cluster.is_enabled()).then(|| RespError::err("This instance has cluster support disabled"))
+    }
+}
+
+pub struct ClusterKeySlot;
+
+impl CommandExecutor for ClusterKeySlot {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClusterKeySlot { key } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.cluster_disabled() {
+                return CommandHandler::error(command, error);
+            }
+            let slot = key_hash_slot(key);
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(slot as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct ClusterAddSlots;
+
+impl CommandExecutor for ClusterAddSlots {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClusterAddSlots { slots } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.cluster_disabled() {
+                return CommandHandler::error(command, error);
+            }
+            match handler.cluster.add_slots(slots) {
+                Ok(()) => CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)),
+                Err(slot) => CommandHandler::error(command, RespError::Err(format!("Slot {} is already busy", slot))),
+            }
+        })
+    }
+}
+
+pub struct ClusterSetSlot;
+
+impl CommandExecutor for ClusterSetSlot {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClusterSetSlot { slot, address } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.cluster_disabled() {
+                return CommandHandler::error(command, error);
+            }
+            handler.cluster.assign(*slot, address.clone());
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
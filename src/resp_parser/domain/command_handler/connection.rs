@@ -0,0 +1,290 @@
+//! Connection commands: PING, ECHO, SELECT, QUIT, RESET, HELLO and CLIENT.
+
+use bytes::Bytes;
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::info;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::{ProtocolVersion, RespResponse};
+use crate::resp_parser::domain::response_builder::{self, mismatched};
+use crate::resp_parser::domain::session::Session;
+
+pub struct Ping;
+
+impl CommandExecutor for Ping {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Ping { message } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let message = message.clone();
+            // Subscribed RESP2 connections can only take replies shaped like
+            // the messages they receive.
+            if session.is_subscribed() && session.protocol() == ProtocolVersion::Resp2 {
+                let message = message.unwrap_or_default().to_vec();
+                return CommandHandlerResult::new(command, CommandHandlerResultStatus::List(vec![b"pong".to_vec(), message]));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(message))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(Some(message)) => Ok(RespResponse::echo(message.clone())),
+            CommandHandlerResultStatus::Ok(None) => Ok(RespResponse::pong()),
+            CommandHandlerResultStatus::List(items) => Ok(RespResponse::bulk_array(items.clone())),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct Echo;
+
+impl CommandExecutor for Echo {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Echo { message } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let message = message.clone();
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(message))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(Some(message)) => Ok(RespResponse::echo(message.clone())),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct Select;
+
+impl CommandExecutor for Select {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Select { index } = command else {
+                return CommandHandler::mismatched(command);
+            };
+            match CommandHandler::db_index(index) {
+                Some(db) => {
+                    session.select(db);
+                    CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+                },
+                None => CommandHandler::error(command, RespError::err("DB index is out of range")),
+            }
+        })
+    }
+}
+
+pub struct Quit;
+
+impl CommandExecutor for Quit {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move { CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)) })
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Reset;
+
+impl CommandExecutor for Reset {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            handler.close_session(session).await;
+            session.select(0);
+            session.set_protocol(ProtocolVersion::Resp2);
+            session.set_name(None);
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::SimpleString("RESET".to_string())),
+            _ => Err(mismatched(result)),
+        }
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Hello;
+
+impl CommandExecutor for Hello {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Hello { protocol, auth, name } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let protocol = match protocol {
+                None => session.protocol(),
+                Some(2) => ProtocolVersion::Resp2,
+                Some(3) => ProtocolVersion::Resp3,
+                Some(_) => return CommandHandler::error(command, RespError::NoProto),
+            };
+            // Only the default user exists and it has no password.
+            if auth.as_ref().is_some_and(|(username, _)| username.as_slice() != b"default") {
+                return CommandHandler::error(command, RespError::WrongPass);
+            }
+            if let Some(name) = name {
+                if !CommandHandler::is_valid_client_name(name) {
+                    let message = "Client names cannot contain spaces, newlines or special characters.";
+                    return CommandHandler::error(command, RespError::err(message));
+                }
+                session.set_name((!name.is_empty()).then(|| name.clone()));
+            }
+            session.set_protocol(protocol);
+            let status = CommandHandlerResultStatus::Hello {
+                client_id: session.client_id(),
+                protocol: protocol.number(),
+                mode: handler.cluster.mode(),
+                role: if handler.replication.is_replica() { "replica" } else { "master" },
+            };
+            CommandHandlerResult::new(command, status)
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Hello { client_id, protocol, mode, role } => Ok(RespResponse::Map(vec![
+                (RespResponse::bulk("server"), RespResponse::bulk("redis")),
+                (RespResponse::bulk("version"), RespResponse::bulk(info::REDIS_VERSION)),
+                (RespResponse::bulk("proto"), RespResponse::Integer(*protocol)),
+                (RespResponse::bulk("id"), RespResponse::Integer(*client_id as i64)),
+                (RespResponse::bulk("mode"), RespResponse::bulk(*mode)),
+                (RespResponse::bulk("role"), RespResponse::bulk(*role)),
+                (RespResponse::bulk("modules"), RespResponse::Array(Some(Vec::new()))),
+            ])),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct ClientId;
+
+impl CommandExecutor for ClientId {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(session.client_id() as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct ClientGetRedir;
+
+impl CommandExecutor for ClientGetRedir {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let redirect = match session.tracking() {
+                Some(options) => options.redirect.map(|id| id as i64).unwrap_or(0),
+                None => -1,
+            };
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(redirect))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct ClientTracking;
+
+impl CommandExecutor for ClientTracking {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClientTracking { enabled, options } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if !*enabled {
+                if session.tracking().is_some() {
+                    session.set_tracking(None);
+                    handler.tracking_table.disable(session.client_id()).await;
+                }
+                return CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None));
+            }
+            if !handler.tracking_table.enable(session.client_id(), options.clone(), session.subscriber()).await {
+                let message = "The client ID you want redirect to does not exist";
+                return CommandHandler::error(command, RespError::err(message));
+            }
+            session.set_tracking(Some(options.clone()));
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct ClientCaching;
+
+impl CommandExecutor for ClientCaching {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClientCaching { enabled } = command else {
+                return CommandHandler::mismatched(command);
+            };
+            match session.tracking() {
+                Some(options) if enabled && options.optin => {},
+                Some(options) if !enabled && options.optout => {},
+                Some(options) if options.optin || options.optout => {
+                    let message = if enabled {
+                        "CLIENT CACHING YES is only valid when tracking is enabled in OPTIN mode."
+                    } else {
+                        "CLIENT CACHING NO is only valid when tracking is enabled in OPTOUT mode."
+                    };
+                    return CommandHandler::error(command, RespError::err(message));
+                },
+                _ => return CommandHandler::error(
+                    command,
+                    RespError::err("CLIENT CACHING can be called only when the client is in tracking mode with OPTIN or OPTOUT mode enabled"),
+                ),
+            }
+            session.set_caching(enabled);
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct ClientSetName;
+
+impl CommandExecutor for ClientSetName {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ClientSetName { name } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if !CommandHandler::is_valid_client_name(name) {
+                let message = "Client names cannot contain spaces, newlines or special characters.";
+                return CommandHandler::error(command, RespError::err(message));
+            }
+            session.set_name((!name.is_empty()).then(|| name.clone()));
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct ClientGetName;
+
+impl CommandExecutor for ClientGetName {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let name = session.name().map(Bytes::copy_from_slice);
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(name))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::bulk_reply(result)
+    }
+}
@@ -0,0 +1,222 @@
+//! COMMAND and its subcommands, which describe the command table.
+
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::command_table::{self, CommandSpec, KeySpec};
+use crate::resp_parser::domain::glob_pattern::glob_match;
+use crate::resp_parser::domain::resp_command::{CommandListFilter, RespCommand};
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder::{self, mismatched};
+use crate::resp_parser::domain::session::Session;
+
+pub struct Command;
+
+impl CommandExecutor for Command {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move { CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)) })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::Array(Some(
+                command_table::COMMAND_TABLE.iter().map(command_info).collect(),
+            ))),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct CommandCount;
+
+impl CommandExecutor for CommandCount {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let count = command_table::COMMAND_TABLE.len() as i64;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(count))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct CommandDocs;
+
+impl CommandExecutor for CommandDocs {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move { CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)) })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        let (RespCommand::CommandDocs { names }, CommandHandlerResultStatus::Ok(_)) =
+            (result.get_resp_command(), result.get_status()) else {
+            return Err(mismatched(result));
+        };
+        let specs: Vec<&CommandSpec> = if names.is_empty() {
+            command_table::COMMAND_TABLE.iter().collect()
+        } else {
+            names
+                .iter()
+                .filter_map(|name| command_table::find(&String::from_utf8_lossy(name)))
+                .collect()
+        };
+        Ok(RespResponse::Map(
+            specs.into_iter().map(|spec| (RespResponse::bulk(spec.name), command_docs(spec))).collect(),
+        ))
+    }
+}
+
+pub struct CommandGetKeys;
+
+impl CommandExecutor for CommandGetKeys {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::CommandGetKeys { arguments } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let Ok(spec) = command_table::resolve(arguments) else {
+                return CommandHandler::error(command, RespError::err("Invalid command specified"));
+            };
+            if !spec.accepts(arguments.len()) {
+                return CommandHandler::error(command, RespError::err("Invalid number of arguments specified for command"));
+            }
+            let keys: Vec<Vec<u8>> = spec.keys(arguments).into_iter().map(|key| key.to_vec()).collect();
+            if keys.is_empty() {
+                return CommandHandler::error(command, RespError::err("The command has no key arguments"));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::List(keys))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::list_reply(result)
+    }
+}
+
+pub struct CommandInfo;
+
+impl CommandExecutor for CommandInfo {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move { CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)) })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        let (RespCommand::CommandInfo { names }, CommandHandlerResultStatus::Ok(_)) =
+            (result.get_resp_command(), result.get_status()) else {
+            return Err(mismatched(result));
+        };
+        if names.is_empty() {
+            return Ok(RespResponse::Array(Some(command_table::COMMAND_TABLE.iter().map(command_info).collect())));
+        }
+        Ok(RespResponse::Array(Some(
+            names
+                .iter()
+                .map(|name| command_table::find(&String::from_utf8_lossy(name)).map_or(RespResponse::Null, command_info))
+                .collect(),
+        )))
+    }
+}
+
+pub struct CommandList;
+
+impl CommandExecutor for CommandList {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::CommandList { filter } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let names = command_table::entries()
+                .filter(|spec| match filter {
+                    None => true,
+                    Some(CommandListFilter::Module) => false,
+                    Some(CommandListFilter::AclCategory(category)) => spec.acl_categories
+                        .iter()
+                        .any(|name| name.as_bytes().eq_ignore_ascii_case(category)),
+                    Some(CommandListFilter::Pattern(pattern)) => glob_match(pattern, spec.name.as_bytes(), true),
+                })
+                .map(|spec| spec.name.as_bytes().to_vec())
+                .collect();
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::List(names))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::list_reply(result)
+    }
+}
+
+/// One COMMAND INFO entry: name, arity, flags, the legacy first key, last
+/// key and step, ACL categories, tips, key specifications and subcommands.
+fn command_info(spec: &CommandSpec) -> RespResponse {
+    let (first_key, last_key, step) = spec.legacy_key_range();
+    RespResponse::Array(Some(vec![
+        RespResponse::bulk(spec.name),
+        RespResponse::Integer(spec.arity as i64),
+        RespResponse::Set(spec.flags.iter().map(|flag| RespResponse::SimpleString(flag.name().to_string())).collect()),
+        RespResponse::Integer(first_key),
+        RespResponse::Integer(last_key),
+        RespResponse::Integer(step),
+        RespResponse::Set(
+            spec.acl_categories
+                .iter()
+                .map(|category| RespResponse::SimpleString(format!("@{}", category)))
+                .collect(),
+        ),
+        RespResponse::Array(Some(Vec::new())),
+        RespResponse::Array(Some(spec.key_specs.iter().map(key_spec).collect())),
+        RespResponse::Array(Some(spec.subcommands.iter().map(command_info).collect())),
+    ]))
+}
+
+fn key_spec(key_spec: &KeySpec) -> RespResponse {
+    RespResponse::Map(vec![
+        (
+            RespResponse::bulk("flags"),
+            RespResponse::Set(key_spec.flags.iter().map(|flag| RespResponse::SimpleString(flag.to_string())).collect()),
+        ),
+        (
+            RespResponse::bulk("begin_search"),
+            RespResponse::Map(vec![
+                (RespResponse::bulk("type"), RespResponse::bulk("index")),
+                (
+                    RespResponse::bulk("spec"),
+                    RespResponse::Map(vec![(RespResponse::bulk("index"), RespResponse::Integer(key_spec.begin_index as i64))]),
+                ),
+            ]),
+        ),
+        (
+            RespResponse::bulk("find_keys"),
+            RespResponse::Map(vec![
+                (RespResponse::bulk("type"), RespResponse::bulk("range")),
+                (
+                    RespResponse::bulk("spec"),
+                    RespResponse::Map(vec![
+                        (RespResponse::bulk("lastkey"), RespResponse::Integer(key_spec.last_key as i64)),
+                        (RespResponse::bulk("keystep"), RespResponse::Integer(key_spec.step as i64)),
+                        (RespResponse::bulk("limit"), RespResponse::Integer(0)),
+                    ]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+/// One COMMAND DOCS entry, with the subcommands' docs nested under it.
+fn command_docs(spec: &CommandSpec) -> RespResponse {
+    let mut docs = vec![
+        (RespResponse::bulk("summary"), RespResponse::bulk(spec.summary)),
+        (RespResponse::bulk("since"), RespResponse::bulk(spec.since)),
+        (RespResponse::bulk("group"), RespResponse::bulk(spec.group)),
+    ];
+    if !spec.subcommands.is_empty() {
+        let subcommands = spec.subcommands
+            .iter()
+            .map(|subcommand| (RespResponse::bulk(subcommand.name), command_docs(subcommand)))
+            .collect();
+        docs.push((RespResponse::bulk("subcommands"), RespResponse::Map(subcommands)));
+    }
+    RespResponse::Map(docs)
+}
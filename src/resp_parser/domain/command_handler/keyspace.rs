@@ -0,0 +1,150 @@
+//! Keyspace commands: SET, GET, DEL, DUMP, RESTORE, FLUSHDB and SWAPDB.
+
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::resp_command::{RespCommand, SetExpiry};
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder::{self, mismatched};
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::infra::memory::storage::unix_time_ms;
+
+pub struct Set;
+
+impl CommandExecutor for Set {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Set { key, value, expiry } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let expires_at = expiry.as_ref().map(|expiry| match expiry {
+                SetExpiry::Relative(ms) => unix_time_ms().saturating_add(*ms),
+                SetExpiry::Absolute(at) => *at,
+            });
+            handler.command_repository
+                .set(session.db(), key.clone(), value.clone(), expires_at, Some(session.client_id()))
+                .await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::set()),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct Get;
+
+impl CommandExecutor for Get {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Get { key } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let tracked_by = session.tracks_reads().then(|| session.client_id());
+            match handler.query_repository.get(session.db(), key.clone(), tracked_by).await {
+                Ok(value) => CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(value)),
+                Err(error) => CommandHandler::error(command, error),
+            }
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::bulk_reply(result)
+    }
+}
+
+pub struct Del;
+
+impl CommandExecutor for Del {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Del { keys } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let deleted = handler.command_repository.del(session.db(), keys, Some(session.client_id())).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(deleted as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct Dump;
+
+impl CommandExecutor for Dump {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Dump { key } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let tracked_by = session.tracks_reads().then(|| session.client_id());
+            let payload = handler.query_repository.dump(session.db(), key, tracked_by).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(payload))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::bulk_reply(result)
+    }
+}
+
+pub struct Restore;
+
+impl CommandExecutor for Restore {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Restore { key, ttl, payload, replace, absttl } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let expires_at = match (*ttl, *absttl) {
+                (0, _) => None,
+                (at, true) => Some(at),
+                (ms, false) => Some(unix_time_ms().saturating_add(ms)),
+            };
+            let result = handler.command_repository
+                .restore(session.db(), key.clone(), payload, expires_at, *replace, Some(session.client_id()))
+                .await;
+            match result {
+                Ok(()) => CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)),
+                Err(error) => CommandHandler::error(command, error),
+            }
+        })
+    }
+}
+
+pub struct FlushDb;
+
+impl CommandExecutor for FlushDb {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            handler.command_repository.flush_db(session.db()).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct SwapDb;
+
+impl CommandExecutor for SwapDb {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::SwapDb { first, second } = command else {
+                return CommandHandler::mismatched(command);
+            };
+            match (CommandHandler::db_index(first), CommandHandler::db_index(second)) {
+                (Some(first), Some(second)) => {
+                    handler.command_repository.swap_db(first, second).await;
+                    CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+                },
+                _ => CommandHandler::error(command, RespError::err("DB index is out of range")),
+            }
+        })
+    }
+}
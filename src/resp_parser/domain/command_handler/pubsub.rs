@@ -0,0 +1,312 @@
+//! Pub/Sub commands, sharded ones included.
+
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder;
+use crate::resp_parser::domain::session::Session;
+
+pub struct Subscribe;
+
+impl CommandExecutor for Subscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Subscribe { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let mut subscriptions = Vec::new();
+            for channel in channels {
+                if session.subscribe(channel.clone()) {
+                    handler.channel_registry
+                        .subscribe(channel.clone(), session.client_id(), session.subscriber())
+                        .await;
+                }
+                subscriptions.push((Some(channel.clone()), session.subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct Unsubscribe;
+
+impl CommandExecutor for Unsubscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Unsubscribe { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let channels = if channels.is_empty() { session.channels() } else { channels.clone() };
+            let mut subscriptions = Vec::new();
+            for channel in channels {
+                if session.unsubscribe(&channel) {
+                    handler.channel_registry.unsubscribe(&channel, session.client_id()).await;
+                }
+                subscriptions.push((Some(channel), session.subscription_count()));
+            }
+            if subscriptions.is_empty() {
+                subscriptions.push((None, session.subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct PSubscribe;
+
+impl CommandExecutor for PSubscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PSubscribe { patterns } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let mut subscriptions = Vec::new();
+            for pattern in patterns {
+                if session.psubscribe(pattern.clone()) {
+                    handler.channel_registry
+                        .psubscribe(pattern.clone(), session.client_id(), session.subscriber())
+                        .await;
+                }
+                subscriptions.push((Some(pattern.clone()), session.subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct PUnsubscribe;
+
+impl CommandExecutor for PUnsubscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PUnsubscribe { patterns } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let patterns = if patterns.is_empty() { session.patterns() } else { patterns.clone() };
+            let mut subscriptions = Vec::new();
+            for pattern in patterns {
+                if session.punsubscribe(&pattern) {
+                    handler.channel_registry.punsubscribe(&pattern, session.client_id()).await;
+                }
+                subscriptions.push((Some(pattern), session.subscription_count()));
+            }
+            if subscriptions.is_empty() {
+                subscriptions.push((None, session.subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct Publish;
+
+impl CommandExecutor for Publish {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Publish { channel, message } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let receivers = handler.channel_registry.publish(channel, message).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(receivers as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct SSubscribe;
+
+impl CommandExecutor for SSubscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::SSubscribe { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.shard_redirection(channels) {
+                return CommandHandler::error(command, error);
+            }
+            let mut subscriptions = Vec::new();
+            for channel in channels {
+                if session.ssubscribe(channel.clone()) {
+                    handler.shard_channel_registry
+                        .subscribe(channel.clone(), session.client_id(), session.subscriber())
+                        .await;
+                }
+                subscriptions.push((Some(channel.clone()), session.shard_subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct SUnsubscribe;
+
+impl CommandExecutor for SUnsubscribe {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::SUnsubscribe { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.shard_redirection(channels) {
+                return CommandHandler::error(command, error);
+            }
+            let channels = if channels.is_empty() { session.shard_channels() } else { channels.clone() };
+            let mut subscriptions = Vec::new();
+            for channel in channels {
+                if session.sunsubscribe(&channel) {
+                    handler.shard_channel_registry.unsubscribe(&channel, session.client_id()).await;
+                }
+                subscriptions.push((Some(channel), session.shard_subscription_count()));
+            }
+            if subscriptions.is_empty() {
+                subscriptions.push((None, session.shard_subscription_count()));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Subscriptions(subscriptions))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::subscriptions_reply(result)
+    }
+}
+
+pub struct SPublish;
+
+impl CommandExecutor for SPublish {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::SPublish { channel, message } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            if let Some(error) = handler.shard_redirection(std::slice::from_ref(channel)) {
+                return CommandHandler::error(command, error);
+            }
+            let receivers = handler.shard_channel_registry.publish(channel, message).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(receivers as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct PubSubChannels;
+
+impl CommandExecutor for PubSubChannels {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PubSubChannels { pattern } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let channels = handler.channel_registry.channels(pattern.as_deref()).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::List(channels))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::list_reply(result)
+    }
+}
+
+pub struct PubSubNumSub;
+
+impl CommandExecutor for PubSubNumSub {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PubSubNumSub { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let mut counts = Vec::new();
+            for channel in channels {
+                let count = handler.channel_registry.subscriber_count(channel).await;
+                counts.push((channel.clone(), count as i64));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Counts(counts))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::counts_reply(result)
+    }
+}
+
+pub struct PubSubNumPat;
+
+impl CommandExecutor for PubSubNumPat {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let patterns = handler.channel_registry.pattern_count().await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(patterns as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct PubSubShardChannels;
+
+impl CommandExecutor for PubSubShardChannels {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PubSubShardChannels { pattern } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let channels = handler.shard_channel_registry.channels(pattern.as_deref()).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::List(channels))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::list_reply(result)
+    }
+}
+
+pub struct PubSubShardNumSub;
+
+impl CommandExecutor for PubSubShardNumSub {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::PubSubShardNumSub { channels } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let mut counts = Vec::new();
+            for channel in channels {
+                let count = handler.shard_channel_registry.subscriber_count(channel).await;
+                counts.push((channel.clone(), count as i64));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Counts(counts))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::counts_reply(result)
+    }
+}
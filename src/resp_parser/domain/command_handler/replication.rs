@@ -0,0 +1,233 @@
+//! Replication commands: REPLICAOF, REPLCONF, PSYNC, WAIT and WAITAOF.
+
+use std::time::Duration;
+use bytes::Bytes;
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::resp_command::{ReplConfOption, RespCommand};
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder::{self, mismatched};
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::infra::replication::{LinkState, MasterAddress, Resync};
+
+impl CommandHandler {
+    /// WAIT and WAITAOF: counts the replicas that acknowledged the writes of
+    /// the session, and for WAITAOF whether this server fsynced them to its
+    /// append-only file. With `blocking` this waits until the counts are
+    /// reached or the timeout passes, asking the replicas to acknowledge
+    /// right away. It runs outside the storage gates, so the replicas'
+    /// acknowledgements keep coming in meanwhile.
+    async fn wait(&self, command: RespCommand, session: &Session, blocking: bool) -> CommandHandlerResult {
+        let (numlocal, numreplicas, timeout, aof) = match command {
+            RespCommand::Wait { numreplicas, timeout } => (0, numreplicas, timeout, false),
+            RespCommand::WaitAof { numlocal, numreplicas, timeout } => (numlocal, numreplicas, timeout, true),
+            _ => return Self::error(command, RespError::err("Mismatched command for WAIT")),
+        };
+        if self.replication.is_replica() {
+            let message = match aof {
+                false => "WAIT cannot be used with replica instances. Please also note that since Redis 4.0 if a replica is configured to be writable (which is not the default) writes to replicas are just local and are not propagated.",
+                true => "WAITAOF cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.",
+            };
+            return Self::error(command, RespError::err(message));
+        }
+        let appendonly = self.appender.state().enabled;
+        if numlocal > 0 && !appendonly {
+            return Self::error(command, RespError::err("WAITAOF cannot be used when numlocal is set but appendonly is disabled."));
+        }
+
+        let offset = session.write_offset();
+        let written = self.appender.written();
+        let mut acks = self.replication.watch_acks();
+        let mut fsynced = self.appender.watch_fsynced();
+        let deadline = (timeout > 0).then(|| tokio::time::Instant::now() + Duration::from_millis(timeout));
+        let mut timed_out = !blocking;
+        let mut requested = false;
+        let (local, replicas) = loop {
+            let (applied, replicas_fsynced) = self.replication.acknowledged(offset);
+            let replicas = if aof { replicas_fsynced } else { applied } as i64;
+            let local = (aof && appendonly && *fsynced.borrow_and_update() >= written) as i64;
+            if timed_out || (local >= numlocal && replicas >= numreplicas) {
+                break (local, replicas);
+            }
+            if !requested {
+                self.replication.request_acks();
+                requested = true;
+            }
+            tokio::select! {
+                _ = acks.changed() => {},
+                _ = fsynced.changed() => {},
+                _ = sleep_until(deadline) => timed_out = true,
+            }
+        };
+        let status = match aof {
+            false => CommandHandlerResultStatus::Integer(replicas),
+            true => CommandHandlerResultStatus::Integers(vec![local, replicas]),
+        };
+        CommandHandlerResult::new(command, status)
+    }
+}
+
+/// Sleeps until `deadline`, or for ever without one.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+pub struct ReplicaOf;
+
+impl CommandExecutor for ReplicaOf {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            if handler.cluster.is_enabled() {
+                return CommandHandler::error(command, RespError::err("REPLICAOF not allowed in cluster mode."));
+            }
+            if session.is_replica() {
+                return CommandHandler::error(command, RespError::err("Command is not valid when client is a replica."));
+            }
+            let RespCommand::ReplicaOf { master } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let address = master.clone().map(|(host, port)| MasterAddress { host, port });
+            if !handler.replication.set_master(address) {
+                let status = Bytes::from_static(b"OK Already connected to specified master");
+                return CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(status)));
+            }
+            match master {
+                Some((host, port)) => println!("REPLICAOF {}:{} enabled (user request)", host, port),
+                None => println!("MASTER MODE enabled (user request)"),
+            }
+            let master = master.clone();
+            handler.config_registry.update(|config| config.replicaof = master).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::status_reply(result)
+    }
+}
+
+pub struct ReplConf;
+
+impl CommandExecutor for ReplConf {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ReplConf { options } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            for option in options {
+                match option {
+                    ReplConfOption::ListeningPort(port) => session.announcement_mut().port = Some(*port),
+                    ReplConfOption::IpAddress(ip) => session.announcement_mut().ip = Some(ip.clone()),
+                    ReplConfOption::Capa(capability) => session.announcement_mut().capabilities.push(capability.clone()),
+                    ReplConfOption::Ack(offset) if session.is_replica() => {
+                        let aof_offset = options.iter().find_map(|option| match option {
+                            ReplConfOption::Fack(offset) => Some(*offset),
+                            _ => None,
+                        });
+                        handler.replication.acknowledge(session.client_id(), *offset, aof_offset);
+                    },
+                    // Answered by the replica's end of the link; FACK
+                    // comes with ACK.
+                    ReplConfOption::Ack(_) | ReplConfOption::Fack(_) | ReplConfOption::GetAck => {},
+                }
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        let (RespCommand::ReplConf { options }, CommandHandlerResultStatus::Ok(_)) =
+            (result.get_resp_command(), result.get_status()) else {
+            return Err(mismatched(result));
+        };
+        // Acknowledgements travel on the replication link, where a reply
+        // would corrupt the stream.
+        match options.iter().any(|option| matches!(option, ReplConfOption::Ack(_) | ReplConfOption::GetAck)) {
+            true => Ok(RespResponse::Sequence(Vec::new())),
+            false => Ok(RespResponse::ok()),
+        }
+    }
+}
+
+pub struct Psync;
+
+impl CommandExecutor for Psync {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Psync { replid, offset } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            // A replica serves no replicas of its own until its data
+            // matches its master's.
+            if handler.replication.state().link.is_some_and(|link| link.state != LinkState::Connected) {
+                return CommandHandler::error(command, RespError::NoMasterLink);
+            }
+            let ip = session.announcement().ip.clone().or_else(|| session.peer_ip().map(str::to_string));
+            let port = session.announcement().port.unwrap_or(0);
+            let attachment = handler.replication
+                .attach(session.client_id(), ip.unwrap_or_default(), port, replid, *offset)
+                .await;
+            let status = match &attachment.resync {
+                Resync::Full { replid, offset, .. } => {
+                    println!("Replica {} asks for synchronization, starting a full resync", session.client_id());
+                    format!("FULLRESYNC {} {}", replid, offset)
+                },
+                Resync::Partial { replid, backlog } => {
+                    println!(
+                        "Partial resynchronization request from replica {} accepted, sending {} bytes of backlog",
+                        session.client_id(),
+                        backlog.len(),
+                    );
+                    // Replicas that do not know replication ids can change
+                    // are not told the new one.
+                    match session.announcement().capabilities.iter().any(|capability| capability.eq_ignore_ascii_case("psync2")) {
+                        true => format!("CONTINUE {}", replid),
+                        false => "CONTINUE".to_string(),
+                    }
+                },
+            };
+            session.start_replica(attachment);
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(Bytes::from(status))))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::status_reply(result)
+    }
+}
+
+pub struct Wait;
+
+impl CommandExecutor for Wait {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        // Inside a transaction WAIT reports what is acknowledged already,
+        // without blocking.
+        Box::pin(async move { handler.wait(command, session, !session.is_running_transaction()).await })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct WaitAof;
+
+impl CommandExecutor for WaitAof {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move { handler.wait(command, session, !session.is_running_transaction()).await })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Integers(counts) => {
+                Ok(RespResponse::Array(Some(counts.iter().map(|count| RespResponse::Integer(*count)).collect())))
+            },
+            _ => Err(mismatched(result)),
+        }
+    }
+}
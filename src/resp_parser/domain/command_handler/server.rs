@@ -0,0 +1,478 @@
+//! Server commands: DEBUG PROTOCOL, INFO, persistence and CONFIG.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use bytes::Bytes;
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::config::PARAMETERS;
+use crate::resp_parser::domain::glob_pattern::glob_match;
+use crate::resp_parser::domain::info;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::{ProtocolVersion, RespResponse};
+use crate::resp_parser::domain::response_builder::{self, mismatched};
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::infra::config_file;
+use crate::resp_parser::infra::memory::server_stats;
+use crate::resp_parser::infra::rdb::snapshotter::BackgroundSave;
+use crate::resp_parser::infra::replication::LinkState;
+
+impl CommandHandler {
+    /// The INFO report for the requested sections.
+    async fn info(&self, sections: &[String]) -> String {
+        let config = self.config_registry.snapshot().await;
+        let databases = self.query_repository.database_stats().await;
+        let used_memory: u64 = databases.iter().map(|database| database.memory).sum();
+        let mut report = Vec::new();
+        for section in info::selected_sections(sections) {
+            let fields: Vec<(String, String)> = match section {
+                "server" => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                    let uptime = self.stats.uptime_in_seconds();
+                    named(vec![
+                        ("redis_version", info::REDIS_VERSION.to_string()),
+                        ("redis_mode", self.cluster.mode().to_string()),
+                        ("os", format!("{} {}", std::env::consts::OS, std::env::consts::ARCH)),
+                        ("arch_bits", (usize::BITS).to_string()),
+                        ("process_id", std::process::id().to_string()),
+                        ("run_id", self.stats.run_id().to_string()),
+                        ("tcp_port", config.port.to_string()),
+                        ("server_time_usec", now.as_micros().to_string()),
+                        ("uptime_in_seconds", uptime.to_string()),
+                        ("uptime_in_days", (uptime / 86400).to_string()),
+                        ("hz", "10".to_string()),
+                        ("executable", std::env::current_exe().map(|path| path.display().to_string()).unwrap_or_default()),
+                        ("config_file", config.config_file.clone().unwrap_or_default()),
+                    ])
+                },
+                "clients" => named(vec![
+                    ("connected_clients", self.stats.connected_clients().to_string()),
+                    ("cluster_connections", "0".to_string()),
+                    ("maxclients", "10000".to_string()),
+                    ("blocked_clients", "0".to_string()),
+                ]),
+                "memory" => {
+                    let rss = server_stats::resident_memory();
+                    let peak = self.stats.used_memory_peak(used_memory);
+                    named(vec![
+                        ("used_memory", used_memory.to_string()),
+                        ("used_memory_human", info::human_bytes(used_memory)),
+                        ("used_memory_rss", rss.to_string()),
+                        ("used_memory_rss_human", info::human_bytes(rss)),
+                        ("used_memory_peak", peak.to_string()),
+                        ("used_memory_peak_human", info::human_bytes(peak)),
+                        ("maxmemory", config.maxmemory.to_string()),
+                        ("maxmemory_human", info::human_bytes(config.maxmemory)),
+                        ("maxmemory_policy", "noeviction".to_string()),
+                        ("mem_allocator", "libc".to_string()),
+                    ])
+                },
+                "persistence" => {
+                    let save = self.snapshotter.state();
+                    let aof = self.appender.state();
+                    let mut fields = named(vec![
+                        ("loading", "0".to_string()),
+                        ("async_loading", "0".to_string()),
+                        ("rdb_changes_since_last_save", self.snapshotter.changes_since_last_save().await.to_string()),
+                        ("rdb_bgsave_in_progress", (save.in_progress as u8).to_string()),
+                        ("rdb_last_save_time", save.last_save.to_string()),
+                        ("rdb_last_bgsave_status", if save.last_status_ok { "ok" } else { "err" }.to_string()),
+                        ("rdb_last_bgsave_time_sec", save.last_bgsave_seconds.to_string()),
+                        ("rdb_saves", save.saves.to_string()),
+                        ("aof_enabled", (aof.enabled as u8).to_string()),
+                        ("aof_rewrite_in_progress", (aof.rewrite_in_progress as u8).to_string()),
+                        ("aof_rewrites", aof.rewrites.to_string()),
+                        ("aof_last_rewrite_time_sec", aof.last_rewrite_seconds.to_string()),
+                        ("aof_last_bgrewrite_status", if aof.last_rewrite_ok { "ok" } else { "err" }.to_string()),
+                        ("aof_last_write_status", if aof.last_write_ok { "ok" } else { "err" }.to_string()),
+                    ]);
+                    if aof.enabled {
+                        fields.extend(named(vec![
+                            ("aof_current_size", aof.current_size.to_string()),
+                            ("aof_base_size", aof.base_size.to_string()),
+                        ]));
+                    }
+                    fields
+                },
+                "stats" => named(vec![
+                    ("total_connections_received", self.stats.connections_received().to_string()),
+                    ("total_commands_processed", self.stats.commands_processed().to_string()),
+                    ("total_net_input_bytes", self.stats.net_input_bytes().to_string()),
+                    ("total_net_output_bytes", self.stats.net_output_bytes().to_string()),
+                    ("rejected_connections", "0".to_string()),
+                    ("expired_keys", self.stats.expired_keys().to_string()),
+                    ("evicted_keys", "0".to_string()),
+                    ("keyspace_hits", self.stats.keyspace_hits().to_string()),
+                    ("keyspace_misses", self.stats.keyspace_misses().to_string()),
+                    ("pubsub_channels", self.channel_registry.channels(None).await.len().to_string()),
+                    ("pubsub_patterns", self.channel_registry.pattern_count().await.to_string()),
+                    ("pubsub_shardchannels", self.shard_channel_registry.channels(None).await.len().to_string()),
+                    ("tracking_total_keys", self.tracking_table.key_count().await.to_string()),
+                    ("total_error_replies", self.stats.error_replies().to_string()),
+                ]),
+                "replication" => {
+                    let replication = self.replication.state();
+                    let backlog_size = self.config_registry.read(|config| config.repl_backlog_size).await;
+                    let mut fields = Vec::new();
+                    match &replication.link {
+                        None => fields.push(("role".to_string(), "master".to_string())),
+                        Some(link) => {
+                            fields.extend(named(vec![
+                                ("role", "slave".to_string()),
+                                ("master_host", link.master.host.clone()),
+                                ("master_port", link.master.port.to_string()),
+                                ("master_link_status", if link.state == LinkState::Connected { "up" } else { "down" }.to_string()),
+                                ("master_last_io_seconds_ago", if link.state == LinkState::Connected { link.last_io_seconds as i64 } else { -1 }.to_string()),
+                                ("master_sync_in_progress", ((link.state == LinkState::Transfer) as u8).to_string()),
+                                ("slave_read_repl_offset", replication.offset.to_string()),
+                                ("slave_repl_offset", replication.offset.to_string()),
+                            ]));
+                            if let Some(seconds) = link.down_seconds {
+                                fields.push(("master_link_down_since_seconds".to_string(), seconds.to_string()));
+                            }
+                            fields.extend(named(vec![
+                                ("slave_priority", "100".to_string()),
+                                ("slave_read_only", (self.replication.is_read_only() as u8).to_string()),
+                                ("replica_announced", "1".to_string()),
+                            ]));
+                        },
+                    }
+                    fields.push(("connected_slaves".to_string(), replication.replicas.len().to_string()));
+                    for (index, replica) in replication.replicas.iter().enumerate() {
+                        fields.push((
+                            format!("slave{}", index),
+                            format!("ip={},port={},state=online,offset={},lag={}", replica.ip, replica.port, replica.offset, replica.lag_seconds),
+                        ));
+                    }
+                    fields.extend(named(vec![
+                        ("master_failover_state", "no-failover".to_string()),
+                        ("master_replid", replication.replid),
+                        ("master_replid2", replication.replid2),
+                        ("master_repl_offset", replication.offset.to_string()),
+                        ("second_repl_offset", replication.second_offset.map_or(-1, |offset| offset as i64).to_string()),
+                        ("repl_backlog_active", (replication.backlog.is_some() as u8).to_string()),
+                    ]));
+                    let (size, first_byte_offset, histlen) = match &replication.backlog {
+                        Some(backlog) => (backlog.size as u64, backlog.first_byte_offset, backlog.histlen),
+                        None => (backlog_size, 0, 0),
+                    };
+                    fields.extend(named(vec![
+                        ("repl_backlog_size", size.to_string()),
+                        ("repl_backlog_first_byte_offset", first_byte_offset.to_string()),
+                        ("repl_backlog_histlen", histlen.to_string()),
+                    ]));
+                    fields
+                },
+                "cpu" => {
+                    let (system, user) = server_stats::cpu_time();
+                    named(vec![
+                        ("used_cpu_sys", format!("{:.6}", system)),
+                        ("used_cpu_user", format!("{:.6}", user)),
+                        ("used_cpu_sys_children", format!("{:.6}", 0.0)),
+                        ("used_cpu_user_children", format!("{:.6}", 0.0)),
+                    ])
+                },
+                "errorstats" => self.stats
+                    .errors()
+                    .into_iter()
+                    .map(|(prefix, count)| (format!("errorstat_{}", prefix), format!("count={}", count)))
+                    .collect(),
+                "cluster" => named(vec![("cluster_enabled", (self.cluster.is_enabled() as u8).to_string())]),
+                "keyspace" => databases
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, database)| database.keys > 0)
+                    .map(|(db, database)| (
+                        format!("db{}", db),
+                        format!("keys={},expires={},avg_ttl={}", database.keys, database.expires, database.avg_ttl),
+                    ))
+                    .collect(),
+                "commandstats" => self.stats
+                    .commands()
+                    .into_iter()
+                    .map(|(name, stats)| (
+                        format!("cmdstat_{}", name),
+                        format!(
+                            "calls={},usec={},usec_per_call={:.2},rejected_calls={},failed_calls={}",
+                            stats.calls,
+                            stats.usec,
+                            stats.usec as f64 / stats.calls.max(1) as f64,
+                            stats.rejected_calls,
+                            stats.failed_calls,
+                        ),
+                    ))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            report.push((section, fields));
+        }
+        info::render(&report)
+    }
+}
+
+/// INFO fields with fixed names.
+fn named(fields: Vec<(&str, String)>) -> Vec<(String, String)> {
+    fields.into_iter().map(|(field, value)| (field.to_string(), value)).collect()
+}
+
+pub struct DebugProtocol;
+
+impl CommandExecutor for DebugProtocol {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::DebugProtocol { kind } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let kinds = [
+                "string", "integer", "double", "bignum", "null", "array", "set",
+                "map", "attrib", "push", "verbatim", "true", "false",
+            ];
+            if !kinds.contains(&kind.as_str()) {
+                return CommandHandler::error(
+                    command,
+                    RespError::err("Wrong protocol type name. Please use one of the following: string|integer|double|bignum|null|array|set|map|attrib|push|verbatim|true|false"),
+                );
+            }
+            if matches!(kind.as_str(), "attrib" | "push") && session.protocol() == ProtocolVersion::Resp2 {
+                return CommandHandler::error(command, RespError::err("RESP2 is not supported by this command"));
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    /// Sample replies of every RESP3 type, for client library testing. The
+    /// values are the ones Redis itself returns.
+    #[allow(clippy::approx_constant)]
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        let (RespCommand::DebugProtocol { kind }, CommandHandlerResultStatus::Ok(_)) =
+            (result.get_resp_command(), result.get_status()) else {
+            return Err(mismatched(result));
+        };
+        Ok(match kind.as_str() {
+            "string" => RespResponse::bulk("Hello World"),
+            "integer" => RespResponse::Integer(12345),
+            "double" => RespResponse::Double(3.141),
+            "bignum" => RespResponse::BigNumber("1234567999999999999999999999999999999".to_string()),
+            "null" => RespResponse::Null,
+            "array" => RespResponse::Array(Some((0..3).map(RespResponse::Integer).collect())),
+            "set" => RespResponse::Set((0..3).map(RespResponse::Integer).collect()),
+            "map" => RespResponse::Map(
+                (0..3).map(|i| (RespResponse::Integer(i), RespResponse::Boolean(i == 1))).collect(),
+            ),
+            "attrib" => RespResponse::Attribute {
+                attributes: vec![(
+                    RespResponse::bulk("key-popularity"),
+                    RespResponse::Array(Some(vec![RespResponse::bulk("key:123"), RespResponse::Integer(90)])),
+                )],
+                reply: Box::new(RespResponse::bulk("Some real reply following the attribute")),
+            },
+            "push" => RespResponse::Sequence(vec![
+                RespResponse::Push(vec![RespResponse::bulk("server-cpu-usage"), RespResponse::Integer(42)]),
+                RespResponse::bulk("Some real reply following the push reply"),
+            ]),
+            "verbatim" => RespResponse::Verbatim {
+                format: "txt".to_string(),
+                text: "This is a verbatim\nstring".to_string(),
+            },
+            "true" => RespResponse::Boolean(true),
+            _ => RespResponse::Boolean(false),
+        })
+    }
+}
+
+pub struct Info;
+
+impl CommandExecutor for Info {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::Info { sections } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let text = handler.info(sections).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(Bytes::from(text))))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Ok(Some(text)) => Ok(RespResponse::Verbatim {
+                format: "txt".to_string(),
+                text: String::from_utf8_lossy(text).to_string(),
+            }),
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct Save;
+
+impl CommandExecutor for Save {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            match handler.snapshotter.save().await {
+                Ok(()) => CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)),
+                Err(message) => CommandHandler::error(command, RespError::Err(message)),
+            }
+        })
+    }
+}
+
+pub struct BgSave;
+
+impl CommandExecutor for BgSave {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::BgSave { schedule } = command else {
+                return CommandHandler::mismatched(command);
+            };
+            match handler.snapshotter.background_save(schedule).await {
+                Ok(started) => {
+                    let status = match started {
+                        BackgroundSave::Started => "Background saving started",
+                        BackgroundSave::Scheduled => "Background saving scheduled",
+                    };
+                    CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(Bytes::from_static(status.as_bytes()))))
+                },
+                Err(message) => CommandHandler::error(command, RespError::Err(message)),
+            }
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::status_reply(result)
+    }
+}
+
+pub struct BgRewriteAof;
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            match handler.appender.rewrite(false).await {
+                Ok(()) => {
+                    let status = Bytes::from_static(b"Background append only file rewriting started");
+                    CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(Some(status)))
+                },
+                Err(message) => CommandHandler::error(command, RespError::Err(message)),
+            }
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::status_reply(result)
+    }
+}
+
+pub struct LastSave;
+
+impl CommandExecutor for LastSave {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let last_save = handler.snapshotter.state().last_save;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Integer(last_save as i64))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        response_builder::integer_reply(result)
+    }
+}
+
+pub struct ConfigGet;
+
+impl CommandExecutor for ConfigGet {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ConfigGet { patterns } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let config = handler.config_registry.snapshot().await;
+            let parameters = PARAMETERS
+                .iter()
+                .filter(|parameter| patterns.iter().any(|pattern| glob_match(pattern, parameter.name.as_bytes(), true)))
+                .flat_map(|parameter| [parameter.name.as_bytes().to_vec(), (parameter.get)(&config).into_bytes()])
+                .collect();
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::List(parameters))
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::List(parameters) => {
+                let pairs = parameters
+                    .chunks(2)
+                    .map(|pair| (RespResponse::bulk(pair[0].clone()), RespResponse::bulk(pair[1].clone())))
+                    .collect();
+                Ok(RespResponse::Map(pairs))
+            },
+            _ => Err(mismatched(result)),
+        }
+    }
+}
+
+pub struct ConfigSet;
+
+impl CommandExecutor for ConfigSet {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let RespCommand::ConfigSet { parameters } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            let pairs: Vec<(String, String)> = parameters
+                .iter()
+                .map(|(name, value)| (String::from_utf8_lossy(name).to_string(), String::from_utf8_lossy(value).to_string()))
+                .collect();
+            let appendonly = handler.config_registry.read(|config| config.appendonly).await;
+            let config = match handler.config_registry.set(&pairs).await {
+                Ok(config) => config,
+                Err(message) => return CommandHandler::error(command, RespError::Err(message)),
+            };
+            handler.keyspace_notifier.set_flags(config.notify_keyspace_events);
+            handler.appender.set_fsync(config.appendfsync);
+            handler.replication.set_backlog_size(config.repl_backlog_size);
+            handler.replication.set_read_only(config.replica_read_only);
+            handler.replication.set_serve_stale_data(config.replica_serve_stale_data);
+            handler.tracking_table.set_max_keys(config.tracking_table_max_keys);
+            match (appendonly, config.appendonly) {
+                (false, true) => {
+                    if let Err(message) = handler.appender.start().await {
+                        let _ = handler.config_registry.set(&[("appendonly".to_string(), "no".to_string())]).await;
+                        return CommandHandler::error(command, RespError::Err(message));
+                    }
+                },
+                (true, false) => handler.appender.stop(),
+                _ => {},
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct ConfigResetStat;
+
+impl CommandExecutor for ConfigResetStat {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            handler.stats.reset();
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
+
+pub struct ConfigRewrite;
+
+impl CommandExecutor for ConfigRewrite {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, _: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            let config = handler.config_registry.snapshot().await;
+            let Some(path) = config.config_file.as_deref() else {
+                return CommandHandler::error(command, RespError::err("The server is running without a config file"));
+            };
+            match config_file::rewrite(path, &config) {
+                Ok(()) => CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None)),
+                Err(e) => CommandHandler::error(command, RespError::Err(format!("Rewriting config file: {}", e))),
+            }
+        })
+    }
+}
@@ -0,0 +1,167 @@
+//! Transaction commands: MULTI, EXEC, DISCARD, WATCH and UNWATCH.
+
+use std::time::Instant;
+use crate::resp_parser::domain::command_handler::{
+    CommandExecutor, CommandHandler, CommandHandlerResult, CommandHandlerResultStatus, Execution,
+};
+use crate::resp_parser::domain::command_table::CommandFlag;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::response_builder::{mismatched, ResponseBuilder};
+use crate::resp_parser::domain::session::{Session, WatchedKey};
+
+impl CommandHandler {
+    async fn exec(&self, command: RespCommand, session: &mut Session) -> CommandHandlerResult {
+        let transaction = match session.take_transaction() {
+            Some(transaction) => transaction,
+            None => return Self::error(command, RespError::err("EXEC without MULTI")),
+        };
+        if transaction.has_errors() {
+            self.unwatch_all(session).await;
+            return Self::error(command, RespError::ExecAbort);
+        }
+
+        let mut is_dirty = false;
+        for watched in session.watched_keys() {
+            // A watched key that expired in the meantime counts as modified.
+            self.command_repository.expire_if_needed(watched.db, &watched.key).await;
+            if self.query_repository.version(watched.db, &watched.key).await != watched.version {
+                is_dirty = true;
+            }
+        }
+        self.unwatch_all(session).await;
+        if is_dirty {
+            return CommandHandlerResult::new(command, CommandHandlerResultStatus::Transaction(None));
+        }
+
+        let commands = transaction.into_commands();
+        let writes = commands.iter().any(|queued| queued.spec().has_flag(CommandFlag::Write));
+        if writes {
+            self.command_repository.begin_transaction();
+        }
+        session.set_running_transaction(true);
+        let mut results = Vec::new();
+        for queued in commands {
+            let name = queued.name();
+            let started_at = Instant::now();
+            let result = self.execute(queued, session).await;
+            self.record_call(name, started_at, &result);
+            results.push(self.record_error(result));
+        }
+        session.set_running_transaction(false);
+        if writes {
+            self.command_repository.end_transaction();
+        }
+        CommandHandlerResult::new(command, CommandHandlerResultStatus::Transaction(Some(results)))
+    }
+}
+
+pub struct Multi;
+
+impl CommandExecutor for Multi {
+    fn execute<'a>(&self, _: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            if session.is_in_transaction() {
+                return CommandHandler::error(command, RespError::err("MULTI calls can not be nested"));
+            }
+            session.begin_transaction();
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Exec;
+
+impl CommandExecutor for Exec {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            // CLIENT CACHING before MULTI applies to the whole transaction.
+            let result = handler.exec(command, session).await;
+            session.clear_caching();
+            result
+        })
+    }
+
+    fn reply(&self, result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match result.get_status() {
+            CommandHandlerResultStatus::Transaction(Some(results)) => {
+                // Each queued command keeps its own reply, errors included.
+                let builder = ResponseBuilder::new();
+                let replies = results
+                    .iter()
+                    .map(|result| builder.build(result).unwrap_or_else(|e| RespResponse::Error(e.to_string())))
+                    .collect();
+                Ok(RespResponse::Array(Some(replies)))
+            },
+            CommandHandlerResultStatus::Transaction(None) => Ok(RespResponse::null_array()),
+            _ => Err(mismatched(result)),
+        }
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Discard;
+
+impl CommandExecutor for Discard {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            if session.take_transaction().is_none() {
+                return CommandHandler::error(command, RespError::err("DISCARD without MULTI"));
+            }
+            handler.unwatch_all(session).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Watch;
+
+impl CommandExecutor for Watch {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            if session.is_in_transaction() {
+                return CommandHandler::error(command, RespError::err("WATCH inside MULTI is not allowed"));
+            }
+            let RespCommand::Watch { keys } = &command else {
+                return CommandHandler::mismatched(command);
+            };
+            for key in keys {
+                if session.is_watching(session.db(), key) {
+                    continue;
+                }
+                // Already expired keys must not abort the transaction once removed.
+                handler.command_repository.expire_if_needed(session.db(), key).await;
+                let version = handler.command_repository.watch(session.db(), key.clone()).await;
+                session.watch(WatchedKey { db: session.db(), key: key.clone(), version });
+            }
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+
+    fn is_queued(&self) -> bool {
+        false
+    }
+}
+
+pub struct Unwatch;
+
+impl CommandExecutor for Unwatch {
+    fn execute<'a>(&self, handler: &'a CommandHandler, command: RespCommand, session: &'a mut Session) -> Execution<'a> {
+        Box::pin(async move {
+            handler.unwatch_all(session).await;
+            CommandHandlerResult::new(command, CommandHandlerResultStatus::Ok(None))
+        })
+    }
+}
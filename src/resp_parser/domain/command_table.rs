@@ -0,0 +1,1110 @@
+use crate::resp_parser::domain::command_handler::{
+    cluster, connection, introspection, keyspace, pubsub, replication, server, transactions, CommandExecutor,
+};
+use crate::resp_parser::domain::resp_command::*;
+
+/// Properties clients and the server itself use to reason about a command
+/// without running it, named after their Redis counterparts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandFlag {
+    Write,
+    ReadOnly,
+    DenyOom,
+    Admin,
+    PubSub,
+    NoScript,
+    Loading,
+    Stale,
+    SkipSlowlog,
+    Fast,
+    NoAuth,
+    MayReplicate,
+    NoMulti,
+    AllowBusy,
+}
+
+impl CommandFlag {
+    /// The name COMMAND INFO reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Write => "write",
+            ReadOnly => "readonly",
+            DenyOom => "denyoom",
+            Admin => "admin",
+            PubSub => "pubsub",
+            NoScript => "noscript",
+            Loading => "loading",
+            Stale => "stale",
+            SkipSlowlog => "skip_slowlog",
+            Fast => "fast",
+            NoAuth => "no_auth",
+            MayReplicate => "may_replicate",
+            NoMulti => "no_multi",
+            AllowBusy => "allow_busy",
+        }
+    }
+}
+
+/// Where a command finds its keys: `begin_index` is the position of the
+/// first key and `last_key` the last one relative to it, negative values
+/// counting from the end of the arguments.
+pub struct KeySpec {
+    pub flags: &'static [&'static str],
+    pub begin_index: usize,
+    pub last_key: i32,
+    pub step: usize,
+}
+
+impl KeySpec {
+    /// Positions of the keys among the arguments, command name included.
+    fn positions(&self, argument_count: usize) -> impl Iterator<Item = usize> {
+        let last = if self.last_key >= 0 {
+            self.begin_index + self.last_key as usize
+        } else {
+            argument_count.saturating_sub(self.last_key.unsigned_abs() as usize)
+        };
+        (self.begin_index..=last.min(argument_count.saturating_sub(1))).step_by(self.step)
+    }
+}
+
+/// Builds a `RespCommand` out of the arguments that follow the name.
+pub trait CommandParser: Sync {
+    fn parse(&self, arguments: &mut Arguments) -> Result<RespCommand, String>;
+}
+
+impl<F> CommandParser for F
+where
+    F: Fn(&mut Arguments) -> Result<RespCommand, String> + Sync,
+{
+    fn parse(&self, arguments: &mut Arguments) -> Result<RespCommand, String> {
+        self(arguments)
+    }
+}
+
+/// One entry of the command table.
+pub struct CommandSpec {
+    /// Lowercase name, `container|subcommand` for subcommands.
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Redis version that introduced the command.
+    pub since: &'static str,
+    pub group: &'static str,
+    /// Number of arguments including the name, negative for "at least".
+    pub arity: i32,
+    pub flags: &'static [CommandFlag],
+    pub acl_categories: &'static [&'static str],
+    pub key_specs: &'static [KeySpec],
+    /// `None` for containers that only dispatch to their subcommands.
+    pub parser: Option<&'static dyn CommandParser>,
+    /// Runs the parsed command; present exactly when `parser` is.
+    pub executor: Option<&'static dyn CommandExecutor>,
+    pub subcommands: &'static [CommandSpec],
+}
+
+impl CommandSpec {
+    pub fn has_flag(&self, flag: CommandFlag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn is_subcommand(&self) -> bool {
+        self.name.contains('|')
+    }
+
+    pub fn accepts(&self, argument_count: usize) -> bool {
+        if self.arity >= 0 {
+            argument_count == self.arity as usize
+        } else {
+            argument_count >= self.arity.unsigned_abs() as usize
+        }
+    }
+
+    /// The keys among `arguments`, which start with the command name.
+    pub fn keys<'a, T: AsRef<[u8]>>(&self, arguments: &'a [T]) -> Vec<&'a [u8]> {
+        self.key_specs
+            .iter()
+            .flat_map(|key_spec| key_spec.positions(arguments.len()))
+            .map(|position| arguments[position].as_ref())
+            .collect()
+    }
+
+    /// First key, last key and step in the pre Redis 7 form, which can only
+    /// describe a single range.
+    pub fn legacy_key_range(&self) -> (i64, i64, i64) {
+        match self.key_specs.first() {
+            Some(key_spec) if key_spec.last_key >= 0 => {
+                let first = key_spec.begin_index as i64;
+                (first, first + key_spec.last_key as i64, key_spec.step as i64)
+            },
+            Some(key_spec) => (key_spec.begin_index as i64, key_spec.last_key as i64, key_spec.step as i64),
+            None => (0, 0, 0),
+        }
+    }
+}
+
+use CommandFlag::*;
+
+const KEY: &[KeySpec] = &[KeySpec { flags: &["RO", "ACCESS"], begin_index: 1, last_key: 0, step: 1 }];
+const ALL_KEYS: &[KeySpec] = &[KeySpec { flags: &["RO"], begin_index: 1, last_key: -1, step: 1 }];
+const CHANNEL: &[KeySpec] = &[KeySpec { flags: &["NOT_KEY"], begin_index: 1, last_key: 0, step: 1 }];
+const ALL_CHANNELS: &[KeySpec] = &[KeySpec { flags: &["NOT_KEY"], begin_index: 1, last_key: -1, step: 1 }];
+
+const SUBSCRIBE_FLAGS: &[CommandFlag] = &[PubSub, NoScript, Loading, Stale, NoMulti];
+const TRANSACTION_FLAGS: &[CommandFlag] = &[NoScript, Loading, Stale, Fast, AllowBusy];
+const INTROSPECTION_FLAGS: &[CommandFlag] = &[NoScript, Loading, Stale];
+const CONFIG_FLAGS: &[CommandFlag] = &[Admin, NoScript, Loading, Stale];
+
+/// Every command the server knows, in the shape Redis' own table uses.
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "ping",
+        summary: "Returns the server's liveliness response.",
+        since: "1.0.0",
+        group: "connection",
+        arity: -1,
+        flags: &[Fast],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_ping),
+        executor: Some(&connection::Ping),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "echo",
+        summary: "Returns the given string.",
+        since: "1.0.0",
+        group: "connection",
+        arity: 2,
+        flags: &[Fast],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_echo),
+        executor: Some(&connection::Echo),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "set",
+        summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+        arity: -3,
+        flags: &[Write, DenyOom],
+        acl_categories: &["write", "string", "slow"],
+        key_specs: &[KeySpec { flags: &["RW", "ACCESS", "UPDATE", "VARIABLE_FLAGS"], begin_index: 1, last_key: 0, step: 1 }],
+        parser: Some(&parse_set),
+        executor: Some(&keyspace::Set),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "get",
+        summary: "Returns the string value of a key.",
+        since: "1.0.0",
+        group: "string",
+        arity: 2,
+        flags: &[ReadOnly, Fast],
+        acl_categories: &["read", "string", "fast"],
+        key_specs: KEY,
+        parser: Some(&parse_get),
+        executor: Some(&keyspace::Get),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "del",
+        summary: "Deletes one or more keys.",
+        since: "1.0.0",
+        group: "generic",
+        arity: -2,
+        flags: &[Write],
+        acl_categories: &["keyspace", "write", "slow"],
+        key_specs: &[KeySpec { flags: &["RM", "DELETE"], begin_index: 1, last_key: -1, step: 1 }],
+        parser: Some(&parse_del),
+        executor: Some(&keyspace::Del),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "dump",
+        summary: "Returns a serialized representation of the value stored at a key.",
+        since: "2.6.0",
+        group: "generic",
+        arity: 2,
+        flags: &[ReadOnly],
+        acl_categories: &["keyspace", "read", "slow"],
+        key_specs: KEY,
+        parser: Some(&parse_dump),
+        executor: Some(&keyspace::Dump),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "restore",
+        summary: "Creates a key from the serialized representation of a value.",
+        since: "2.6.0",
+        group: "generic",
+        arity: -4,
+        flags: &[Write, DenyOom],
+        acl_categories: &["keyspace", "write", "slow", "dangerous"],
+        key_specs: &[KeySpec { flags: &["OW", "UPDATE"], begin_index: 1, last_key: 0, step: 1 }],
+        parser: Some(&parse_restore),
+        executor: Some(&keyspace::Restore),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "select",
+        summary: "Changes the selected database.",
+        since: "1.0.0",
+        group: "connection",
+        arity: 2,
+        flags: &[Loading, Stale, Fast],
+        acl_categories: &["keyspace", "fast"],
+        key_specs: &[],
+        parser: Some(&parse_select),
+        executor: Some(&connection::Select),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "flushdb",
+        summary: "Removes all keys from the current database.",
+        since: "1.0.0",
+        group: "server",
+        arity: -1,
+        flags: &[Write],
+        acl_categories: &["keyspace", "write", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_flushdb),
+        executor: Some(&keyspace::FlushDb),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "swapdb",
+        summary: "Swaps two Redis databases.",
+        since: "4.0.0",
+        group: "server",
+        arity: 3,
+        flags: &[Write, Fast],
+        acl_categories: &["keyspace", "write", "fast", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_swapdb),
+        executor: Some(&keyspace::SwapDb),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "multi",
+        summary: "Starts a transaction.",
+        since: "1.2.0",
+        group: "transactions",
+        arity: 1,
+        flags: &[NoScript, Loading, Stale, Fast, NoMulti, AllowBusy],
+        acl_categories: &["fast", "transaction"],
+        key_specs: &[],
+        parser: Some(&parse_multi),
+        executor: Some(&transactions::Multi),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "exec",
+        summary: "Executes all commands in a transaction.",
+        since: "1.2.0",
+        group: "transactions",
+        arity: 1,
+        flags: &[NoScript, Loading, Stale, SkipSlowlog],
+        acl_categories: &["slow", "transaction"],
+        key_specs: &[],
+        parser: Some(&parse_exec),
+        executor: Some(&transactions::Exec),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "discard",
+        summary: "Discards a transaction.",
+        since: "2.0.0",
+        group: "transactions",
+        arity: 1,
+        flags: TRANSACTION_FLAGS,
+        acl_categories: &["fast", "transaction"],
+        key_specs: &[],
+        parser: Some(&parse_discard),
+        executor: Some(&transactions::Discard),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "watch",
+        summary: "Monitors changes to keys to determine the execution of a transaction.",
+        since: "2.2.0",
+        group: "transactions",
+        arity: -2,
+        flags: &[NoScript, Loading, Stale, Fast, NoMulti, AllowBusy],
+        acl_categories: &["fast", "transaction"],
+        key_specs: ALL_KEYS,
+        parser: Some(&parse_watch),
+        executor: Some(&transactions::Watch),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "unwatch",
+        summary: "Forgets about watched keys of a transaction.",
+        since: "2.2.0",
+        group: "transactions",
+        arity: 1,
+        flags: TRANSACTION_FLAGS,
+        acl_categories: &["fast", "transaction"],
+        key_specs: &[],
+        parser: Some(&parse_unwatch),
+        executor: Some(&transactions::Unwatch),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "subscribe",
+        summary: "Listens for messages published to channels.",
+        since: "2.0.0",
+        group: "pubsub",
+        arity: -2,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        parser: Some(&parse_subscribe),
+        executor: Some(&pubsub::Subscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "unsubscribe",
+        summary: "Stops listening to messages posted to channels.",
+        since: "2.0.0",
+        group: "pubsub",
+        arity: -1,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        parser: Some(&parse_unsubscribe),
+        executor: Some(&pubsub::Unsubscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "psubscribe",
+        summary: "Listens for messages published to channels that match one or more patterns.",
+        since: "2.0.0",
+        group: "pubsub",
+        arity: -2,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        parser: Some(&parse_psubscribe),
+        executor: Some(&pubsub::PSubscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "punsubscribe",
+        summary: "Stops listening to messages published to channels that match one or more patterns.",
+        since: "2.0.0",
+        group: "pubsub",
+        arity: -1,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        parser: Some(&parse_punsubscribe),
+        executor: Some(&pubsub::PUnsubscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "publish",
+        summary: "Posts a message to a channel.",
+        since: "2.0.0",
+        group: "pubsub",
+        arity: 3,
+        flags: &[PubSub, Loading, Stale, Fast, MayReplicate],
+        acl_categories: &["pubsub", "fast"],
+        key_specs: &[],
+        parser: Some(&parse_publish),
+        executor: Some(&pubsub::Publish),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "ssubscribe",
+        summary: "Listens for messages published to shard channels.",
+        since: "7.0.0",
+        group: "pubsub",
+        arity: -2,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: ALL_CHANNELS,
+        parser: Some(&parse_ssubscribe),
+        executor: Some(&pubsub::SSubscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "sunsubscribe",
+        summary: "Stops listening to messages posted to shard channels.",
+        since: "7.0.0",
+        group: "pubsub",
+        arity: -1,
+        flags: SUBSCRIBE_FLAGS,
+        acl_categories: &["pubsub", "slow"],
+        key_specs: ALL_CHANNELS,
+        parser: Some(&parse_sunsubscribe),
+        executor: Some(&pubsub::SUnsubscribe),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "spublish",
+        summary: "Post a message to a shard channel",
+        since: "7.0.0",
+        group: "pubsub",
+        arity: 3,
+        flags: &[PubSub, Loading, Stale, Fast, MayReplicate],
+        acl_categories: &["pubsub", "fast"],
+        key_specs: CHANNEL,
+        parser: Some(&parse_spublish),
+        executor: Some(&pubsub::SPublish),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "pubsub",
+        summary: "A container for Pub/Sub commands.",
+        since: "2.8.0",
+        group: "pubsub",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        parser: None,
+        executor: None,
+        subcommands: &[
+            CommandSpec {
+                name: "pubsub|channels",
+                summary: "Returns the active channels.",
+                since: "2.8.0",
+                group: "pubsub",
+                arity: -2,
+                flags: &[PubSub, Loading, Stale],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                parser: Some(&parse_pubsub_channels),
+                executor: Some(&pubsub::PubSubChannels),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "pubsub|numsub",
+                summary: "Returns a count of subscribers to channels.",
+                since: "2.8.0",
+                group: "pubsub",
+                arity: -2,
+                flags: &[PubSub, Loading, Stale],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                parser: Some(&parse_pubsub_numsub),
+                executor: Some(&pubsub::PubSubNumSub),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "pubsub|numpat",
+                summary: "Returns a count of unique pattern subscriptions.",
+                since: "2.8.0",
+                group: "pubsub",
+                arity: 2,
+                flags: &[PubSub, Loading, Stale],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                parser: Some(&parse_pubsub_numpat),
+                executor: Some(&pubsub::PubSubNumPat),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "pubsub|shardchannels",
+                summary: "Returns the active shard channels.",
+                since: "7.0.0",
+                group: "pubsub",
+                arity: -2,
+                flags: &[PubSub, Loading, Stale],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                parser: Some(&parse_pubsub_shardchannels),
+                executor: Some(&pubsub::PubSubShardChannels),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "pubsub|shardnumsub",
+                summary: "Returns the count of subscribers of shard channels.",
+                since: "7.0.0",
+                group: "pubsub",
+                arity: -2,
+                flags: &[PubSub, Loading, Stale],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                parser: Some(&parse_pubsub_shardnumsub),
+                executor: Some(&pubsub::PubSubShardNumSub),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "client",
+        summary: "A container for client connection commands.",
+        since: "2.4.0",
+        group: "connection",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        parser: None,
+        executor: None,
+        subcommands: &[
+            CommandSpec {
+                name: "client|id",
+                summary: "Returns the unique client ID of the connection.",
+                since: "5.0.0",
+                group: "connection",
+                arity: 2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_id),
+                executor: Some(&connection::ClientId),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "client|tracking",
+                summary: "Controls server-assisted client-side caching for the connection.",
+                since: "6.0.0",
+                group: "connection",
+                arity: -3,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_tracking),
+                executor: Some(&connection::ClientTracking),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "client|caching",
+                summary: "Instructs the server whether to track the keys in the next request.",
+                since: "6.0.0",
+                group: "connection",
+                arity: 3,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_caching),
+                executor: Some(&connection::ClientCaching),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "client|getredir",
+                summary: "Returns the client ID to which the connection's tracking notifications are redirected.",
+                since: "6.0.0",
+                group: "connection",
+                arity: 2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_getredir),
+                executor: Some(&connection::ClientGetRedir),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "client|setname",
+                summary: "Sets the connection name.",
+                since: "2.6.9",
+                group: "connection",
+                arity: 3,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_setname),
+                executor: Some(&connection::ClientSetName),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "client|getname",
+                summary: "Returns the name of the connection.",
+                since: "2.6.9",
+                group: "connection",
+                arity: 2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_client_getname),
+                executor: Some(&connection::ClientGetName),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "hello",
+        summary: "Handshakes with the Redis server.",
+        since: "6.0.0",
+        group: "connection",
+        arity: -1,
+        flags: &[NoScript, Loading, Stale, Fast, NoAuth, AllowBusy],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_hello),
+        executor: Some(&connection::Hello),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "debug",
+        summary: "A container for debugging commands.",
+        since: "1.0.0",
+        group: "server",
+        arity: -2,
+        flags: &[Admin, NoScript, Loading, Stale],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_debug),
+        executor: Some(&server::DebugProtocol),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "info",
+        summary: "Returns information and statistics about the server.",
+        since: "1.0.0",
+        group: "server",
+        arity: -1,
+        flags: &[Loading, Stale],
+        acl_categories: &["slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_info),
+        executor: Some(&server::Info),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "save",
+        summary: "Synchronously saves the database(s) to disk.",
+        since: "1.0.0",
+        group: "server",
+        arity: 1,
+        flags: &[Admin, NoScript, NoMulti],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_save),
+        executor: Some(&server::Save),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "bgsave",
+        summary: "Asynchronously saves the database(s) to disk.",
+        since: "1.0.0",
+        group: "server",
+        arity: -1,
+        flags: &[Admin, NoScript],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_bgsave),
+        executor: Some(&server::BgSave),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "bgrewriteaof",
+        summary: "Asynchronously rewrites the append-only file to disk.",
+        since: "1.0.0",
+        group: "server",
+        arity: 1,
+        flags: &[Admin, NoScript],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_bgrewriteaof),
+        executor: Some(&server::BgRewriteAof),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "lastsave",
+        summary: "Returns the Unix timestamp of the last successful save to disk.",
+        since: "1.0.0",
+        group: "server",
+        arity: 1,
+        flags: &[Loading, Stale, Fast],
+        acl_categories: &["fast", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_lastsave),
+        executor: Some(&server::LastSave),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "replicaof",
+        summary: "Configures a server as replica of another, or promotes it to a master.",
+        since: "5.0.0",
+        group: "server",
+        arity: 3,
+        flags: &[Admin, NoScript, Stale],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_replicaof),
+        executor: Some(&replication::ReplicaOf),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "replconf",
+        summary: "An internal command for configuring the replication stream.",
+        since: "3.0.0",
+        group: "server",
+        arity: -1,
+        flags: &[Admin, NoScript, Loading, Stale, AllowBusy],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_replconf),
+        executor: Some(&replication::ReplConf),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "psync",
+        summary: "An internal command used in replication.",
+        since: "2.8.0",
+        group: "server",
+        arity: -3,
+        flags: &[Admin, NoScript, NoMulti],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        parser: Some(&parse_psync),
+        executor: Some(&replication::Psync),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "wait",
+        summary: "Blocks until the asynchronous replication of all preceding write commands sent by the connection is completed.",
+        since: "3.0.0",
+        group: "generic",
+        arity: 3,
+        flags: &[NoScript],
+        acl_categories: &["slow", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_wait),
+        executor: Some(&replication::Wait),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "waitaof",
+        summary: "Blocks until all of the preceding write commands sent by the connection are written to the append-only file of the master and/or replicas.",
+        since: "7.2.0",
+        group: "generic",
+        arity: 4,
+        flags: &[NoScript],
+        acl_categories: &["slow", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_waitaof),
+        executor: Some(&replication::WaitAof),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "config",
+        summary: "A container for server configuration commands.",
+        since: "2.0.0",
+        group: "server",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        parser: None,
+        executor: None,
+        subcommands: &[
+            CommandSpec {
+                name: "config|get",
+                summary: "Returns the effective values of configuration parameters.",
+                since: "2.0.0",
+                group: "server",
+                arity: -3,
+                flags: CONFIG_FLAGS,
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_config_get),
+                executor: Some(&server::ConfigGet),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "config|set",
+                summary: "Sets configuration parameters in-flight.",
+                since: "2.0.0",
+                group: "server",
+                arity: -4,
+                flags: CONFIG_FLAGS,
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_config_set),
+                executor: Some(&server::ConfigSet),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "config|resetstat",
+                summary: "Resets the server's statistics.",
+                since: "2.0.0",
+                group: "server",
+                arity: 2,
+                flags: CONFIG_FLAGS,
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_config_resetstat),
+                executor: Some(&server::ConfigResetStat),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "config|rewrite",
+                summary: "Persists the effective configuration to file.",
+                since: "2.8.0",
+                group: "server",
+                arity: 2,
+                flags: CONFIG_FLAGS,
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_config_rewrite),
+                executor: Some(&server::ConfigRewrite),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "cluster",
+        summary: "A container for Redis Cluster commands.",
+        since: "3.0.0",
+        group: "cluster",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        parser: None,
+        executor: None,
+        subcommands: &[
+            CommandSpec {
+                name: "cluster|keyslot",
+                summary: "Returns the hash slot for a key.",
+                since: "3.0.0",
+                group: "cluster",
+                arity: 3,
+                flags: &[Stale],
+                acl_categories: &["slow"],
+                key_specs: &[],
+                parser: Some(&parse_cluster_keyslot),
+                executor: Some(&cluster::ClusterKeySlot),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "cluster|addslots",
+                summary: "Assigns new hash slots to a node.",
+                since: "3.0.0",
+                group: "cluster",
+                arity: -3,
+                flags: &[Admin, Stale],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_cluster_addslots),
+                executor: Some(&cluster::ClusterAddSlots),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "cluster|setslot",
+                summary: "Binds a hash slot to a node.",
+                since: "3.0.0",
+                group: "cluster",
+                arity: -4,
+                flags: &[Admin, Stale],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                parser: Some(&parse_cluster_setslot),
+                executor: Some(&cluster::ClusterSetSlot),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "command",
+        summary: "Returns detailed information about all commands.",
+        since: "2.8.13",
+        group: "server",
+        arity: -1,
+        flags: INTROSPECTION_FLAGS,
+        acl_categories: &["slow", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_command),
+        executor: Some(&introspection::Command),
+        subcommands: &[
+            CommandSpec {
+                name: "command|count",
+                summary: "Returns a count of commands.",
+                since: "2.8.13",
+                group: "server",
+                arity: 2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_command_count),
+                executor: Some(&introspection::CommandCount),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "command|docs",
+                summary: "Returns documentary information about one, multiple or all commands.",
+                since: "7.0.0",
+                group: "server",
+                arity: -2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_command_docs),
+                executor: Some(&introspection::CommandDocs),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "command|getkeys",
+                summary: "Extracts the key names from an arbitrary command.",
+                since: "2.8.13",
+                group: "server",
+                arity: -3,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_command_getkeys),
+                executor: Some(&introspection::CommandGetKeys),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "command|info",
+                summary: "Returns information about one, multiple or all commands.",
+                since: "2.8.13",
+                group: "server",
+                arity: -2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_command_info),
+                executor: Some(&introspection::CommandInfo),
+                subcommands: &[],
+            },
+            CommandSpec {
+                name: "command|list",
+                summary: "Returns a list of command names.",
+                since: "7.0.0",
+                group: "server",
+                arity: -2,
+                flags: INTROSPECTION_FLAGS,
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                parser: Some(&parse_command_list),
+                executor: Some(&introspection::CommandList),
+                subcommands: &[],
+            },
+        ],
+    },
+    CommandSpec {
+        name: "quit",
+        summary: "Closes the connection.",
+        since: "1.0.0",
+        group: "connection",
+        arity: -1,
+        flags: &[AllowBusy, NoScript, Loading, Stale, Fast, NoAuth],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_quit),
+        executor: Some(&connection::Quit),
+        subcommands: &[],
+    },
+    CommandSpec {
+        name: "reset",
+        summary: "Resets the connection.",
+        since: "6.2.0",
+        group: "connection",
+        arity: 1,
+        flags: &[NoScript, Loading, Stale, Fast, NoAuth, AllowBusy],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        parser: Some(&parse_reset),
+        executor: Some(&connection::Reset),
+        subcommands: &[],
+    },
+];
+
+/// Resolves the command, and its subcommand for containers, named by the
+/// arguments and checks the argument count against its arity.
+pub fn lookup<T: AsRef<[u8]>>(arguments: &[T]) -> Result<&'static CommandSpec, String> {
+    let spec = resolve(arguments)?;
+    if !spec.accepts(arguments.len()) {
+        return Err(format!("wrong number of arguments for '{}' command", spec.name));
+    }
+    Ok(spec)
+}
+
+/// Like `lookup`, without the arity check.
+pub fn resolve<T: AsRef<[u8]>>(arguments: &[T]) -> Result<&'static CommandSpec, String> {
+    let name = arguments.first().map(|name| text(name.as_ref())).unwrap_or_default();
+    let spec = COMMAND_TABLE
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(&name))
+        .ok_or_else(|| {
+            let arguments: String = arguments
+                .iter()
+                .skip(1)
+                .map(|argument| format!("'{}' ", text(argument.as_ref())))
+                .collect();
+            format!("unknown command '{}', with args beginning with: {}", name, arguments)
+        })?;
+
+    if spec.subcommands.is_empty() || arguments.len() < 2 {
+        return Ok(spec);
+    }
+    let subcommand = text(arguments[1].as_ref());
+    spec.subcommands
+        .iter()
+        .find(|sub| sub.name[spec.name.len() + 1..].eq_ignore_ascii_case(&subcommand))
+        .ok_or_else(|| format!("unknown subcommand '{}'. Try {} HELP.", subcommand, spec.name.to_uppercase()))
+}
+
+/// Every command and subcommand.
+pub fn entries() -> impl Iterator<Item = &'static CommandSpec> {
+    COMMAND_TABLE
+        .iter()
+        .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands))
+}
+
+/// Entry for a full name such as `get` or `config|get`, in any case.
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    entries().find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+fn text(argument: &[u8]) -> String {
+    String::from_utf8_lossy(argument).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use super::*;
+
+    fn arguments(arguments: &[&'static str]) -> Vec<Bytes> {
+        arguments.iter().map(|argument| Bytes::from_static(argument.as_bytes())).collect()
+    }
+
+    #[test]
+    fn test_lookup_checks_arity_and_subcommands() {
+        assert_eq!(lookup(&arguments(&["GeT", "k"])).map(|spec| spec.name), Ok("get"));
+        assert_eq!(lookup(&arguments(&["config", "GET", "*"])).map(|spec| spec.name), Ok("config|get"));
+        assert_eq!(
+            lookup(&arguments(&["set", "k"])).err(),
+            Some("wrong number of arguments for 'set' command".to_string()),
+        );
+        assert_eq!(
+            lookup(&arguments(&["config"])).err(),
+            Some("wrong number of arguments for 'config' command".to_string()),
+        );
+        assert_eq!(
+            lookup(&arguments(&["config", "nope"])).err(),
+            Some("unknown subcommand 'nope'. Try CONFIG HELP.".to_string()),
+        );
+        assert!(lookup(&arguments(&["nope"])).is_err());
+    }
+
+    #[test]
+    fn test_keys_follow_key_specs() {
+        fn keys(command: &[&str]) -> Vec<Vec<u8>> {
+            find(command[0]).unwrap().keys(command).into_iter().map(|key| key.to_vec()).collect()
+        }
+        assert_eq!(keys(&["set", "k", "v", "PX", "10"]), vec![b"k".to_vec()]);
+        assert_eq!(keys(&["watch", "a", "b", "c"]), vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert!(keys(&["ping", "hello"]).is_empty());
+        assert_eq!(find("watch").unwrap().legacy_key_range(), (1, -1, 1));
+        assert_eq!(find("get").unwrap().legacy_key_range(), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_every_command_resolves_to_its_entry() {
+        for spec in entries() {
+            assert!(std::ptr::eq(find(spec.name).unwrap(), spec));
+            // Containers without a parser of their own always need a subcommand.
+            assert!(spec.parser.is_some() || (!spec.subcommands.is_empty() && spec.arity <= -2), "{}", spec.name);
+            assert_eq!(spec.parser.is_some(), spec.executor.is_some(), "{}", spec.name);
+        }
+    }
+}
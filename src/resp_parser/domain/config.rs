@@ -0,0 +1,545 @@
+use std::path::Path;
+use crate::resp_parser::domain::keyspace_events::{flags_to_string, parse_flags};
+
+/// Server configuration, built from redis.conf and the command line and
+/// changed at runtime by CONFIG SET.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub bind: Vec<String>,
+    pub port: u16,
+    /// Working directory for persistence files.
+    pub dir: String,
+    pub dbfilename: String,
+    /// `(seconds, changes)` policies: save once `changes` writes are at
+    /// least `seconds` old. Empty disables automatic saving.
+    pub save: Vec<(u64, u64)>,
+    /// Logs every write to the append-only file and replays it at startup.
+    pub appendonly: bool,
+    /// Prefix of the append-only files and their manifest.
+    pub appendfilename: String,
+    /// Directory inside `dir` holding the append-only files.
+    pub appenddirname: String,
+    pub appendfsync: AppendFsync,
+    /// Loads an append-only file whose last command was cut short instead
+    /// of refusing to start.
+    pub aof_load_truncated: bool,
+    /// Growth over the size after the last rewrite, in percent, that
+    /// triggers a rewrite. 0 disables automatic rewrites.
+    pub auto_aof_rewrite_percentage: u64,
+    /// Files smaller than this are not rewritten automatically.
+    pub auto_aof_rewrite_min_size: u64,
+    /// Memory limit in bytes, 0 for none.
+    pub maxmemory: u64,
+    /// Seconds after which an idle client is closed, 0 for never.
+    pub timeout: u64,
+    pub notify_keyspace_events: u32,
+    /// Master this server replicates at startup, as host and port.
+    pub replicaof: Option<(String, u16)>,
+    /// Bytes of recent writes kept for replicas that reconnect.
+    pub repl_backlog_size: u64,
+    /// Whether a replica rejects writes from its clients.
+    pub replica_read_only: bool,
+    /// Whether a replica keeps answering queries while its link to the
+    /// master is down.
+    pub replica_serve_stale_data: bool,
+    /// Keys remembered for client-side caching at most, 0 for no limit.
+    pub tracking_table_max_keys: u64,
+    /// Routes keys and shard channels by slot, answering MOVED for the
+    /// slots other nodes serve.
+    pub cluster_enabled: bool,
+    /// File the configuration was read from, which CONFIG REWRITE updates.
+    pub config_file: Option<String>,
+}
+
+/// When writes to the append-only file are flushed to disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// Before replying to the write.
+    Always,
+    /// Once a second in the background, losing at most a second of writes.
+    EverySec,
+    /// Whenever the operating system sees fit.
+    No,
+}
+
+impl AppendFsync {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        }
+    }
+}
+
+/// A configuration parameter as CONFIG GET and SET and configuration
+/// files see it: a single string value.
+pub struct Parameter {
+    pub name: &'static str,
+    /// Immutable parameters can only be set at startup.
+    pub mutable: bool,
+    /// Takes several words in a configuration file, e.g. `bind a b`.
+    pub multiple_arguments: bool,
+    pub get: fn(&Config) -> String,
+    pub set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+pub static PARAMETERS: &[Parameter] = &[
+    Parameter {
+        name: "bind",
+        mutable: false,
+        multiple_arguments: true,
+        get: |config| config.bind.join(" "),
+        set: |config, value| {
+            let addresses: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+            if addresses.is_empty() {
+                return Err("wrong number of arguments".to_string());
+            }
+            config.bind = addresses;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "port",
+        mutable: false,
+        multiple_arguments: false,
+        get: |config| config.port.to_string(),
+        set: |config, value| {
+            let port = parse_integer(value)?;
+            config.port = u16::try_from(port).map_err(|_| "argument must be between 0 and 65535 inclusive".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dir",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.dir.clone(),
+        set: |config, value| {
+            if !Path::new(value).is_dir() {
+                return Err("No such file or directory".to_string());
+            }
+            config.dir = value.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dbfilename",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.dbfilename.clone(),
+        set: |config, value| {
+            if value.contains('/') {
+                return Err("dbfilename can't be a path, just a filename".to_string());
+            }
+            config.dbfilename = value.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "save",
+        mutable: true,
+        multiple_arguments: true,
+        get: |config| {
+            let policies: Vec<String> = config.save.iter().map(|(seconds, changes)| format!("{} {}", seconds, changes)).collect();
+            policies.join(" ")
+        },
+        set: |config, value| {
+            let numbers: Option<Vec<u64>> = value.split_whitespace().map(|number| number.parse().ok()).collect();
+            match numbers {
+                Some(numbers) if numbers.len().is_multiple_of(2) => {
+                    config.save = numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+                    Ok(())
+                },
+                _ => Err("Invalid save parameters".to_string()),
+            }
+        },
+    },
+    Parameter {
+        name: "appendonly",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| yes_no(config.appendonly),
+        set: |config, value| {
+            config.appendonly = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appendfilename",
+        mutable: false,
+        multiple_arguments: false,
+        get: |config| config.appendfilename.clone(),
+        set: |config, value| {
+            if value.contains('/') {
+                return Err("appendfilename can't be a path, just a filename".to_string());
+            }
+            config.appendfilename = value.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appenddirname",
+        mutable: false,
+        multiple_arguments: false,
+        get: |config| config.appenddirname.clone(),
+        set: |config, value| {
+            if value.contains('/') {
+                return Err("appenddirname can't be a path, just a dirname".to_string());
+            }
+            config.appenddirname = value.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appendfsync",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.appendfsync.name().to_string(),
+        set: |config, value| {
+            config.appendfsync = [AppendFsync::Always, AppendFsync::EverySec, AppendFsync::No]
+                .into_iter()
+                .find(|policy| policy.name().eq_ignore_ascii_case(value))
+                .ok_or_else(|| "argument(s) must be one of the following: always, everysec, no".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "aof-load-truncated",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| yes_no(config.aof_load_truncated),
+        set: |config, value| {
+            config.aof_load_truncated = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "auto-aof-rewrite-percentage",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.auto_aof_rewrite_percentage.to_string(),
+        set: |config, value| {
+            let percentage = parse_integer(value)?;
+            if !(0..=i32::MAX as i64).contains(&percentage) {
+                return Err("argument must be between 0 and 2147483647 inclusive".to_string());
+            }
+            config.auto_aof_rewrite_percentage = percentage as u64;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "auto-aof-rewrite-min-size",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.auto_aof_rewrite_min_size.to_string(),
+        set: |config, value| {
+            config.auto_aof_rewrite_min_size = parse_memory(value).ok_or_else(|| "argument must be a memory value".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "maxmemory",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.maxmemory.to_string(),
+        set: |config, value| {
+            config.maxmemory = parse_memory(value).ok_or_else(|| "argument must be a memory value".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "timeout",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.timeout.to_string(),
+        set: |config, value| {
+            let timeout = parse_integer(value)?;
+            if !(0..=i32::MAX as i64).contains(&timeout) {
+                return Err("argument must be between 0 and 2147483647 inclusive".to_string());
+            }
+            config.timeout = timeout as u64;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "notify-keyspace-events",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| flags_to_string(config.notify_keyspace_events),
+        set: |config, value| {
+            config.notify_keyspace_events = parse_flags(value)
+                .ok_or_else(|| "Invalid event class character. Use 'Ag$lshzxeKEtmdn'.".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replicaof",
+        mutable: false,
+        multiple_arguments: true,
+        get: |config| config.replicaof.as_ref().map(|(host, port)| format!("{} {}", host, port)).unwrap_or_default(),
+        set: |config, value| {
+            let (host, port) = value.split_once(' ').ok_or_else(|| "wrong number of arguments".to_string())?;
+            let port = port.parse().map_err(|_| "Invalid master port".to_string())?;
+            config.replicaof = Some((host.to_string(), port));
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "repl-backlog-size",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.repl_backlog_size.to_string(),
+        set: |config, value| {
+            config.repl_backlog_size = parse_memory(value).ok_or_else(|| "argument must be a memory value".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replica-read-only",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| yes_no(config.replica_read_only),
+        set: |config, value| {
+            config.replica_read_only = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replica-serve-stale-data",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| yes_no(config.replica_serve_stale_data),
+        set: |config, value| {
+            config.replica_serve_stale_data = parse_bool(value)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "tracking-table-max-keys",
+        mutable: true,
+        multiple_arguments: false,
+        get: |config| config.tracking_table_max_keys.to_string(),
+        set: |config, value| {
+            let max_keys = parse_integer(value)?;
+            if max_keys < 0 {
+                return Err("argument must be between 0 and 9223372036854775807 inclusive".to_string());
+            }
+            config.tracking_table_max_keys = max_keys as u64;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "cluster-enabled",
+        mutable: false,
+        multiple_arguments: false,
+        get: |config| yes_no(config.cluster_enabled),
+        set: |config, value| {
+            config.cluster_enabled = parse_bool(value)?;
+            Ok(())
+        },
+    },
+];
+
+/// Looks a parameter up by name, in any case.
+pub fn parameter(name: &str) -> Option<&'static Parameter> {
+    PARAMETERS.iter().find(|parameter| parameter.name.eq_ignore_ascii_case(name))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: vec!["127.0.0.1".to_string()],
+            port: 6379,
+            dir: ".".to_string(),
+            dbfilename: "dump.rdb".to_string(),
+            save: vec![(3600, 1), (300, 100), (60, 10000)],
+            appendonly: false,
+            appendfilename: "appendonly.aof".to_string(),
+            appenddirname: "appendonlydir".to_string(),
+            appendfsync: AppendFsync::EverySec,
+            aof_load_truncated: true,
+            auto_aof_rewrite_percentage: 100,
+            auto_aof_rewrite_min_size: 64 * 1024 * 1024,
+            maxmemory: 0,
+            timeout: 0,
+            notify_keyspace_events: 0,
+            replicaof: None,
+            repl_backlog_size: 1024 * 1024,
+            replica_read_only: true,
+            replica_serve_stale_data: true,
+            tracking_table_max_keys: 1_000_000,
+            cluster_enabled: false,
+            config_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Applies one configuration file directive, e.g. `port 6380`, with the
+    /// errors Redis reports for a bad line.
+    pub fn apply(&mut self, name: &str, arguments: &[String]) -> Result<(), String> {
+        let parameter = parameter(name).ok_or_else(|| "Bad directive or wrong number of arguments".to_string())?;
+        if arguments.is_empty() || (!parameter.multiple_arguments && arguments.len() > 1) {
+            return Err("wrong number of arguments".to_string());
+        }
+        (parameter.set)(self, &arguments.join(" "))
+    }
+
+    /// The configuration with all CONFIG SET `pairs` applied, or the error
+    /// for the first one that fails, in which case none of them apply.
+    pub fn with_values(&self, pairs: &[(String, String)]) -> Result<Config, String> {
+        let failed = |name: &str, reason: &str| {
+            format!("CONFIG SET failed (possibly related to argument '{}') - {}", name, reason)
+        };
+        let mut config = self.clone();
+        for (index, (name, value)) in pairs.iter().enumerate() {
+            let parameter = parameter(name)
+                .ok_or_else(|| format!("Unknown option or number of arguments for CONFIG SET - '{}'", name))?;
+            if pairs[..index].iter().any(|(other, _)| other.eq_ignore_ascii_case(name)) {
+                return Err(failed(name, "duplicate parameter"));
+            }
+            if !parameter.mutable {
+                return Err(failed(name, "can't set immutable config"));
+            }
+            (parameter.set)(&mut config, value).map_err(|reason| failed(name, &reason))?;
+        }
+        Ok(config)
+    }
+}
+
+fn parse_integer(value: &str) -> Result<i64, String> {
+    value.parse().map_err(|_| "argument couldn't be parsed into an integer".to_string())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+/// Parses a byte count with an optional unit: `k`/`m`/`g` are powers of
+/// 1000, `kb`/`mb`/`gb` powers of 1024, in any case.
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_lowercase();
+    let digits = value.find(|c: char| !c.is_ascii_digit() && c != '-').unwrap_or(value.len());
+    let multiplier = match &value[digits..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    value[..digits].parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_memory_units() {
+        assert_eq!(parse_memory("100"), Some(100));
+        assert_eq!(parse_memory("1k"), Some(1000));
+        assert_eq!(parse_memory("1KB"), Some(1024));
+        assert_eq!(parse_memory("2mb"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_memory("1gb"), Some(1024 * 1024 * 1024));
+        assert_eq!(parse_memory("1g"), Some(1_000_000_000));
+        assert_eq!(parse_memory("1tb"), None);
+        assert_eq!(parse_memory("-1"), None);
+        assert_eq!(parse_memory("gb"), None);
+    }
+
+    #[test]
+    fn test_apply_validates_directives() {
+        let mut config = Config::default();
+        config.apply("PORT", &arguments(&["6380"])).unwrap();
+        config.apply("bind", &arguments(&["127.0.0.1", "::1"])).unwrap();
+        config.apply("maxmemory", &arguments(&["1mb"])).unwrap();
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.bind, arguments(&["127.0.0.1", "::1"]));
+        assert_eq!(config.maxmemory, 1048576);
+        config.apply("save", &arguments(&["900", "1", "300", "10"])).unwrap();
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+        config.apply("save", &arguments(&[""])).unwrap();
+        assert!(config.save.is_empty());
+        assert_eq!(config.apply("save", &arguments(&["900"])), Err("Invalid save parameters".to_string()));
+        config.apply("replicaof", &arguments(&["10.0.0.1", "6380"])).unwrap();
+        assert_eq!(config.replicaof, Some(("10.0.0.1".to_string(), 6380)));
+        assert_eq!(config.apply("replicaof", &arguments(&["10.0.0.1"])), Err("wrong number of arguments".to_string()));
+        config.apply("repl-backlog-size", &arguments(&["10mb"])).unwrap();
+        assert_eq!(config.repl_backlog_size, 10 * 1024 * 1024);
+        config.apply("appendonly", &arguments(&["YES"])).unwrap();
+        config.apply("appendfsync", &arguments(&["always"])).unwrap();
+        assert!(config.appendonly);
+        assert_eq!(config.appendfsync, AppendFsync::Always);
+        assert_eq!(config.apply("appendonly", &arguments(&["on"])), Err("argument must be 'yes' or 'no'".to_string()));
+        config.apply("cluster-enabled", &arguments(&["yes"])).unwrap();
+        assert!(config.cluster_enabled);
+        config.apply("tracking-table-max-keys", &arguments(&["0"])).unwrap();
+        assert_eq!(config.tracking_table_max_keys, 0);
+        assert_eq!(
+            config.apply("tracking-table-max-keys", &arguments(&["-1"])),
+            Err("argument must be between 0 and 9223372036854775807 inclusive".to_string()),
+        );
+
+        assert_eq!(
+            config.apply("port", &arguments(&["70000"])),
+            Err("argument must be between 0 and 65535 inclusive".to_string()),
+        );
+        assert_eq!(
+            config.apply("dbfilename", &arguments(&["a/b.rdb"])),
+            Err("dbfilename can't be a path, just a filename".to_string()),
+        );
+        assert_eq!(
+            config.apply("nope", &arguments(&["1"])),
+            Err("Bad directive or wrong number of arguments".to_string()),
+        );
+        assert_eq!(config.port, 6380);
+    }
+
+    #[test]
+    fn test_with_values_is_all_or_nothing() {
+        let config = Config::default();
+        let updated = config.with_values(&pairs(&[("maxmemory", "1kb"), ("TIMEOUT", "30")])).unwrap();
+        assert_eq!((updated.maxmemory, updated.timeout), (1024, 30));
+
+        assert_eq!(
+            config.with_values(&pairs(&[("maxmemory", "1kb"), ("timeout", "soon")])),
+            Err("CONFIG SET failed (possibly related to argument 'timeout') - argument couldn't be parsed into an integer".to_string()),
+        );
+        assert_eq!(
+            config.with_values(&pairs(&[("timeout", "1"), ("timeout", "2")])),
+            Err("CONFIG SET failed (possibly related to argument 'timeout') - duplicate parameter".to_string()),
+        );
+        assert_eq!(
+            config.with_values(&pairs(&[("port", "7000")])),
+            Err("CONFIG SET failed (possibly related to argument 'port') - can't set immutable config".to_string()),
+        );
+        assert_eq!(
+            config.with_values(&pairs(&[("cluster-enabled", "yes")])),
+            Err("CONFIG SET failed (possibly related to argument 'cluster-enabled') - can't set immutable config".to_string()),
+        );
+        assert_eq!(
+            config.with_values(&pairs(&[("nope", "1")])),
+            Err("Unknown option or number of arguments for CONFIG SET - 'nope'".to_string()),
+        );
+    }
+}
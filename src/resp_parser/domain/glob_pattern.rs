@@ -0,0 +1,95 @@
+/// Redis-style glob matching (`*`, `?`, `[...]`, `[^...]` and `\` escapes).
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| if nocase { a.eq_ignore_ascii_case(&b) } else { a == b };
+    let (mut p, mut s) = (0, 0);
+    // Position to resume from when a later mismatch happens after a `*`.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        let matched = if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    while p < pattern.len() && pattern[p] == b'*' {
+                        p += 1;
+                    }
+                    if p == pattern.len() {
+                        return true;
+                    }
+                    backtrack = Some((p, s));
+                    continue;
+                },
+                b'?' => Some(p + 1),
+                b'[' => match_class(pattern, p, string[s], nocase),
+                b'\\' if p + 1 < pattern.len() => eq(pattern[p + 1], string[s]).then_some(p + 2),
+                c => eq(c, string[s]).then_some(p + 1),
+            }
+        } else {
+            None
+        };
+
+        match matched {
+            Some(next) => {
+                p = next;
+                s += 1;
+            },
+            None => match backtrack {
+                Some((star_p, star_s)) => {
+                    p = star_p;
+                    s = star_s + 1;
+                    backtrack = Some((star_p, star_s + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Matches `c` against the `[...]` class starting at `start`, returning the
+/// index just past the class on success.
+fn match_class(pattern: &[u8], start: usize, c: u8, nocase: bool) -> Option<usize> {
+    let fold = |b: u8| if nocase { b.to_ascii_lowercase() } else { b };
+    let c = fold(c);
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            matched |= fold(pattern[p + 1]) == c;
+            p += 2;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (low, high) = (fold(pattern[p]).min(fold(pattern[p + 2])), fold(pattern[p]).max(fold(pattern[p + 2])));
+            matched |= low <= c && c <= high;
+            p += 3;
+        } else {
+            matched |= fold(pattern[p]) == c;
+            p += 1;
+        }
+    }
+    // An unterminated class runs to the end of the pattern, like in Redis.
+    (matched != negate).then_some(p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b"anything", false));
+        assert!(glob_match(b"news.*", b"news.tech", false));
+        assert!(!glob_match(b"news.*", b"sports.tech", false));
+        assert!(glob_match(b"h?llo", b"hello", false));
+        assert!(glob_match(b"h[ae]llo", b"hallo", false));
+        assert!(!glob_match(b"h[^e]llo", b"hello", false));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo", false));
+        assert!(glob_match(b"a*b*c", b"axxbyyc", false));
+        assert!(!glob_match(b"a*b*c", b"axxbyy", false));
+        assert!(glob_match(b"\\*", b"*", false));
+        assert!(glob_match(b"MAX*", b"maxmemory", true));
+    }
+}
@@ -0,0 +1,90 @@
+pub const REDIS_VERSION: &str = "7.2.0";
+
+/// Every INFO section in report order, with its title.
+const SECTIONS: &[(&str, &str)] = &[
+    ("server", "Server"),
+    ("clients", "Clients"),
+    ("memory", "Memory"),
+    ("persistence", "Persistence"),
+    ("stats", "Stats"),
+    ("replication", "Replication"),
+    ("cpu", "CPU"),
+    ("modules", "Modules"),
+    ("errorstats", "Errorstats"),
+    ("cluster", "Cluster"),
+    ("keyspace", "Keyspace"),
+    ("commandstats", "Commandstats"),
+];
+
+/// Sections left out unless asked for by name, `all` or `everything`.
+const NON_DEFAULT_SECTIONS: &[&str] = &["commandstats"];
+
+/// Sections selected by the INFO arguments, in report order. No arguments
+/// means `default`; unknown names select nothing.
+pub fn selected_sections(arguments: &[String]) -> Vec<&'static str> {
+    let arguments: Vec<String> = match arguments.is_empty() {
+        true => vec!["default".to_string()],
+        false => arguments.iter().map(|argument| argument.to_lowercase()).collect(),
+    };
+    SECTIONS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| {
+            arguments.iter().any(|argument| match argument.as_str() {
+                "all" | "everything" => true,
+                "default" => !NON_DEFAULT_SECTIONS.contains(name),
+                argument => argument == *name,
+            })
+        })
+        .collect()
+}
+
+/// Formats sections the way INFO replies: a `# Title` line, `field:value`
+/// lines and a blank line between sections.
+pub fn render(sections: &[(&str, Vec<(String, String)>)]) -> String {
+    sections
+        .iter()
+        .map(|(name, fields)| {
+            let title = SECTIONS.iter().find(|(section, _)| section == name).map_or(*name, |(_, title)| *title);
+            let mut text = format!("# {}\r\n", title);
+            for (field, value) in fields {
+                text.push_str(&format!("{}:{}\r\n", field, value));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Byte count in the `1.50M` form of the `_human` INFO fields.
+pub fn human_bytes(bytes: u64) -> String {
+    let units = [("K", 1u64 << 10), ("M", 1 << 20), ("G", 1 << 30), ("T", 1 << 40), ("P", 1 << 50)];
+    match units.iter().rev().find(|(_, size)| bytes >= *size) {
+        Some((unit, size)) => format!("{:.2}{}", bytes as f64 / *size as f64, unit),
+        None => format!("{}B", bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_section_selection_and_rendering() {
+        assert!(!selected_sections(&[]).contains(&"commandstats"));
+        assert!(selected_sections(&arguments(&["ALL"])).contains(&"commandstats"));
+        assert_eq!(selected_sections(&arguments(&["keyspace", "server", "nope"])), vec!["server", "keyspace"]);
+
+        let sections = [
+            ("server", vec![("redis_version".to_string(), REDIS_VERSION.to_string())]),
+            ("cpu", vec![]),
+        ];
+        assert_eq!(render(&sections), "# Server\r\nredis_version:7.2.0\r\n\r\n# CPU\r\n");
+        assert_eq!(human_bytes(512), "512B");
+        assert_eq!(human_bytes(1536 * 1024), "1.50M");
+    }
+}
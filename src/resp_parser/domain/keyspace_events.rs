@@ -0,0 +1,82 @@
+//! Event classes selected by the `notify-keyspace-events` flag string.
+
+pub const KEYSPACE: u32 = 1 << 0;
+pub const KEYEVENT: u32 = 1 << 1;
+pub const GENERIC: u32 = 1 << 2;
+pub const STRING: u32 = 1 << 3;
+pub const LIST: u32 = 1 << 4;
+pub const SET: u32 = 1 << 5;
+pub const HASH: u32 = 1 << 6;
+pub const ZSET: u32 = 1 << 7;
+pub const EXPIRED: u32 = 1 << 8;
+pub const EVICTED: u32 = 1 << 9;
+pub const STREAM: u32 = 1 << 10;
+pub const KEY_MISS: u32 = 1 << 11;
+pub const NEW: u32 = 1 << 12;
+/// What `A` stands for: every class except key-miss and new-key events.
+pub const ALL: u32 = GENERIC | STRING | LIST | SET | HASH | ZSET | EXPIRED | EVICTED | STREAM;
+
+const CLASSES: [(char, u32); 9] = [
+    ('g', GENERIC),
+    ('$', STRING),
+    ('l', LIST),
+    ('s', SET),
+    ('h', HASH),
+    ('z', ZSET),
+    ('x', EXPIRED),
+    ('e', EVICTED),
+    ('t', STREAM),
+];
+
+pub fn parse_flags(flags: &str) -> Option<u32> {
+    let mut parsed = 0;
+    for flag in flags.chars() {
+        parsed |= match flag {
+            'A' => ALL,
+            'K' => KEYSPACE,
+            'E' => KEYEVENT,
+            'm' => KEY_MISS,
+            'n' => NEW,
+            _ => CLASSES.iter().find(|(c, _)| *c == flag)?.1,
+        };
+    }
+    Some(parsed)
+}
+
+/// Canonical flag string, as CONFIG GET reports it.
+pub fn flags_to_string(flags: u32) -> String {
+    let mut string = String::new();
+    if flags & ALL == ALL {
+        string.push('A');
+    } else {
+        CLASSES.iter()
+            .filter(|(_, class)| flags & class != 0)
+            .for_each(|(c, _)| string.push(*c));
+    }
+    for (c, class) in [('K', KEYSPACE), ('E', KEYEVENT), ('m', KEY_MISS), ('n', NEW)] {
+        if flags & class != 0 {
+            string.push(c);
+        }
+    }
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flags() {
+        assert_eq!(parse_flags(""), Some(0));
+        assert_eq!(parse_flags("KEA"), Some(KEYSPACE | KEYEVENT | ALL));
+        assert_eq!(parse_flags("Ex"), Some(KEYEVENT | EXPIRED));
+        assert_eq!(parse_flags("Kq"), None);
+    }
+
+    #[test]
+    fn test_flags_to_string() {
+        assert_eq!(flags_to_string(parse_flags("AKE").unwrap()), "AKE");
+        assert_eq!(flags_to_string(parse_flags("xEg$").unwrap()), "g$xE");
+        assert_eq!(flags_to_string(parse_flags("nmK").unwrap()), "Kmn");
+    }
+}
@@ -3,4 +3,13 @@ pub mod resp_command;
 pub mod command_handler;
 pub mod resp_response;
 pub mod response_builder;
-
+pub mod session;
+pub mod glob_pattern;
+pub mod pubsub_message;
+pub mod cluster;
+pub mod keyspace_events;
+pub mod tracking;
+pub mod resp_error;
+pub mod command_table;
+pub mod config;
+pub mod info;
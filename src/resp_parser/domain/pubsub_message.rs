@@ -0,0 +1,24 @@
+/// A message pushed to a connection outside the request/reply flow.
+pub enum PubSubMessage {
+    Message {
+        channel: Vec<u8>,
+        payload: Vec<u8>,
+    },
+    PatternMessage {
+        pattern: Vec<u8>,
+        channel: Vec<u8>,
+        payload: Vec<u8>,
+    },
+    ShardMessage {
+        channel: Vec<u8>,
+        payload: Vec<u8>,
+    },
+    /// Client-side caching invalidation; `None` means every key (a flush).
+    Invalidate {
+        keys: Option<Vec<Vec<u8>>>,
+    },
+    /// The REDIRECT target of a tracking client, `client_id`, is gone.
+    TrackingRedirBroken {
+        client_id: u64,
+    },
+}
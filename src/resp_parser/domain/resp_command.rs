@@ -1,68 +1,856 @@
-use crate::resp_parser::domain::stream_chunking_service::StringCommand;
+use bytes::Bytes;
+use crate::resp_parser::domain::cluster::CLUSTER_SLOTS;
+use crate::resp_parser::domain::command_handler::CommandExecutor;
+use crate::resp_parser::domain::command_table::{self, CommandSpec};
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::stream_chunking_service::RawCommand;
+use crate::resp_parser::domain::tracking::TrackingOptions;
 
+#[derive(Clone)]
+pub enum SetExpiry {
+    /// Milliseconds from the moment the command runs.
+    Relative(u64),
+    /// Unix time in milliseconds.
+    Absolute(u64),
+}
+
+#[derive(Clone)]
 pub enum RespCommand {
     Ping {
-        message: Option<Vec<u8>>,
+        message: Option<Bytes>,
     },
     Echo {
-        message: Option<Vec<u8>>,
+        message: Option<Bytes>,
     },
     Set {
         key: Vec<u8>,
-        value: Vec<u8>,
+        value: Bytes,
+        expiry: Option<SetExpiry>,
     },
     Get {
         key: Vec<u8>,
     },
+    Del {
+        keys: Vec<Vec<u8>>,
+    },
+    Dump {
+        key: Vec<u8>,
+    },
+    Restore {
+        key: Vec<u8>,
+        /// Milliseconds to live, or when to expire with `absttl`; 0 for
+        /// no expiry.
+        ttl: u64,
+        payload: Bytes,
+        replace: bool,
+        absttl: bool,
+    },
+    Select {
+        index: i64,
+    },
+    FlushDb,
+    SwapDb {
+        first: i64,
+        second: i64,
+    },
+    Multi,
+    Exec,
+    Discard,
+    Watch {
+        keys: Vec<Vec<u8>>,
+    },
+    Unwatch,
+    Subscribe {
+        channels: Vec<Vec<u8>>,
+    },
+    Unsubscribe {
+        channels: Vec<Vec<u8>>,
+    },
+    PSubscribe {
+        patterns: Vec<Vec<u8>>,
+    },
+    PUnsubscribe {
+        patterns: Vec<Vec<u8>>,
+    },
+    Publish {
+        channel: Vec<u8>,
+        message: Vec<u8>,
+    },
+    SSubscribe {
+        channels: Vec<Vec<u8>>,
+    },
+    SUnsubscribe {
+        channels: Vec<Vec<u8>>,
+    },
+    SPublish {
+        channel: Vec<u8>,
+        message: Vec<u8>,
+    },
+    PubSubChannels {
+        pattern: Option<Vec<u8>>,
+    },
+    PubSubNumSub {
+        channels: Vec<Vec<u8>>,
+    },
+    PubSubNumPat,
+    PubSubShardChannels {
+        pattern: Option<Vec<u8>>,
+    },
+    PubSubShardNumSub {
+        channels: Vec<Vec<u8>>,
+    },
+    Quit,
+    Reset,
+    ClientId,
+    ClientTracking {
+        enabled: bool,
+        options: TrackingOptions,
+    },
+    ClientCaching {
+        enabled: bool,
+    },
+    ClientGetRedir,
+    ClientSetName {
+        name: Vec<u8>,
+    },
+    ClientGetName,
+    Hello {
+        protocol: Option<i64>,
+        auth: Option<(Vec<u8>, Vec<u8>)>,
+        name: Option<Vec<u8>>,
+    },
+    DebugProtocol {
+        kind: String,
+    },
+    Save,
+    /// `schedule` queues the save behind one already running.
+    BgSave {
+        schedule: bool,
+    },
+    LastSave,
+    BgRewriteAof,
+    /// `None` for REPLICAOF NO ONE.
+    ReplicaOf {
+        master: Option<(String, u16)>,
+    },
+    ReplConf {
+        options: Vec<ReplConfOption>,
+    },
+    /// The replication id and offset a replica has, `?` and -1 when it has
+    /// none.
+    Psync {
+        replid: String,
+        offset: i64,
+    },
+    /// `timeout` in milliseconds, 0 to wait for ever.
+    Wait {
+        numreplicas: i64,
+        timeout: u64,
+    },
+    WaitAof {
+        numlocal: i64,
+        numreplicas: i64,
+        timeout: u64,
+    },
+    /// Section names in lowercase; empty for the default sections.
+    Info {
+        sections: Vec<String>,
+    },
+    ConfigGet {
+        patterns: Vec<Vec<u8>>,
+    },
+    ConfigSet {
+        parameters: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    ConfigResetStat,
+    ConfigRewrite,
+    ClusterKeySlot {
+        key: Vec<u8>,
+    },
+    ClusterAddSlots {
+        slots: Vec<u16>,
+    },
+    /// Hands `slot` to the node at `address`, given as `host:port`.
+    ClusterSetSlot {
+        slot: u16,
+        address: String,
+    },
+    Command,
+    CommandCount,
+    CommandInfo {
+        names: Vec<Vec<u8>>,
+    },
+    CommandDocs {
+        names: Vec<Vec<u8>>,
+    },
+    CommandGetKeys {
+        arguments: Vec<Vec<u8>>,
+    },
+    CommandList {
+        filter: Option<CommandListFilter>,
+    },
     //...
 }
 
+/// An option of REPLCONF, which replicas send their master.
+#[derive(Clone)]
+pub enum ReplConfOption {
+    ListeningPort(u16),
+    IpAddress(String),
+    Capa(String),
+    /// The offset of the master's stream the replica applied.
+    Ack(u64),
+    /// The offset of the master's stream the replica fsynced to its
+    /// append-only file, sent along with `Ack`.
+    Fack(u64),
+    GetAck,
+}
+
+/// The FILTERBY clause of COMMAND LIST.
+#[derive(Clone)]
+pub enum CommandListFilter {
+    /// No modules can be loaded, so the module name is not kept.
+    Module,
+    AclCategory(Vec<u8>),
+    Pattern(Vec<u8>),
+}
+
 impl RespCommand {
-    pub fn parse(string_command_with_args: StringCommand) -> Result<RespCommand, String> {
-        let mut split_command = string_command_with_args
-            .as_str()
-            .split("\r\n");
-        let command = split_command.next().unwrap();
+    /// Looks the command up in the command table, checks its arity and hands
+    /// the remaining arguments to its parser. Argument errors are always
+    /// `ERR` errors.
+    pub fn parse(raw_command: RawCommand) -> Result<RespCommand, RespError> {
+        let arguments = raw_command.into_arguments();
+        let spec = command_table::lookup(&arguments).map_err(RespError::Err)?;
+        let skip = if spec.is_subcommand() { 2 } else { 1 };
+        let mut arguments = Arguments::new(spec.name, arguments.into_iter().skip(skip).collect());
+        spec.parser
+            .expect("commands with subcommands are resolved by lookup")
+            .parse(&mut arguments)
+            .map_err(RespError::Err)
+    }
+
+    /// Table entry of the command, e.g. for its flags.
+    pub fn spec(&self) -> &'static CommandSpec {
+        command_table::find(self.name()).expect("every command has a table entry")
+    }
+
+    /// What runs the command and builds its reply, from its table entry.
+    pub fn executor(&self) -> &'static dyn CommandExecutor {
+        self.spec().executor.expect("commands with subcommands are resolved by lookup")
+    }
+}
+
+/// Arguments following the command (and subcommand) name.
+pub struct Arguments {
+    command: &'static str,
+    arguments: std::vec::IntoIter<Bytes>,
+}
+
+impl Arguments {
+    fn new(command: &'static str, arguments: Vec<Bytes>) -> Self {
+        Self {
+            command,
+            arguments: arguments.into_iter(),
+        }
+    }
+
+    /// The next argument, or the arity error if there is none left.
+    pub fn next_arg(&mut self) -> Result<Bytes, String> {
+        self.arguments
+            .next()
+            .ok_or_else(|| format!("wrong number of arguments for '{}' command", self.command))
+    }
+
+    /// All remaining arguments.
+    pub fn rest(&mut self) -> Vec<Vec<u8>> {
+        self.arguments.by_ref().map(|argument| argument.to_vec()).collect()
+    }
+}
+
+pub fn parse_ping(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Ping { message: arguments.next_arg().ok() })
+}
+
+pub fn parse_echo(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Echo { message: arguments.next_arg().ok() })
+}
 
-        match command.to_uppercase().as_str() {
-            "PING" => Ok(RespCommand::Ping { message: split_command.next().map(|s| s.as_bytes().to_vec()) }),
-            "ECHO" => Ok(RespCommand::Echo { message: split_command.next().map(|s| s.as_bytes().to_vec()) }),
-            "SET" => Ok(RespCommand::Set {
-                key: split_command.next().unwrap().as_bytes().to_vec(),
-                value: split_command.next().unwrap().as_bytes().to_vec()
-            }),
-            "GET" => Ok(RespCommand::Get {
-                key: split_command.next().unwrap().as_bytes().to_vec(),
-            }),
-            _ => Err(format!("Unknown command: {}", command)),
+pub fn parse_set(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let key = arguments.next_arg()?.to_vec();
+    let value = arguments.next_arg()?;
+    let mut expiry = None;
+    while let Ok(option) = arguments.next_arg() {
+        let option = text(&option).to_uppercase();
+        if expiry.is_some() || !matches!(option.as_str(), "EX" | "PX" | "EXAT" | "PXAT") {
+            return Err("syntax error".to_string());
         }
+        let amount = parse_integer(&arguments.next_arg().map_err(|_| "syntax error".to_string())?)?;
+        if amount <= 0 {
+            return Err("invalid expire time in 'set' command".to_string());
+        }
+        let amount = amount as u64;
+        expiry = Some(match option.as_str() {
+            "EX" => SetExpiry::Relative(amount.saturating_mul(1000)),
+            "PX" => SetExpiry::Relative(amount),
+            "EXAT" => SetExpiry::Absolute(amount.saturating_mul(1000)),
+            _ => SetExpiry::Absolute(amount),
+        });
+    }
+    Ok(RespCommand::Set { key, value, expiry })
+}
+
+pub fn parse_get(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Get { key: arguments.next_arg()?.to_vec() })
+}
+
+pub fn parse_del(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Del { keys: arguments.rest() })
+}
+
+pub fn parse_dump(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Dump { key: arguments.next_arg()?.to_vec() })
+}
+
+pub fn parse_restore(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let key = arguments.next_arg()?.to_vec();
+    let ttl = parse_integer(&arguments.next_arg()?)?;
+    let payload = arguments.next_arg()?;
+    let (mut replace, mut absttl, mut idle_time, mut freq) = (false, false, None, None);
+    while let Ok(option) = arguments.next_arg() {
+        match text(&option).to_uppercase().as_str() {
+            "REPLACE" => replace = true,
+            "ABSTTL" => absttl = true,
+            // Eviction hints, which mean nothing without eviction, so they
+            // are only validated.
+            "IDLETIME" if freq.is_none() => {
+                let seconds = parse_integer(&arguments.next_arg().map_err(|_| "syntax error".to_string())?)?;
+                if seconds < 0 {
+                    return Err("Invalid IDLETIME value, must be >= 0".to_string());
+                }
+                idle_time = Some(seconds);
+            },
+            "FREQ" if idle_time.is_none() => {
+                let frequency = parse_integer(&arguments.next_arg().map_err(|_| "syntax error".to_string())?)?;
+                if !(0..=255).contains(&frequency) {
+                    return Err("Invalid FREQ value, must be >= 0 and <= 255".to_string());
+                }
+                freq = Some(frequency);
+            },
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+    if ttl < 0 {
+        return Err("Invalid TTL value, must be >= 0".to_string());
     }
+    Ok(RespCommand::Restore { key, ttl: ttl as u64, payload, replace, absttl })
+}
+
+pub fn parse_select(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Select { index: parse_integer(&arguments.next_arg()?)? })
+}
+
+pub fn parse_flushdb(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::FlushDb)
+}
+
+pub fn parse_swapdb(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let first = arguments.next_arg()?;
+    let second = arguments.next_arg()?;
+    Ok(RespCommand::SwapDb {
+        first: parse_integer(&first).map_err(|_| "invalid first DB index".to_string())?,
+        second: parse_integer(&second).map_err(|_| "invalid second DB index".to_string())?,
+    })
+}
+
+pub fn parse_multi(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Multi)
+}
+
+pub fn parse_exec(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Exec)
+}
+
+pub fn parse_discard(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Discard)
+}
+
+pub fn parse_watch(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Watch { keys: arguments.rest() })
+}
+
+pub fn parse_unwatch(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Unwatch)
+}
+
+pub fn parse_subscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Subscribe { channels: arguments.rest() })
+}
+
+pub fn parse_unsubscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Unsubscribe { channels: arguments.rest() })
+}
+
+pub fn parse_psubscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::PSubscribe { patterns: arguments.rest() })
+}
+
+pub fn parse_punsubscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::PUnsubscribe { patterns: arguments.rest() })
+}
+
+pub fn parse_publish(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Publish {
+        channel: arguments.next_arg()?.to_vec(),
+        message: arguments.next_arg()?.to_vec(),
+    })
+}
+
+pub fn parse_ssubscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::SSubscribe { channels: arguments.rest() })
+}
+
+pub fn parse_sunsubscribe(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::SUnsubscribe { channels: arguments.rest() })
+}
+
+pub fn parse_spublish(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::SPublish {
+        channel: arguments.next_arg()?.to_vec(),
+        message: arguments.next_arg()?.to_vec(),
+    })
+}
+
+pub fn parse_pubsub_channels(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let mut rest = arguments.rest();
+    if rest.len() > 1 {
+        return Err("wrong number of arguments for 'pubsub|channels' command".to_string());
+    }
+    Ok(RespCommand::PubSubChannels { pattern: rest.pop() })
+}
+
+pub fn parse_pubsub_numsub(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::PubSubNumSub { channels: arguments.rest() })
+}
+
+pub fn parse_pubsub_numpat(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::PubSubNumPat)
+}
+
+pub fn parse_pubsub_shardchannels(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let mut rest = arguments.rest();
+    if rest.len() > 1 {
+        return Err("wrong number of arguments for 'pubsub|shardchannels' command".to_string());
+    }
+    Ok(RespCommand::PubSubShardChannels { pattern: rest.pop() })
+}
+
+pub fn parse_pubsub_shardnumsub(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::PubSubShardNumSub { channels: arguments.rest() })
+}
+
+pub fn parse_client_id(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ClientId)
+}
+
+pub fn parse_client_getredir(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ClientGetRedir)
+}
+
+pub fn parse_client_setname(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ClientSetName { name: arguments.next_arg()?.to_vec() })
+}
+
+pub fn parse_client_getname(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ClientGetName)
+}
+
+pub fn parse_client_caching(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let enabled = match text(&arguments.next_arg()?).to_uppercase().as_str() {
+        "YES" => true,
+        "NO" => false,
+        _ => return Err("syntax error".to_string()),
+    };
+    Ok(RespCommand::ClientCaching { enabled })
+}
+
+pub fn parse_client_tracking(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let enabled = match text(&arguments.next_arg()?).to_uppercase().as_str() {
+        "ON" => true,
+        "OFF" => false,
+        _ => return Err("syntax error".to_string()),
+    };
+    let mut options = TrackingOptions::default();
+    while let Ok(option) = arguments.next_arg() {
+        match text(&option).to_uppercase().as_str() {
+            "REDIRECT" => {
+                let id = arguments.next_arg().map_err(|_| "syntax error".to_string())?;
+                options.redirect = Some(text(&id).parse::<u64>()
+                    .map_err(|_| "value is not an integer or out of range".to_string())?);
+            },
+            "PREFIX" => {
+                let prefix = arguments.next_arg().map_err(|_| "syntax error".to_string())?;
+                options.prefixes.push(prefix.to_vec());
+            },
+            "BCAST" => options.bcast = true,
+            "OPTIN" => options.optin = true,
+            "OPTOUT" => options.optout = true,
+            "NOLOOP" => options.noloop = true,
+            _ => return Err("syntax error".to_string()),
+        }
+    }
+    if !options.bcast && !options.prefixes.is_empty() {
+        return Err("PREFIX option requires BCAST mode to be enabled".to_string());
+    }
+    if options.optin && options.optout {
+        return Err("You can't use both OPTIN and OPTOUT options".to_string());
+    }
+    if options.bcast && (options.optin || options.optout) {
+        return Err("OPTIN and OPTOUT are not compatible with BCAST".to_string());
+    }
+    Ok(RespCommand::ClientTracking { enabled, options })
+}
+
+pub fn parse_hello(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let mut protocol = None;
+    let mut auth = None;
+    let mut name = None;
+    if let Ok(version) = arguments.next_arg() {
+        protocol = Some(text(&version).parse::<i64>()
+            .map_err(|_| "Protocol version is not an integer or out of range".to_string())?);
+    }
+    while let Ok(option) = arguments.next_arg() {
+        let option = text(&option).to_uppercase();
+        let syntax_error = || format!("Syntax error in HELLO option '{}'", option);
+        match option.as_str() {
+            "AUTH" => {
+                let username = arguments.next_arg().map_err(|_| syntax_error())?.to_vec();
+                let password = arguments.next_arg().map_err(|_| syntax_error())?.to_vec();
+                auth = Some((username, password));
+            },
+            "SETNAME" => name = Some(arguments.next_arg().map_err(|_| syntax_error())?.to_vec()),
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok(RespCommand::Hello { protocol, auth, name })
+}
+
+pub fn parse_debug(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let subcommand = arguments.next_arg()?;
+    match text(&subcommand).to_uppercase().as_str() {
+        "PROTOCOL" => Ok(RespCommand::DebugProtocol { kind: text(&arguments.next_arg()?).to_lowercase() }),
+        _ => Err(format!("unknown subcommand '{}'. Try DEBUG HELP.", text(&subcommand))),
+    }
+}
+
+pub fn parse_save(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Save)
+}
+
+pub fn parse_bgsave(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    match arguments.rest().as_slice() {
+        [] => Ok(RespCommand::BgSave { schedule: false }),
+        [option] if option.eq_ignore_ascii_case(b"SCHEDULE") => Ok(RespCommand::BgSave { schedule: true }),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+pub fn parse_lastsave(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::LastSave)
+}
+
+pub fn parse_bgrewriteaof(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::BgRewriteAof)
+}
+
+pub fn parse_replicaof(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let host = arguments.next_arg()?;
+    let port = arguments.next_arg()?;
+    if host.eq_ignore_ascii_case(b"NO") && port.eq_ignore_ascii_case(b"ONE") {
+        return Ok(RespCommand::ReplicaOf { master: None });
+    }
+    let port = text(&port).parse::<u16>().map_err(|_| "Invalid master port".to_string())?;
+    Ok(RespCommand::ReplicaOf { master: Some((text(&host), port)) })
+}
+
+pub fn parse_replconf(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let rest = arguments.rest();
+    if !rest.len().is_multiple_of(2) {
+        return Err("syntax error".to_string());
+    }
+    let mut options = Vec::new();
+    for pair in rest.chunks(2) {
+        let value = &pair[1];
+        let option = match text(&pair[0]).to_lowercase().as_str() {
+            "listening-port" => {
+                let port = parse_integer(value)?;
+                ReplConfOption::ListeningPort(u16::try_from(port).map_err(|_| "Invalid listening port".to_string())?)
+            },
+            "ip-address" => ReplConfOption::IpAddress(text(value)),
+            "capa" => ReplConfOption::Capa(text(value).to_lowercase()),
+            "ack" => ReplConfOption::Ack(parse_offset(value)?),
+            "fack" => ReplConfOption::Fack(parse_offset(value)?),
+            "getack" => ReplConfOption::GetAck,
+            _ => return Err(format!("Unrecognized REPLCONF option: {}", text(&pair[0]))),
+        };
+        options.push(option);
+    }
+    Ok(RespCommand::ReplConf { options })
+}
+
+pub fn parse_psync(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let replid = text(&arguments.next_arg()?);
+    let offset = parse_integer(&arguments.next_arg()?)?;
+    Ok(RespCommand::Psync { replid, offset })
+}
+
+pub fn parse_wait(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let numreplicas = parse_integer(&arguments.next_arg()?)?;
+    let timeout = parse_wait_timeout(&arguments.next_arg()?)?;
+    Ok(RespCommand::Wait { numreplicas, timeout })
+}
+
+pub fn parse_waitaof(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let numlocal = parse_integer(&arguments.next_arg()?)?;
+    let numreplicas = parse_integer(&arguments.next_arg()?)?;
+    let timeout = parse_wait_timeout(&arguments.next_arg()?)?;
+    Ok(RespCommand::WaitAof { numlocal, numreplicas, timeout })
+}
+
+pub fn parse_info(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let sections = arguments.rest().iter().map(|section| text(section).to_lowercase()).collect();
+    Ok(RespCommand::Info { sections })
+}
+
+pub fn parse_config_get(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ConfigGet { patterns: arguments.rest() })
+}
+
+pub fn parse_config_set(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let rest = arguments.rest();
+    if !rest.len().is_multiple_of(2) {
+        return Err("wrong number of arguments for 'config|set' command".to_string());
+    }
+    let parameters = rest.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect();
+    Ok(RespCommand::ConfigSet { parameters })
+}
+
+pub fn parse_config_resetstat(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ConfigResetStat)
+}
+
+pub fn parse_config_rewrite(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ConfigRewrite)
+}
+
+pub fn parse_cluster_keyslot(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::ClusterKeySlot { key: arguments.next_arg()?.to_vec() })
+}
+
+pub fn parse_cluster_addslots(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let slots = arguments.rest().iter().map(|slot| parse_slot(slot)).collect::<Result<_, _>>()?;
+    Ok(RespCommand::ClusterAddSlots { slots })
+}
+
+pub fn parse_cluster_setslot(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let slot = parse_slot(&arguments.next_arg()?)?;
+    let action = arguments.next_arg()?;
+    let address = arguments.next_arg()?;
+    if !action.eq_ignore_ascii_case(b"NODE") || !arguments.rest().is_empty() {
+        return Err("Invalid CLUSTER SETSLOT action or number of arguments. Try CLUSTER HELP".to_string());
+    }
+    let address = text(&address);
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {},
+        _ => return Err(format!("Invalid node address specified: {}", address)),
+    }
+    Ok(RespCommand::ClusterSetSlot { slot, address })
+}
+
+pub fn parse_command(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Command)
+}
+
+pub fn parse_command_count(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::CommandCount)
+}
+
+pub fn parse_command_info(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::CommandInfo { names: arguments.rest() })
+}
+
+pub fn parse_command_docs(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::CommandDocs { names: arguments.rest() })
+}
+
+pub fn parse_command_getkeys(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::CommandGetKeys { arguments: arguments.rest() })
+}
+
+pub fn parse_command_list(arguments: &mut Arguments) -> Result<RespCommand, String> {
+    let Ok(clause) = arguments.next_arg() else {
+        return Ok(RespCommand::CommandList { filter: None });
+    };
+    let kind = arguments.next_arg().map_err(|_| "syntax error".to_string())?;
+    let value = arguments.next_arg().map_err(|_| "syntax error".to_string())?.to_vec();
+    if !clause.eq_ignore_ascii_case(b"FILTERBY") || !arguments.rest().is_empty() {
+        return Err("syntax error".to_string());
+    }
+    let filter = match text(&kind).to_uppercase().as_str() {
+        "MODULE" => CommandListFilter::Module,
+        "ACLCAT" => CommandListFilter::AclCategory(value),
+        "PATTERN" => CommandListFilter::Pattern(value),
+        _ => return Err("syntax error".to_string()),
+    };
+    Ok(RespCommand::CommandList { filter: Some(filter) })
+}
+
+pub fn parse_quit(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Quit)
+}
+
+pub fn parse_reset(_: &mut Arguments) -> Result<RespCommand, String> {
+    Ok(RespCommand::Reset)
+}
+
+impl RespCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RespCommand::Ping { .. } => "ping",
+            RespCommand::Echo { .. } => "echo",
+            RespCommand::Set { .. } => "set",
+            RespCommand::Get { .. } => "get",
+            RespCommand::Del { .. } => "del",
+            RespCommand::Dump { .. } => "dump",
+            RespCommand::Restore { .. } => "restore",
+            RespCommand::Select { .. } => "select",
+            RespCommand::FlushDb => "flushdb",
+            RespCommand::SwapDb { .. } => "swapdb",
+            RespCommand::Multi => "multi",
+            RespCommand::Exec => "exec",
+            RespCommand::Discard => "discard",
+            RespCommand::Watch { .. } => "watch",
+            RespCommand::Unwatch => "unwatch",
+            RespCommand::Subscribe { .. } => "subscribe",
+            RespCommand::Unsubscribe { .. } => "unsubscribe",
+            RespCommand::PSubscribe { .. } => "psubscribe",
+            RespCommand::PUnsubscribe { .. } => "punsubscribe",
+            RespCommand::Publish { .. } => "publish",
+            RespCommand::SSubscribe { .. } => "ssubscribe",
+            RespCommand::SUnsubscribe { .. } => "sunsubscribe",
+            RespCommand::SPublish { .. } => "spublish",
+            RespCommand::PubSubChannels { .. } => "pubsub|channels",
+            RespCommand::PubSubNumSub { .. } => "pubsub|numsub",
+            RespCommand::PubSubNumPat => "pubsub|numpat",
+            RespCommand::PubSubShardChannels { .. } => "pubsub|shardchannels",
+            RespCommand::PubSubShardNumSub { .. } => "pubsub|shardnumsub",
+            RespCommand::Quit => "quit",
+            RespCommand::Reset => "reset",
+            RespCommand::ClientId => "client|id",
+            RespCommand::ClientTracking { .. } => "client|tracking",
+            RespCommand::ClientCaching { .. } => "client|caching",
+            RespCommand::ClientGetRedir => "client|getredir",
+            RespCommand::ClientSetName { .. } => "client|setname",
+            RespCommand::ClientGetName => "client|getname",
+            RespCommand::Hello { .. } => "hello",
+            RespCommand::DebugProtocol { .. } => "debug",
+            RespCommand::Save => "save",
+            RespCommand::BgSave { .. } => "bgsave",
+            RespCommand::LastSave => "lastsave",
+            RespCommand::BgRewriteAof => "bgrewriteaof",
+            RespCommand::ReplicaOf { .. } => "replicaof",
+            RespCommand::ReplConf { .. } => "replconf",
+            RespCommand::Psync { .. } => "psync",
+            RespCommand::Wait { .. } => "wait",
+            RespCommand::WaitAof { .. } => "waitaof",
+            RespCommand::Info { .. } => "info",
+            RespCommand::ConfigGet { .. } => "config|get",
+            RespCommand::ConfigSet { .. } => "config|set",
+            RespCommand::ConfigResetStat => "config|resetstat",
+            RespCommand::ConfigRewrite => "config|rewrite",
+            RespCommand::ClusterKeySlot { .. } => "cluster|keyslot",
+            RespCommand::ClusterAddSlots { .. } => "cluster|addslots",
+            RespCommand::ClusterSetSlot { .. } => "cluster|setslot",
+            RespCommand::Command => "command",
+            RespCommand::CommandCount => "command|count",
+            RespCommand::CommandInfo { .. } => "command|info",
+            RespCommand::CommandDocs { .. } => "command|docs",
+            RespCommand::CommandGetKeys { .. } => "command|getkeys",
+            RespCommand::CommandList { .. } => "command|list",
+        }
+    }
+
+    /// Commands a connection in subscriber mode may still run.
+    pub fn is_allowed_in_subscriber_mode(&self) -> bool {
+        matches!(
+            self,
+            RespCommand::Subscribe { .. }
+                | RespCommand::Unsubscribe { .. }
+                | RespCommand::PSubscribe { .. }
+                | RespCommand::PUnsubscribe { .. }
+                | RespCommand::SSubscribe { .. }
+                | RespCommand::SUnsubscribe { .. }
+                | RespCommand::Ping { .. }
+                | RespCommand::Quit
+                | RespCommand::Reset
+        )
+    }
+}
+
+fn parse_integer(value: &[u8]) -> Result<i64, String> {
+    text(value).parse::<i64>()
+        .map_err(|_| "value is not an integer or out of range".to_string())
+}
+
+fn parse_offset(value: &[u8]) -> Result<u64, String> {
+    u64::try_from(parse_integer(value)?).map_err(|_| "value is out of range, must be positive".to_string())
+}
+
+fn parse_slot(value: &[u8]) -> Result<u16, String> {
+    text(value)
+        .parse::<u16>()
+        .ok()
+        .filter(|slot| *slot < CLUSTER_SLOTS)
+        .ok_or_else(|| "Invalid or out of range slot".to_string())
+}
+
+fn parse_wait_timeout(value: &[u8]) -> Result<u64, String> {
+    let timeout = text(value).parse::<i64>().map_err(|_| "timeout is not an integer or out of range".to_string())?;
+    u64::try_from(timeout).map_err(|_| "timeout is negative".to_string())
+}
+
+fn text(argument: &[u8]) -> String {
+    String::from_utf8_lossy(argument).to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn raw_command(arguments: &[&str]) -> RawCommand {
+        RawCommand::from(arguments)
+    }
+
     #[test]
     fn test_parse_command() {
-        let command = RespCommand::parse(StringCommand::new("PING\r\n".to_string()));
+        let command = RespCommand::parse(raw_command(&["PING"]));
         assert!(command.is_ok());
     }
 
     #[test]
     fn test_parse_command_unknown_command() {
-        let command = RespCommand::parse(StringCommand::new("GET\r\n".to_string()));
+        let command = RespCommand::parse(raw_command(&["GET"]));
         assert!(command.is_err());
     }
 
     #[test]
     fn test_echo_command() {
-        let command = RespCommand::parse(StringCommand::new("ECHO\r\nHey\r\n".to_string()));
+        let command = RespCommand::parse(raw_command(&["ECHO", "Hey"]));
         assert!(command.is_ok());
         let command = command.unwrap();
         match command {
             RespCommand::Echo { message } => {
-                assert_eq!(message, Some("Hey".as_bytes().to_vec()));
+                assert_eq!(message, Some(Bytes::from_static(b"Hey")));
             },
             _ => panic!("Unexpected command type")
         }
@@ -70,13 +858,14 @@ mod tests {
 
     #[test]
     fn test_set_command() {
-        let command = RespCommand::parse(StringCommand::new("SET\r\nkey\r\nvalue\r\n".to_string()));
+        let command = RespCommand::parse(raw_command(&["SET", "key", "value"]));
         assert!(command.is_ok());
         let command = command.unwrap();
         match command {
-            RespCommand::Set { key, value } => {
+            RespCommand::Set { key, value, expiry } => {
                 assert_eq!(key, "key".as_bytes().to_vec());
                 assert_eq!(value, "value".as_bytes().to_vec());
+                assert!(expiry.is_none());
             },
             _ => panic!("Unexpected command type")
         }   
@@ -84,7 +873,7 @@ mod tests {
 
     #[test]
     fn test_get_command() {
-        let command = RespCommand::parse(StringCommand::new("GET\r\nkey\r\n".to_string()));
+        let command = RespCommand::parse(raw_command(&["GET", "key"]));
         assert!(command.is_ok());
         let command = command.unwrap();
         match command {
@@ -94,4 +883,53 @@ mod tests {
             _ => panic!("Unexpected command type")
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_set_command_with_expiry() {
+        let command = RespCommand::parse(raw_command(&["SET", "key", "value", "px", "100"]));
+        match command {
+            Ok(RespCommand::Set { expiry: Some(SetExpiry::Relative(ms)), .. }) => assert_eq!(ms, 100),
+            _ => panic!("Unexpected command type")
+        }
+        let command = RespCommand::parse(raw_command(&["SET", "key", "value", "PX"]));
+        assert!(command.is_err());
+    }
+
+    #[test]
+    fn test_watch_command() {
+        let command = RespCommand::parse(raw_command(&["WATCH", "a", "b"]));
+        match command {
+            Ok(RespCommand::Watch { keys }) => assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec()]),
+            _ => panic!("Unexpected command type")
+        }
+        assert!(RespCommand::parse(raw_command(&["WATCH"])).is_err());
+    }
+
+    #[test]
+    fn test_wait_command() {
+        match RespCommand::parse(raw_command(&["WAITAOF", "1", "2", "100"])) {
+            Ok(RespCommand::WaitAof { numlocal, numreplicas, timeout }) => assert_eq!((numlocal, numreplicas, timeout), (1, 2, 100)),
+            _ => panic!("Unexpected command type")
+        }
+        match RespCommand::parse(raw_command(&["WAIT", "1", "-1"])) {
+            Err(e) => assert!(e.to_string().contains("timeout is negative")),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_cluster_setslot_command() {
+        match RespCommand::parse(raw_command(&["CLUSTER", "SETSLOT", "12182", "node", "10.0.0.2:6379"])) {
+            Ok(RespCommand::ClusterSetSlot { slot, address }) => assert_eq!((slot, address.as_str()), (12182, "10.0.0.2:6379")),
+            _ => panic!("Unexpected command type")
+        }
+        match RespCommand::parse(raw_command(&["CLUSTER", "SETSLOT", "16384", "NODE", "10.0.0.2:6379"])) {
+            Err(e) => assert!(e.to_string().contains("Invalid or out of range slot")),
+            Ok(_) => panic!("Expected an error"),
+        }
+        match RespCommand::parse(raw_command(&["CLUSTER", "SETSLOT", "1", "NODE", "10.0.0.2"])) {
+            Err(e) => assert!(e.to_string().contains("Invalid node address specified")),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+/// An error reply. The variant decides the prefix clients dispatch on, the
+/// rest of the line is the human readable message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RespError {
+    #[error("ERR {0}")]
+    Err(String),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    /// A script or module holds the server. Nothing runs scripts yet.
+    #[error("BUSY {0}")]
+    Busy(String),
+    /// Authentication is required first. Only the passwordless default user
+    /// exists so far.
+    #[error("NOAUTH {0}")]
+    NoAuth(String),
+    /// The user's ACL rules forbid the command.
+    #[error("NOPERM {0}")]
+    NoPerm(String),
+    #[error("MOVED {slot} {address}")]
+    Moved {
+        slot: u16,
+        address: String,
+    },
+    /// The slot is migrating and this one key is already at `address`.
+    /// Nothing migrates slots yet.
+    #[error("ASK {slot} {address}")]
+    Ask {
+        slot: u16,
+        address: String,
+    },
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("NOMASTERLINK Can't SYNC while not connected with my master")]
+    NoMasterLink,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error("MASTERDOWN Link with MASTER is down and replica-serve-stale-data is set to 'no'.")]
+    MasterDown,
+}
+
+impl RespError {
+    pub fn err(message: impl Into<String>) -> Self {
+        RespError::Err(message.into())
+    }
+
+    /// Malformed framing; the connection is closed after this reply.
+    pub fn protocol(message: impl std::fmt::Display) -> Self {
+        RespError::Err(format!("Protocol error: {}", message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_prefixes() {
+        assert_eq!(RespError::err("syntax error").to_string(), "ERR syntax error");
+        assert_eq!(RespError::protocol("invalid bulk length").to_string(), "ERR Protocol error: invalid bulk length");
+        assert!(RespError::WrongType.to_string().starts_with("WRONGTYPE "));
+        assert_eq!(
+            RespError::Moved { slot: 3999, address: "127.0.0.1:6381".to_string() }.to_string(),
+            "MOVED 3999 127.0.0.1:6381",
+        );
+        assert_eq!(
+            RespError::Ask { slot: 3999, address: "127.0.0.1:6381".to_string() }.to_string(),
+            "ASK 3999 127.0.0.1:6381",
+        );
+        assert!(RespError::CrossSlot.to_string().starts_with("CROSSSLOT "));
+        assert_eq!(RespError::NoAuth("Authentication required.".to_string()).to_string(), "NOAUTH Authentication required.");
+        assert_eq!(
+            RespError::NoPerm("this user has no permissions to run the 'set' command".to_string()).to_string(),
+            "NOPERM this user has no permissions to run the 'set' command",
+        );
+        assert_eq!(
+            RespError::Busy("Redis is busy running a script.".to_string()).to_string(),
+            "BUSY Redis is busy running a script.",
+        );
+    }
+}
@@ -1,9 +1,49 @@
+use std::fmt::{Display, Write};
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Protocol negotiated with HELLO. RESP3-only types are downgraded when
+/// encoding for RESP2 connections, the same way Redis does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl ProtocolVersion {
+    pub fn number(&self) -> i64 {
+        match self {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        }
+    }
+}
+
 pub enum RespResponse {
     SimpleString(String),
     Error(String),
     Integer(i64),
-    BulkString(Option<Vec<u8>>),
-    Array(Vec<RespResponse>),
+    BulkString(Option<Bytes>),
+    Array(Option<Vec<RespResponse>>),
+    Null,
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    /// Text with a three letter format such as `txt` or `mkd`.
+    Verbatim {
+        format: String,
+        text: String,
+    },
+    Map(Vec<(RespResponse, RespResponse)>),
+    Set(Vec<RespResponse>),
+    /// Out-of-band attributes describing the reply that follows them.
+    Attribute {
+        attributes: Vec<(RespResponse, RespResponse)>,
+        reply: Box<RespResponse>,
+    },
+    Push(Vec<RespResponse>),
+    /// Several top-level replies produced by one command, e.g. SUBSCRIBE a b.
+    Sequence(Vec<RespResponse>),
 }
 
 impl RespResponse {
@@ -11,38 +51,171 @@ impl RespResponse {
         RespResponse::SimpleString("PONG".to_string())
     }
 
-    pub fn echo(message: impl Into<String>) -> Self {
-        RespResponse::BulkString(Some(message.into().into_bytes()))
+    pub fn echo(message: Bytes) -> Self {
+        RespResponse::BulkString(Some(message))
     }
 
     pub fn set() -> Self {
         RespResponse::SimpleString("OK".to_string())
     }
 
-    pub fn get(value: impl Into<String>) -> Self {
-        RespResponse::BulkString(Some(value.into().into_bytes()))
+    pub fn get(value: Bytes) -> Self {
+        RespResponse::BulkString(Some(value))
     }
 
     pub fn null() -> Self {
         RespResponse::BulkString(None)
     }
 
-    pub fn to_resp(self) -> String {
+    pub fn ok() -> Self {
+        RespResponse::SimpleString("OK".to_string())
+    }
+
+    pub fn queued() -> Self {
+        RespResponse::SimpleString("QUEUED".to_string())
+    }
+
+    pub fn null_array() -> Self {
+        RespResponse::Array(None)
+    }
+
+    pub fn bulk(bytes: impl Into<Bytes>) -> Self {
+        RespResponse::BulkString(Some(bytes.into()))
+    }
+
+    pub fn bulk_array(items: Vec<Vec<u8>>) -> Self {
+        RespResponse::Array(Some(items.into_iter().map(RespResponse::bulk).collect()))
+    }
+
+    /// Appends the encoded reply to `out`. Bulk payloads are copied straight
+    /// from the shared value, byte for byte.
+    pub fn encode(&self, protocol: ProtocolVersion, out: &mut BytesMut) {
+        let resp3 = protocol == ProtocolVersion::Resp3;
         match self {
-            RespResponse::SimpleString(s) => format!("+{}\r\n", s),
-            RespResponse::Error(e) => format!("-{}\r\n", e),
-            RespResponse::Integer(i) => format!(":{}\r\n", i),
-            Self::BulkString(None) => "$-1\r\n".to_string(),
-            Self::BulkString(Some(bytes)) => {
-                format!("${}\r\n{}\r\n", bytes.len(), String::from_utf8_lossy(&bytes))
+            RespResponse::SimpleString(s) => Self::line(out, b'+', s),
+            RespResponse::Error(e) => Self::line(out, b'-', e),
+            RespResponse::Integer(i) => Self::line(out, b':', i),
+            Self::BulkString(None) | Self::Null if resp3 => out.extend_from_slice(b"_\r\n"),
+            Self::BulkString(None) | Self::Null => out.extend_from_slice(b"$-1\r\n"),
+            Self::BulkString(Some(bytes)) => Self::bulk_string(out, bytes),
+            Self::Array(None) if resp3 => out.extend_from_slice(b"_\r\n"),
+            Self::Array(None) => out.extend_from_slice(b"*-1\r\n"),
+            Self::Array(Some(arr)) => Self::aggregate(out, b'*', arr, protocol),
+            Self::Double(d) if resp3 => Self::line(out, b',', format_double(*d)),
+            Self::Double(d) => Self::bulk_string(out, format_double(*d).as_bytes()),
+            Self::Boolean(b) if resp3 => Self::line(out, b'#', if *b { 't' } else { 'f' }),
+            Self::Boolean(b) => Self::line(out, b':', *b as i64),
+            Self::BigNumber(n) if resp3 => Self::line(out, b'(', n),
+            Self::BigNumber(n) => Self::bulk_string(out, n.as_bytes()),
+            Self::Verbatim { format, text } if resp3 => {
+                Self::line(out, b'=', format.len() + 1 + text.len());
+                write!(out, "{}:{}\r\n", format, text).expect("writing to BytesMut cannot fail");
+            },
+            Self::Verbatim { text, .. } => Self::bulk_string(out, text.as_bytes()),
+            Self::Map(pairs) if resp3 => Self::pairs(out, b'%', pairs, protocol),
+            Self::Map(pairs) => Self::pairs(out, b'*', pairs, protocol),
+            Self::Set(items) => Self::aggregate(out, if resp3 { b'~' } else { b'*' }, items, protocol),
+            Self::Attribute { attributes, reply } if resp3 => {
+                Self::pairs(out, b'|', attributes, protocol);
+                reply.encode(protocol, out);
             },
-            Self::Array(arr) => {
-                let mut resp = format!("*{}\r\n", arr.len());
-                for item in arr {
-                    resp.push_str(&item.to_resp());
-                }
-                resp
-            }
+            Self::Attribute { reply, .. } => reply.encode(protocol, out),
+            Self::Push(items) => Self::aggregate(out, if resp3 { b'>' } else { b'*' }, items, protocol),
+            Self::Sequence(replies) => replies.iter().for_each(|reply| reply.encode(protocol, out)),
         }
     }
+
+    fn line(out: &mut BytesMut, prefix: u8, content: impl Display) {
+        out.put_u8(prefix);
+        write!(out, "{}", content).expect("writing to BytesMut cannot fail");
+        out.extend_from_slice(b"\r\n");
+    }
+
+    fn bulk_string(out: &mut BytesMut, bytes: &[u8]) {
+        Self::line(out, b'$', bytes.len());
+        out.extend_from_slice(bytes);
+        out.extend_from_slice(b"\r\n");
+    }
+
+    fn aggregate(out: &mut BytesMut, prefix: u8, items: &[RespResponse], protocol: ProtocolVersion) {
+        Self::line(out, prefix, items.len());
+        for item in items {
+            item.encode(protocol, out);
+        }
+    }
+
+    /// RESP3 counts pairs, RESP2 flattens them into an array of twice the size.
+    fn pairs(out: &mut BytesMut, prefix: u8, pairs: &[(RespResponse, RespResponse)], protocol: ProtocolVersion) {
+        let len = if prefix == b'*' { pairs.len() * 2 } else { pairs.len() };
+        Self::line(out, prefix, len);
+        for (key, value) in pairs {
+            key.encode(protocol, out);
+            value.encode(protocol, out);
+        }
+    }
+}
+
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_string()
+    } else if d.is_infinite() {
+        if d > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+    } else {
+        d.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(response: RespResponse) -> (String, String) {
+        let (mut resp2, mut resp3) = (BytesMut::new(), BytesMut::new());
+        response.encode(ProtocolVersion::Resp2, &mut resp2);
+        response.encode(ProtocolVersion::Resp3, &mut resp3);
+        (String::from_utf8(resp2.to_vec()).unwrap(), String::from_utf8(resp3.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_null_encoding() {
+        assert_eq!(encode(RespResponse::null()), ("$-1\r\n".to_string(), "_\r\n".to_string()));
+        assert_eq!(encode(RespResponse::null_array()), ("*-1\r\n".to_string(), "_\r\n".to_string()));
+    }
+
+    #[test]
+    fn test_bulk_encoding_is_binary_safe() {
+        let mut out = BytesMut::new();
+        RespResponse::get(Bytes::from_static(b"\xff\x00\r\n")).encode(ProtocolVersion::Resp2, &mut out);
+        assert_eq!(&out[..], b"$4\r\n\xff\x00\r\n\r\n");
+    }
+
+    #[test]
+    fn test_map_encoding() {
+        let map = RespResponse::Map(vec![(RespResponse::bulk("proto"), RespResponse::Integer(3))]);
+        assert_eq!(encode(map), (
+            "*2\r\n$5\r\nproto\r\n:3\r\n".to_string(),
+            "%1\r\n$5\r\nproto\r\n:3\r\n".to_string(),
+        ));
+    }
+
+    #[test]
+    fn test_scalar_encoding() {
+        assert_eq!(encode(RespResponse::Double(3.5)), ("$3\r\n3.5\r\n".to_string(), ",3.5\r\n".to_string()));
+        assert_eq!(encode(RespResponse::Double(f64::NEG_INFINITY)).1, ",-inf\r\n");
+        assert_eq!(encode(RespResponse::Boolean(true)), (":1\r\n".to_string(), "#t\r\n".to_string()));
+        assert_eq!(encode(RespResponse::BigNumber("12345678901234567890".to_string())).1, "(12345678901234567890\r\n");
+        let verbatim = RespResponse::Verbatim { format: "txt".to_string(), text: "Some string".to_string() };
+        assert_eq!(encode(verbatim), ("$11\r\nSome string\r\n".to_string(), "=15\r\ntxt:Some string\r\n".to_string()));
+    }
+
+    #[test]
+    fn test_push_and_attribute_encoding() {
+        let push = RespResponse::Push(vec![RespResponse::bulk("invalidate"), RespResponse::null_array()]);
+        assert_eq!(encode(push).1, ">2\r\n$10\r\ninvalidate\r\n_\r\n");
+        let attribute = RespResponse::Attribute {
+            attributes: vec![(RespResponse::bulk("ttl"), RespResponse::Integer(10))],
+            reply: Box::new(RespResponse::ok()),
+        };
+        assert_eq!(encode(attribute), ("+OK\r\n".to_string(), "|1\r\n$3\r\nttl\r\n:10\r\n+OK\r\n".to_string()));
+    }
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
+use bytes::Bytes;
 use crate::resp_parser::domain::command_handler::{CommandHandlerResult, CommandHandlerResultStatus};
-use crate::resp_parser::domain::resp_command::RespCommand;
-use crate::resp_parser::domain::resp_response::RespResponse;
+use crate::resp_parser::domain::pubsub_message::PubSubMessage;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::domain::resp_response::{ProtocolVersion, RespResponse};
 
+#[derive(Default)]
 pub struct ResponseBuilder {}
 
 impl ResponseBuilder {
@@ -9,31 +12,137 @@ impl ResponseBuilder {
         Self {}
     }
 
-    pub fn create(&self, handler_result: CommandHandlerResult) -> Result<RespResponse, String> {
-        let command = handler_result.get_resp_command();
-        match command {
-            RespCommand::Ping { message: _ } => {
-                match handler_result.get_status() {
-                    CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::pong()),
-                }
-            },
-            RespCommand::Echo { message: _ } => {
-                match handler_result.get_status() {
-                    CommandHandlerResultStatus::Ok(Some(msg)) => Ok(RespResponse::echo(msg)),
-                    _ => Err("Mismatched command result for ECHO".to_string()),
-                }
-            },
-            RespCommand::Set { key: _, value: _ } => {
-                match handler_result.get_status() {
-                    CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::set()),
-                }
-            },
-            RespCommand::Get { key : _ } => {
-                match handler_result.get_status() {
-                    CommandHandlerResultStatus::Ok(Some(value)) => Ok(RespResponse::get(value)),
-                    CommandHandlerResultStatus::Ok(None) => Ok(RespResponse::null()),
+    pub fn create(&self, handler_result: CommandHandlerResult) -> Result<RespResponse, RespError> {
+        self.build(&handler_result)
+    }
+
+    /// The reply to a result: errors and `QUEUED` are the same for every
+    /// command, anything else is up to the command's executor.
+    pub fn build(&self, handler_result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+        match handler_result.get_status() {
+            CommandHandlerResultStatus::Error(error) => Ok(RespResponse::Error(error.to_string())),
+            CommandHandlerResultStatus::Queued => Ok(RespResponse::queued()),
+            _ => handler_result.get_resp_command().executor().reply(handler_result),
+        }
+    }
+
+    /// Encodes a message pushed to a connection outside the request/reply flow.
+    pub fn create_message(&self, message: PubSubMessage, protocol: ProtocolVersion) -> RespResponse {
+        match message {
+            PubSubMessage::Message { channel, payload } => RespResponse::Push(vec![
+                RespResponse::bulk("message"),
+                RespResponse::bulk(channel),
+                RespResponse::bulk(payload),
+            ]),
+            PubSubMessage::PatternMessage { pattern, channel, payload } => RespResponse::Push(vec![
+                RespResponse::bulk("pmessage"),
+                RespResponse::bulk(pattern),
+                RespResponse::bulk(channel),
+                RespResponse::bulk(payload),
+            ]),
+            PubSubMessage::ShardMessage { channel, payload } => RespResponse::Push(vec![
+                RespResponse::bulk("smessage"),
+                RespResponse::bulk(channel),
+                RespResponse::bulk(payload),
+            ]),
+            PubSubMessage::Invalidate { keys } => {
+                let keys = RespResponse::Array(keys.map(|keys| keys.into_iter().map(RespResponse::bulk).collect()));
+                match protocol {
+                    ProtocolVersion::Resp3 => RespResponse::Push(vec![RespResponse::bulk("invalidate"), keys]),
+                    ProtocolVersion::Resp2 => RespResponse::Push(vec![
+                        RespResponse::bulk("message"),
+                        RespResponse::bulk("__redis__:invalidate"),
+                        keys,
+                    ]),
                 }
             },
+            PubSubMessage::TrackingRedirBroken { client_id } => RespResponse::Push(vec![
+                RespResponse::bulk("tracking-redir-broken"),
+                RespResponse::Integer(client_id as i64),
+            ]),
         }
     }
-}
\ No newline at end of file
+}
+
+/// The error for a result its command's reply does not expect, which the
+/// handler rules out.
+pub fn mismatched(result: &CommandHandlerResult) -> RespError {
+    let name = result.get_resp_command().name().replace('|', " ").to_uppercase();
+    RespError::Err(format!("Mismatched command result for {}", name))
+}
+
+/// `+OK`, for commands that only succeed or fail.
+pub fn ok_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Ok(_) => Ok(RespResponse::ok()),
+        _ => Err(mismatched(result)),
+    }
+}
+
+/// A status line such as `+Background saving started`, or `+OK` without one.
+pub fn status_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Ok(Some(status)) => {
+            Ok(RespResponse::SimpleString(String::from_utf8_lossy(status).to_string()))
+        },
+        CommandHandlerResultStatus::Ok(None) => Ok(RespResponse::ok()),
+        _ => Err(mismatched(result)),
+    }
+}
+
+/// A bulk string, or null when there is no value.
+pub fn bulk_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Ok(Some(value)) => Ok(RespResponse::get(value.clone())),
+        CommandHandlerResultStatus::Ok(None) => Ok(RespResponse::null()),
+        _ => Err(mismatched(result)),
+    }
+}
+
+pub fn integer_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Integer(value) => Ok(RespResponse::Integer(*value)),
+        _ => Err(mismatched(result)),
+    }
+}
+
+/// An array of bulk strings, e.g. channel or command names.
+pub fn list_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::List(items) => Ok(RespResponse::bulk_array(items.clone())),
+        _ => Err(mismatched(result)),
+    }
+}
+
+/// Names paired with their counts in one flat array, e.g. PUBSUB NUMSUB.
+pub fn counts_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Counts(counts) => {
+            let replies = counts
+                .iter()
+                .flat_map(|(name, count)| [RespResponse::bulk(name.clone()), RespResponse::Integer(*count)])
+                .collect();
+            Ok(RespResponse::Array(Some(replies)))
+        },
+        _ => Err(mismatched(result)),
+    }
+}
+
+/// One push per (un)subscribed name, of the kind named after the command.
+pub fn subscriptions_reply(result: &CommandHandlerResult) -> Result<RespResponse, RespError> {
+    match result.get_status() {
+        CommandHandlerResultStatus::Subscriptions(subscriptions) => {
+            let kind = result.get_resp_command().name();
+            let replies = subscriptions
+                .iter()
+                .map(|(name, count)| RespResponse::Push(vec![
+                    RespResponse::bulk(kind),
+                    RespResponse::BulkString(name.clone().map(Bytes::from)),
+                    RespResponse::Integer(*count as i64),
+                ]))
+                .collect();
+            Ok(RespResponse::Sequence(replies))
+        },
+        _ => Err(mismatched(result)),
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::resp_response::ProtocolVersion;
+use crate::resp_parser::domain::tracking::TrackingOptions;
+use crate::resp_parser::infra::memory::channel_registry::Subscriber;
+use crate::resp_parser::infra::replication::Attachment;
+
+pub struct WatchedKey {
+    pub db: usize,
+    pub key: Vec<u8>,
+    pub version: u64,
+}
+
+/// What a replica told its master with REPLCONF before PSYNC.
+#[derive(Default)]
+pub struct ReplicaAnnouncement {
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    /// Capabilities announced with `capa`, such as `psync2`.
+    pub capabilities: Vec<String>,
+}
+
+pub struct Transaction {
+    commands: Vec<RespCommand>,
+    has_errors: bool,
+}
+
+impl Transaction {
+    pub fn has_errors(&self) -> bool {
+        self.has_errors
+    }
+
+    pub fn into_commands(self) -> Vec<RespCommand> {
+        self.commands
+    }
+}
+
+/// Per-connection state that outlives a single command.
+pub struct Session {
+    client_id: u64,
+    subscriber: Subscriber,
+    protocol: ProtocolVersion,
+    name: Option<Vec<u8>>,
+    db: usize,
+    transaction: Option<Transaction>,
+    /// Whether EXEC is running the queued commands, which must not block.
+    is_running_transaction: bool,
+    watched_keys: Vec<WatchedKey>,
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+    shard_channels: HashSet<Vec<u8>>,
+    tracking: Option<TrackingOptions>,
+    /// Set by CLIENT CACHING for the next command only.
+    caching: Option<bool>,
+    /// Address the client connected from.
+    peer_ip: Option<String>,
+    announcement: ReplicaAnnouncement,
+    /// Whether PSYNC made this connection a replica's.
+    is_replica: bool,
+    /// Whether this is the session of the stream from the master, whose
+    /// writes a read-only replica applies.
+    is_master_link: bool,
+    /// Set by PSYNC until `handle_connection` starts sending it.
+    attachment: Option<Attachment>,
+    /// Replication offset right after the last write of this client,
+    /// which WAIT waits for.
+    write_offset: u64,
+}
+
+impl Session {
+    pub fn new(client_id: u64, subscriber: Subscriber) -> Self {
+        Self {
+            client_id,
+            subscriber,
+            protocol: ProtocolVersion::Resp2,
+            name: None,
+            db: 0,
+            transaction: None,
+            is_running_transaction: false,
+            watched_keys: Vec::new(),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            shard_channels: HashSet::new(),
+            tracking: None,
+            caching: None,
+            peer_ip: None,
+            announcement: ReplicaAnnouncement::default(),
+            is_replica: false,
+            is_master_link: false,
+            attachment: None,
+            write_offset: 0,
+        }
+    }
+
+    /// The session a replica applies its master's stream in.
+    pub fn new_master_link(subscriber: Subscriber) -> Self {
+        Self {
+            is_master_link: true,
+            ..Self::new(0, subscriber)
+        }
+    }
+
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Sending half of the queue `handle_connection` drains into the socket.
+    pub fn subscriber(&self) -> Subscriber {
+        self.subscriber.clone()
+    }
+
+    pub fn protocol(&self) -> ProtocolVersion {
+        self.protocol
+    }
+
+    pub fn set_protocol(&mut self, protocol: ProtocolVersion) {
+        self.protocol = protocol;
+    }
+
+    pub fn name(&self) -> Option<&[u8]> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: Option<Vec<u8>>) {
+        self.name = name;
+    }
+
+    pub fn db(&self) -> usize {
+        self.db
+    }
+
+    pub fn select(&mut self, db: usize) {
+        self.db = db;
+    }
+
+    pub fn is_in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    pub fn begin_transaction(&mut self) {
+        self.transaction = Some(Transaction {
+            commands: Vec::new(),
+            has_errors: false,
+        });
+    }
+
+    pub fn queue(&mut self, command: RespCommand) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.commands.push(command);
+        }
+    }
+
+    /// Makes the next EXEC fail with EXECABORT, as a queued command was rejected.
+    pub fn mark_transaction_dirty(&mut self) {
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.has_errors = true;
+        }
+    }
+
+    pub fn take_transaction(&mut self) -> Option<Transaction> {
+        self.transaction.take()
+    }
+
+    pub fn is_running_transaction(&self) -> bool {
+        self.is_running_transaction
+    }
+
+    pub fn set_running_transaction(&mut self, is_running_transaction: bool) {
+        self.is_running_transaction = is_running_transaction;
+    }
+
+    pub fn is_watching(&self, db: usize, key: &[u8]) -> bool {
+        self.watched_keys.iter().any(|watched| watched.db == db && watched.key == key)
+    }
+
+    pub fn watch(&mut self, watched_key: WatchedKey) {
+        self.watched_keys.push(watched_key);
+    }
+
+    pub fn watched_keys(&self) -> &[WatchedKey] {
+        &self.watched_keys
+    }
+
+    pub fn take_watched_keys(&mut self) -> Vec<WatchedKey> {
+        std::mem::take(&mut self.watched_keys)
+    }
+
+    /// Subscriber mode: only (P|S)SUBSCRIBE, (P|S)UNSUBSCRIBE, PING, QUIT and RESET are allowed.
+    pub fn is_subscribed(&self) -> bool {
+        self.subscription_count() > 0 || !self.shard_channels.is_empty()
+    }
+
+    pub fn subscription_count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// Returns false when the session was already subscribed to the channel.
+    pub fn subscribe(&mut self, channel: Vec<u8>) -> bool {
+        self.channels.insert(channel)
+    }
+
+    pub fn unsubscribe(&mut self, channel: &[u8]) -> bool {
+        self.channels.remove(channel)
+    }
+
+    pub fn channels(&self) -> Vec<Vec<u8>> {
+        self.channels.iter().cloned().collect()
+    }
+
+    pub fn psubscribe(&mut self, pattern: Vec<u8>) -> bool {
+        self.patterns.insert(pattern)
+    }
+
+    pub fn punsubscribe(&mut self, pattern: &[u8]) -> bool {
+        self.patterns.remove(pattern)
+    }
+
+    pub fn patterns(&self) -> Vec<Vec<u8>> {
+        self.patterns.iter().cloned().collect()
+    }
+
+    pub fn ssubscribe(&mut self, channel: Vec<u8>) -> bool {
+        self.shard_channels.insert(channel)
+    }
+
+    pub fn sunsubscribe(&mut self, channel: &[u8]) -> bool {
+        self.shard_channels.remove(channel)
+    }
+
+    pub fn shard_channels(&self) -> Vec<Vec<u8>> {
+        self.shard_channels.iter().cloned().collect()
+    }
+
+    /// Shard channel subscriptions are counted separately from the others.
+    pub fn shard_subscription_count(&self) -> usize {
+        self.shard_channels.len()
+    }
+
+    pub fn tracking(&self) -> Option<&TrackingOptions> {
+        self.tracking.as_ref()
+    }
+
+    pub fn set_tracking(&mut self, tracking: Option<TrackingOptions>) {
+        self.tracking = tracking;
+        self.caching = None;
+    }
+
+    pub fn set_caching(&mut self, caching: bool) {
+        self.caching = Some(caching);
+    }
+
+    pub fn clear_caching(&mut self) {
+        self.caching = None;
+    }
+
+    /// Whether keys read by the current command must be remembered for
+    /// invalidation, following the OPTIN/OPTOUT and CLIENT CACHING rules.
+    pub fn tracks_reads(&self) -> bool {
+        match &self.tracking {
+            Some(options) if options.bcast => false,
+            Some(options) if options.optin => self.caching == Some(true),
+            Some(options) if options.optout => self.caching != Some(false),
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn peer_ip(&self) -> Option<&str> {
+        self.peer_ip.as_deref()
+    }
+
+    pub fn set_peer_ip(&mut self, ip: String) {
+        self.peer_ip = Some(ip);
+    }
+
+    pub fn announcement(&self) -> &ReplicaAnnouncement {
+        &self.announcement
+    }
+
+    pub fn announcement_mut(&mut self) -> &mut ReplicaAnnouncement {
+        &mut self.announcement
+    }
+
+    pub fn is_master_link(&self) -> bool {
+        self.is_master_link
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.is_replica
+    }
+
+    /// Turns the connection into a replica's, which is sent `attachment`.
+    pub fn start_replica(&mut self, attachment: Attachment) {
+        self.is_replica = true;
+        self.attachment = Some(attachment);
+    }
+
+    pub fn take_attachment(&mut self) -> Option<Attachment> {
+        self.attachment.take()
+    }
+
+    pub fn write_offset(&self) -> u64 {
+        self.write_offset
+    }
+
+    pub fn set_write_offset(&mut self, offset: u64) {
+        self.write_offset = offset;
+    }
+}
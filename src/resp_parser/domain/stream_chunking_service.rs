@@ -1,7 +1,10 @@
+use bytes::Bytes;
+
 #[derive(Debug)]
 pub enum StreamChunkingServiceError {
     IncompleteCommand,
-    InvalidFormat,
+    /// Malformed framing, with the reason Redis would report.
+    InvalidFormat(String),
 }
 
 impl std::fmt::Display for StreamChunkingServiceError {
@@ -9,32 +12,43 @@ impl std::fmt::Display for StreamChunkingServiceError {
         match self {
             StreamChunkingServiceError::IncompleteCommand =>
                 write!(f, "Incomplete command"),
-            StreamChunkingServiceError::InvalidFormat =>
-                write!(f, "Invalid format"),
+            StreamChunkingServiceError::InvalidFormat(reason) =>
+                write!(f, "Invalid format: {}", reason),
         }
     }
 }
 
 impl std::error::Error for StreamChunkingServiceError {}
 
-pub struct StringCommand(String);
+/// A request split into its arguments, the command name first. Arguments
+/// are raw bytes and may hold anything, CRLF included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawCommand(Vec<Bytes>);
 
-impl std::fmt::Display for StringCommand {
+impl std::fmt::Display for RawCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        let arguments: Vec<_> = self.0.iter().map(|argument| String::from_utf8_lossy(argument)).collect();
+        write!(f, "{}", arguments.join(" "))
     }
 }
 
-impl StringCommand {
-    pub fn new(s: String) -> Self {
-        StringCommand(s)
+impl RawCommand {
+    pub fn new(arguments: Vec<Bytes>) -> Self {
+        RawCommand(arguments)
+    }
+
+    pub fn into_arguments(self) -> Vec<Bytes> {
+        self.0
     }
-    pub fn as_str(&self) -> &str {
-        self.0.as_str()
+}
+
+impl<T: AsRef<[u8]>> From<&[T]> for RawCommand {
+    fn from(arguments: &[T]) -> Self {
+        RawCommand(arguments.iter().map(|argument| Bytes::copy_from_slice(argument.as_ref())).collect())
     }
 }
 
 pub trait StreamChunkingService {
     fn new() -> Self;
-    fn next(&mut self, buffer: &[u8]) -> Result<Vec<StringCommand>, StreamChunkingServiceError>;
+    fn next(&mut self, buffer: &[u8]) -> Result<Vec<RawCommand>, StreamChunkingServiceError>;
 }
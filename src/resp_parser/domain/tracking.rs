@@ -0,0 +1,15 @@
+/// Options given to `CLIENT TRACKING on`.
+#[derive(Clone, Default)]
+pub struct TrackingOptions {
+    /// Client id that receives the invalidations instead of this connection.
+    pub redirect: Option<u64>,
+    /// Broadcast mode: invalidate every key matching `prefixes`, read or not.
+    pub bcast: bool,
+    pub prefixes: Vec<Vec<u8>>,
+    /// Only track reads right after `CLIENT CACHING yes`.
+    pub optin: bool,
+    /// Track reads unless preceded by `CLIENT CACHING no`.
+    pub optout: bool,
+    /// Skip invalidations for keys this connection modified itself.
+    pub noloop: bool,
+}
@@ -0,0 +1,496 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use crate::resp_parser::domain::config::AppendFsync;
+use crate::resp_parser::infra::aof::encode_command;
+use crate::resp_parser::infra::aof::manifest::{self, AofFile, FileType, Manifest};
+use crate::resp_parser::infra::memory::config_registry::ConfigRegistry;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, Snapshot, Storage};
+use crate::resp_parser::infra::rdb::{write_file, writer};
+
+/// Commands logged but not written to the file yet.
+#[derive(Default)]
+struct Feed {
+    enabled: bool,
+    buffer: Vec<u8>,
+    /// Database the logged commands run in, so SELECT is only logged when
+    /// it changes.
+    selected_db: Option<usize>,
+}
+
+/// The incremental file being appended to.
+struct Log {
+    file: File,
+    size: u64,
+    /// Bytes in the base and the earlier incremental files.
+    earlier_size: u64,
+    last_fsync: Instant,
+    unsynced: bool,
+}
+
+impl Log {
+    fn open(path: &Path, earlier_size: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            file,
+            size,
+            earlier_size,
+            last_fsync: Instant::now(),
+            unsynced: false,
+        })
+    }
+
+    /// Writes all of `data` or, on failure, none of it, so a retry does not
+    /// leave half a command in the middle of the file.
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if let Err(e) = self.file.write_all(data) {
+            let _ = self.file.set_len(self.size);
+            return Err(e);
+        }
+        self.size += data.len() as u64;
+        self.unsynced = true;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.last_fsync = Instant::now();
+        self.unsynced = false;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct RewriteState {
+    in_progress: bool,
+    last_status_ok: bool,
+    /// Duration of the last rewrite, -1 before the first.
+    last_seconds: i64,
+    rewrites: u64,
+    /// Size of all files after the last rewrite, which automatic rewrites
+    /// measure growth against.
+    base_size: u64,
+}
+
+/// Append-only file bookkeeping as INFO persistence reports it.
+pub struct AofState {
+    pub enabled: bool,
+    pub last_write_ok: bool,
+    pub current_size: u64,
+    pub base_size: u64,
+    pub rewrite_in_progress: bool,
+    pub last_rewrite_ok: bool,
+    pub last_rewrite_seconds: i64,
+    pub rewrites: u64,
+}
+
+struct Inner {
+    feed: Mutex<Feed>,
+    log: Mutex<Option<Log>>,
+    manifest: Mutex<Manifest>,
+    rewrite: Mutex<RewriteState>,
+    fsync: Mutex<AppendFsync>,
+    last_write_ok: AtomicBool,
+    fsync_in_progress: AtomicBool,
+    /// Bytes written to the files since startup, whichever file they went to.
+    written: AtomicU64,
+    /// How many of the `written` bytes are fsynced.
+    fsynced: watch::Sender<u64>,
+}
+
+/// Logs writes to the multi-part append-only file in `dir/appenddirname`:
+/// a base file, incremental files appended to since, and the manifest
+/// listing them.
+///
+/// Writes are fed while the keyspace lock is held, so the log has them in
+/// the order they were applied. They are buffered and written before the
+/// reply goes out, and fsynced according to `appendfsync`.
+#[derive(Clone)]
+pub struct Appender {
+    storage: Storage,
+    config_registry: ConfigRegistry,
+    inner: Arc<Inner>,
+}
+
+impl Appender {
+    pub fn new(storage: Storage, config_registry: ConfigRegistry) -> Self {
+        let rewrite = RewriteState {
+            in_progress: false,
+            last_status_ok: true,
+            last_seconds: -1,
+            rewrites: 0,
+            base_size: 0,
+        };
+        Self {
+            storage,
+            config_registry,
+            inner: Arc::new(Inner {
+                feed: Mutex::default(),
+                log: Mutex::default(),
+                manifest: Mutex::default(),
+                rewrite: Mutex::new(rewrite),
+                fsync: Mutex::new(AppendFsync::EverySec),
+                last_write_ok: AtomicBool::new(true),
+                fsync_in_progress: AtomicBool::new(false),
+                written: AtomicU64::new(0),
+                fsynced: watch::channel(0).0,
+            }),
+        }
+    }
+
+    pub fn set_fsync(&self, fsync: AppendFsync) {
+        *self.inner.fsync.lock().unwrap() = fsync;
+    }
+
+    pub fn state(&self) -> AofState {
+        let log = self.inner.log.lock().unwrap();
+        let rewrite = self.inner.rewrite.lock().unwrap().clone();
+        AofState {
+            enabled: log.is_some(),
+            last_write_ok: self.inner.last_write_ok.load(Ordering::Relaxed),
+            current_size: log.as_ref().map_or(0, |log| log.earlier_size + log.size),
+            base_size: rewrite.base_size,
+            rewrite_in_progress: rewrite.in_progress,
+            last_rewrite_ok: rewrite.last_status_ok,
+            last_rewrite_seconds: rewrite.last_seconds,
+            rewrites: rewrite.rewrites,
+        }
+    }
+
+    /// Position of the last write in the files, to compare with what
+    /// `watch_fsynced` reports.
+    pub fn written(&self) -> u64 {
+        self.inner.written.load(Ordering::Relaxed)
+    }
+
+    /// How much of what was written is fsynced, which WAITAOF waits on.
+    pub fn watch_fsynced(&self) -> watch::Receiver<u64> {
+        self.inner.fsynced.subscribe()
+    }
+
+    /// Records that everything written up to `written` is fsynced.
+    fn fsynced(&self, written: u64) {
+        self.inner.fsynced.send_if_modified(|fsynced| {
+            let modified = written > *fsynced;
+            *fsynced = (*fsynced).max(written);
+            modified
+        });
+    }
+
+    /// Logs a write made in `db`, or in no database in particular.
+    /// Does nothing while the append-only file is off.
+    pub fn feed(&self, db: Option<usize>, arguments: &[&[u8]]) {
+        let mut feed = self.inner.feed.lock().unwrap();
+        if !feed.enabled {
+            return;
+        }
+        if let Some(db) = db.filter(|db| feed.selected_db != Some(*db)) {
+            encode_command(&mut feed.buffer, &[b"SELECT", db.to_string().as_bytes()]);
+            feed.selected_db = Some(db);
+        }
+        encode_command(&mut feed.buffer, arguments);
+    }
+
+    /// Continues the files of `manifest` after they were loaded at
+    /// startup, appending to the last incremental file.
+    pub async fn open(&self, mut manifest: Manifest) -> io::Result<()> {
+        let (dir, prefix) = self.location().await;
+        let incr = match manifest.incrs.last() {
+            Some(incr) => incr.clone(),
+            None => {
+                let incr = manifest.next_incr(&prefix);
+                manifest.incrs.push(incr.clone());
+                persist(&dir, &prefix, &manifest)?;
+                incr
+            },
+        };
+        remove_history(&dir, &prefix, &mut manifest);
+        let mut earlier_size = 0;
+        for file in manifest.files().filter(|file| file.name != incr.name) {
+            earlier_size += fs::metadata(dir.join(&file.name))?.len();
+        }
+        let log = Log::open(&dir.join(&incr.name), earlier_size)?;
+        self.inner.rewrite.lock().unwrap().base_size = log.earlier_size + log.size;
+        *self.inner.log.lock().unwrap() = Some(log);
+        *self.inner.manifest.lock().unwrap() = manifest;
+        self.enable_feed();
+        Ok(())
+    }
+
+    /// Turns the append-only file on. Writes are logged right away, into
+    /// an incremental file that the rewrite lists after the base it writes
+    /// from the current data.
+    pub async fn start(&self) -> Result<(), String> {
+        self.rewrite(true).await
+    }
+
+    /// Writes and fsyncs whatever is pending, then closes the file.
+    pub fn stop(&self) {
+        self.inner.feed.lock().unwrap().enabled = false;
+        self.flush();
+        if let Some(mut log) = self.inner.log.lock().unwrap().take() {
+            match log.sync() {
+                Ok(()) => self.fsynced(self.written()),
+                Err(e) => println!("Error fsyncing the AOF file: {}", e),
+            }
+        }
+    }
+
+    /// Compacts the files into a new base written from a copy of the data,
+    /// like BGREWRITEAOF. Writes made meanwhile go to a new incremental
+    /// file, which the new manifest keeps, so none are lost. With `enable`
+    /// this also turns the append-only file on.
+    pub async fn rewrite(&self, enable: bool) -> Result<(), String> {
+        {
+            let mut rewrite = self.inner.rewrite.lock().unwrap();
+            if rewrite.in_progress {
+                return Err("Background append only file rewriting already in progress".to_string());
+            }
+            rewrite.in_progress = true;
+        }
+        let (dir, prefix) = self.location().await;
+        let prepared = self.prepare_rewrite(&dir, &prefix, enable).await;
+        let (snapshot, base, incr) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                println!("Can't rewrite append only file in background: {}", e);
+                self.inner.rewrite.lock().unwrap().in_progress = false;
+                return Err("Can't execute an AOF background rewriting. Please check the server logs for more information.".to_string());
+            },
+        };
+
+        let appender = self.clone();
+        tokio::spawn(async move {
+            let started_at = Instant::now();
+            let path = dir.join(&base.name);
+            let result = tokio::task::spawn_blocking(move || {
+                write_file(&path, &writer::encode(&snapshot, unix_time_ms(), true))
+            }).await;
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => Err(io::Error::other(e)),
+            };
+            let result = result.and_then(|()| appender.finish_rewrite(&dir, &prefix, base, incr));
+            // Without a base the new incremental file is not a usable
+            // append-only file on its own.
+            if result.is_err() && enable {
+                println!("Turning the AOF off as its first rewrite failed");
+                appender.stop();
+                let _ = appender.config_registry.set(&[("appendonly".to_string(), "no".to_string())]).await;
+            }
+            let mut rewrite = appender.inner.rewrite.lock().unwrap();
+            rewrite.in_progress = false;
+            rewrite.last_seconds = started_at.elapsed().as_secs() as i64;
+            rewrite.last_status_ok = result.is_ok();
+            match result {
+                Ok(()) => {
+                    rewrite.rewrites += 1;
+                    println!("Background AOF rewrite finished successfully");
+                },
+                Err(e) => println!("Background AOF rewrite failed: {}", e),
+            }
+        });
+        Ok(())
+    }
+
+    /// Writes the buffered commands, fsyncing them with `appendfsync
+    /// always`. A failed write is kept for the next try; with `always` it
+    /// stops the server, as the write may already have been acknowledged
+    /// as durable.
+    pub fn flush(&self) {
+        // The log lock is taken first so concurrent flushes write their
+        // buffers in the order they were fed.
+        let mut log = self.inner.log.lock().unwrap();
+        let Some(log) = log.as_mut() else {
+            return;
+        };
+        let buffer = std::mem::take(&mut self.inner.feed.lock().unwrap().buffer);
+        if buffer.is_empty() {
+            return;
+        }
+        let always = *self.inner.fsync.lock().unwrap() == AppendFsync::Always;
+        let result = log.write(&buffer).and_then(|()| {
+            let written = self.inner.written.fetch_add(buffer.len() as u64, Ordering::Relaxed) + buffer.len() as u64;
+            if always {
+                log.sync()?;
+                self.fsynced(written);
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.inner.last_write_ok.store(true, Ordering::Relaxed),
+            Err(e) => {
+                println!("Error writing to the AOF file: {}", e);
+                self.inner.last_write_ok.store(false, Ordering::Relaxed);
+                if always {
+                    println!("Can't recover from AOF write error when the AOF fsync policy is 'always'. Exiting...");
+                    std::process::exit(1);
+                }
+                self.inner.feed.lock().unwrap().buffer.splice(0..0, buffer);
+            },
+        }
+    }
+
+    /// Writes pending commands and, with `appendfsync everysec`, fsyncs
+    /// them on a blocking thread once a second. Called periodically.
+    pub fn cron(&self) {
+        self.flush();
+        if *self.inner.fsync.lock().unwrap() != AppendFsync::EverySec {
+            return;
+        }
+        let mut log = self.inner.log.lock().unwrap();
+        let Some(log) = log.as_mut().filter(|log| log.unsynced && log.last_fsync.elapsed() >= Duration::from_secs(1)) else {
+            return;
+        };
+        if self.inner.fsync_in_progress.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let file = match log.file.try_clone() {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Error fsyncing the AOF file: {}", e);
+                self.inner.fsync_in_progress.store(false, Ordering::Relaxed);
+                return;
+            },
+        };
+        log.last_fsync = Instant::now();
+        log.unsynced = false;
+        let written = self.written();
+        let appender = self.clone();
+        tokio::task::spawn_blocking(move || {
+            match file.sync_data() {
+                Ok(()) => appender.fsynced(written),
+                Err(e) => println!("Error fsyncing the AOF file: {}", e),
+            }
+            appender.inner.fsync_in_progress.store(false, Ordering::Relaxed);
+        });
+    }
+
+    /// Starts a rewrite once the files grew by `auto-aof-rewrite-percentage`
+    /// since the last one and are at least `auto-aof-rewrite-min-size`.
+    pub async fn rewrite_if_grown(&self) {
+        let state = self.state();
+        if !state.enabled || state.rewrite_in_progress {
+            return;
+        }
+        let (percentage, min_size) = self.config_registry
+            .read(|config| (config.auto_aof_rewrite_percentage, config.auto_aof_rewrite_min_size))
+            .await;
+        if percentage == 0 || state.current_size < min_size {
+            return;
+        }
+        let base_size = state.base_size.max(1);
+        let growth = state.current_size.saturating_sub(base_size) * 100 / base_size;
+        if growth >= percentage {
+            println!("Starting automatic rewriting of AOF on {}% growth", growth);
+            let _ = self.rewrite(false).await;
+        }
+    }
+
+    /// Switches to a new incremental file and copies the data, both while
+    /// writers wait, so every write is either in the copy or in the new
+    /// file. The manifest only lists the new file once there is a base to
+    /// go with it, i.e. right away if the files were already in use.
+    async fn prepare_rewrite(&self, dir: &Path, prefix: &str, enable: bool) -> io::Result<(Snapshot, AofFile, Option<AofFile>)> {
+        fs::create_dir_all(dir)?;
+        let keyspace = self.storage.write().await;
+        let was_enabled = self.inner.log.lock().unwrap().is_some();
+        let mut incr = None;
+        if was_enabled || enable {
+            self.flush();
+            let mut manifest = self.inner.manifest.lock().unwrap();
+            let mut log = self.inner.log.lock().unwrap();
+            let next = manifest.next_incr(prefix);
+            let earlier_size = log.as_ref().map_or(0, |log| log.earlier_size + log.size);
+            let opened = Log::open(&dir.join(&next.name), earlier_size)?;
+            if was_enabled {
+                let mut listed = manifest.clone();
+                listed.incrs.push(next.clone());
+                persist(dir, prefix, &listed)?;
+                *manifest = listed;
+            }
+            *log = Some(opened);
+            incr = Some(next);
+        }
+        if enable {
+            self.enable_feed();
+        } else {
+            self.inner.feed.lock().unwrap().selected_db = None;
+        }
+        let snapshot = keyspace.snapshot();
+        drop(keyspace);
+        let base = self.inner.manifest.lock().unwrap().next_base(prefix);
+        Ok((snapshot, base, incr))
+    }
+
+    /// Makes the new base and the incremental file opened with it the
+    /// whole append-only file, then deletes the files they replace.
+    fn finish_rewrite(&self, dir: &Path, prefix: &str, base: AofFile, incr: Option<AofFile>) -> io::Result<()> {
+        let base_size = fs::metadata(dir.join(&base.name))?.len();
+        let mut manifest = self.inner.manifest.lock().unwrap();
+        let mut rewritten = manifest.clone();
+        let replaced: Vec<AofFile> = rewritten.base
+            .take()
+            .into_iter()
+            .chain(rewritten.incrs.drain(..).filter(|file| Some(file) != incr.as_ref()))
+            .collect();
+        rewritten.history.extend(replaced.into_iter().map(|file| AofFile { kind: FileType::History, ..file }));
+        rewritten.base = Some(base.clone());
+        rewritten.incrs = incr.into_iter().collect();
+        if let Err(e) = persist(dir, prefix, &rewritten) {
+            let _ = fs::remove_file(dir.join(&base.name));
+            return Err(e);
+        }
+        remove_history(dir, prefix, &mut rewritten);
+        *manifest = rewritten;
+        drop(manifest);
+
+        if let Some(log) = self.inner.log.lock().unwrap().as_mut() {
+            log.earlier_size = base_size;
+            self.inner.rewrite.lock().unwrap().base_size = base_size + log.size;
+        }
+        Ok(())
+    }
+
+    fn enable_feed(&self) {
+        let mut feed = self.inner.feed.lock().unwrap();
+        feed.enabled = true;
+        feed.buffer.clear();
+        feed.selected_db = None;
+    }
+
+    /// The append-only directory and the prefix of the files in it.
+    async fn location(&self) -> (PathBuf, String) {
+        self.config_registry
+            .read(|config| (Path::new(&config.dir).join(&config.appenddirname), config.appendfilename.clone()))
+            .await
+    }
+}
+
+/// Replaces the manifest on disk atomically.
+pub fn persist(dir: &Path, prefix: &str, manifest: &Manifest) -> io::Result<()> {
+    write_file(&dir.join(manifest::file_name(prefix)), manifest.encode().as_bytes())
+}
+
+/// Deletes the files a rewrite replaced and persists the manifest without
+/// them.
+fn remove_history(dir: &Path, prefix: &str, manifest: &mut Manifest) {
+    if manifest.history.is_empty() {
+        return;
+    }
+    for file in manifest.history.drain(..) {
+        if let Err(e) = fs::remove_file(dir.join(&file.name)) {
+            if e.kind() != io::ErrorKind::NotFound {
+                println!("Can't remove the replaced AOF file {}: {}", file.name, e);
+            }
+        }
+    }
+    if let Err(e) = persist(dir, prefix, manifest) {
+        println!("Can't persist the AOF manifest after removing history files: {}", e);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashSet};
+use crate::resp_parser::domain::command_table;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::stream_chunking_service::RawCommand;
+use crate::resp_parser::infra::aof::reader::AofReader;
+use crate::resp_parser::infra::aof::AofError;
+use crate::resp_parser::infra::rdb::check::{self as rdb_check, Report as RdbReport};
+
+/// What `check` found in one append-only file, as redis-check-aof
+/// reports it.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub size: usize,
+    /// The RDB preamble of the file, if it starts with one.
+    pub preamble: Option<RdbReport>,
+    /// Commands read of each name.
+    pub commands: BTreeMap<&'static str, u64>,
+    /// Distinct keys written in each database.
+    pub keys: BTreeMap<usize, u64>,
+    /// Where the valid commands end, which is what `--fix` truncates to.
+    pub valid_up_to: usize,
+    /// Line `valid_up_to` is on, counting from 1.
+    pub valid_up_to_line: usize,
+    pub error: Option<AofError>,
+}
+
+impl Report {
+    /// Whether cutting the file at `valid_up_to` leaves a file that loads.
+    /// A broken preamble would leave nothing worth keeping.
+    pub fn is_fixable(&self) -> bool {
+        !matches!(self.error, Some(AofError::Preamble(_)))
+    }
+
+    /// Prints the report in the format of redis-check-aof.
+    pub fn print(&self, name: &str) {
+        if let Some(preamble) = &self.preamble {
+            println!("[offset 0] Checking RDB preamble of {}", name);
+            preamble.print();
+        }
+        if let Some(error) = &self.error {
+            println!("{}", error);
+        }
+        println!(
+            "AOF analyzed: filename={}, size={}, ok_up_to={}, ok_up_to_line={}, diff={}",
+            name,
+            self.size,
+            self.valid_up_to,
+            self.valid_up_to_line,
+            self.size - self.valid_up_to,
+        );
+        println!("[info] {} commands read", self.commands.values().sum::<u64>());
+        for (name, count) in &self.commands {
+            println!("[info] {}: {}", name, count);
+        }
+        for (db, keys) in &self.keys {
+            println!("[info] db {}: {} keys written", db, keys);
+        }
+    }
+}
+
+/// Reads the commands of an append-only file through the parser the
+/// server loads it with, stopping at the first one it would reject. A
+/// transaction missing its EXEC is dropped, as loading does.
+pub fn check(data: &[u8], now_ms: u64) -> Report {
+    let mut report = Report { size: data.len(), ..Report::default() };
+    let mut offset = 0;
+    if data.starts_with(b"REDIS") {
+        let mut preamble = rdb_check::check(data, now_ms);
+        let result = match (preamble.error.take(), preamble.length) {
+            (Some(corruption), _) => Err(AofError::Preamble(corruption.error)),
+            (None, length) => Ok(length.unwrap_or_default()),
+        };
+        report.preamble = Some(preamble);
+        match result {
+            Ok(length) => offset = length,
+            Err(error) => {
+                report.error = Some(error);
+                report.valid_up_to_line = 1;
+                return report;
+            },
+        }
+    }
+
+    let mut reader = AofReader::new(data, offset);
+    let mut keys = HashSet::new();
+    let mut db = 0;
+    let mut transaction_start = None;
+    loop {
+        let offset = reader.offset();
+        let arguments = match reader.next_command() {
+            Ok(Some(arguments)) => arguments,
+            Ok(None) => {
+                report.error = transaction_start.map(AofError::UnfinishedTransaction);
+                break;
+            },
+            Err(AofError::Truncated(offset)) => {
+                report.error = Some(AofError::Truncated(transaction_start.unwrap_or(offset)));
+                break;
+            },
+            Err(error) => {
+                report.error = Some(error);
+                break;
+            },
+        };
+        let written: Vec<Vec<u8>> = command_table::lookup(&arguments)
+            .map(|spec| spec.keys(&arguments).into_iter().map(<[u8]>::to_vec).collect())
+            .unwrap_or_default();
+        let command = match RespCommand::parse(RawCommand::new(arguments)) {
+            Ok(command) => command,
+            Err(e) => {
+                report.error = Some(AofError::Command { offset, reason: e.to_string() });
+                break;
+            },
+        };
+        match command {
+            RespCommand::Select { index } => db = index as usize,
+            RespCommand::Multi => transaction_start = Some(offset),
+            RespCommand::Exec => transaction_start = None,
+            _ => {},
+        }
+        *report.commands.entry(command.name()).or_default() += 1;
+        for key in written {
+            if keys.insert((db, key)) {
+                *report.keys.entry(db).or_default() += 1;
+            }
+        }
+    }
+
+    report.valid_up_to = match &report.error {
+        Some(AofError::Truncated(offset) | AofError::UnfinishedTransaction(offset)) => *offset,
+        Some(AofError::Format { offset, .. } | AofError::Command { offset, .. }) => *offset,
+        _ => data.len(),
+    };
+    report.valid_up_to_line = data[..report.valid_up_to].iter().filter(|byte| **byte == b'\n').count() + 1;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_parser::infra::aof::encode_command;
+
+    #[test]
+    fn test_counts_commands_and_keys_and_finds_where_to_truncate() {
+        let mut data = Vec::new();
+        encode_command(&mut data, &[b"SET", b"a", b"1"]);
+        encode_command(&mut data, &[b"SET", b"a", b"2"]);
+        encode_command(&mut data, &[b"SELECT", b"1"]);
+        encode_command(&mut data, &[b"DEL", b"a", b"b"]);
+        let complete = data.len();
+
+        let report = check(&data, 0);
+        assert!(report.error.is_none());
+        assert_eq!(report.valid_up_to, complete);
+        assert_eq!(report.commands.get("set"), Some(&2));
+        assert_eq!(report.keys, BTreeMap::from([(0, 1), (1, 2)]));
+
+        encode_command(&mut data, &[b"MULTI"]);
+        encode_command(&mut data, &[b"SET", b"c", b"3"]);
+        let report = check(&data, 0);
+        assert!(matches!(report.error, Some(AofError::UnfinishedTransaction(offset)) if offset == complete));
+        assert_eq!(report.valid_up_to, complete);
+        assert_eq!(report.valid_up_to_line, 27);
+
+        data.truncate(complete);
+        encode_command(&mut data, &[b"NOSUCHCOMMAND"]);
+        let report = check(&data, 0);
+        assert!(matches!(report.error, Some(AofError::Command { offset, .. }) if offset == complete));
+        assert!(report.is_fixable());
+    }
+}
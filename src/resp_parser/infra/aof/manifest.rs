@@ -0,0 +1,127 @@
+use std::fmt::Write;
+
+/// What a file listed in the manifest holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    /// The data a rewrite started from, as RDB or commands.
+    Base,
+    /// Commands logged after the base was taken.
+    Incr,
+    /// Replaced by a rewrite and waiting to be deleted.
+    History,
+}
+
+impl FileType {
+    fn code(&self) -> char {
+        match self {
+            FileType::Base => 'b',
+            FileType::Incr => 'i',
+            FileType::History => 'h',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AofFile {
+    pub name: String,
+    pub seq: u64,
+    pub kind: FileType,
+}
+
+/// The files making up a Redis 7 multi-part append-only file, stored as
+/// `file appendonly.aof.1.base.rdb seq 1 type b` lines.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub base: Option<AofFile>,
+    /// Incremental files in the order they are replayed.
+    pub incrs: Vec<AofFile>,
+    pub history: Vec<AofFile>,
+}
+
+/// Name of the manifest for `appendfilename`.
+pub fn file_name(prefix: &str) -> String {
+    format!("{}.manifest", prefix)
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut manifest = Manifest::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let invalid = || format!("Invalid AOF manifest line: '{}'", line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if !words.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+            let field = |name: &str| words.chunks(2).find(|pair| pair[0] == name).map(|pair| pair[1]);
+            let name = field("file").ok_or_else(invalid)?.to_string();
+            let seq = field("seq").and_then(|seq| seq.parse().ok()).ok_or_else(invalid)?;
+            match field("type").ok_or_else(invalid)? {
+                "b" if manifest.base.is_some() => return Err("Found duplicate base file information".to_string()),
+                "b" => manifest.base = Some(AofFile { name, seq, kind: FileType::Base }),
+                "i" => {
+                    if manifest.incrs.last().is_some_and(|last| last.seq >= seq) {
+                        return Err("Found a non-monotonic sequence number".to_string());
+                    }
+                    manifest.incrs.push(AofFile { name, seq, kind: FileType::Incr });
+                },
+                "h" => manifest.history.push(AofFile { name, seq, kind: FileType::History }),
+                _ => return Err(invalid()),
+            }
+        }
+        if manifest.base.is_none() && manifest.incrs.is_empty() {
+            return Err("Found an empty AOF manifest".to_string());
+        }
+        Ok(manifest)
+    }
+
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+        for file in self.base.iter().chain(&self.history).chain(&self.incrs) {
+            let _ = writeln!(text, "file {} seq {} type {}", file.name, file.seq, file.kind.code());
+        }
+        text
+    }
+
+    /// The base and incremental files, in the order they are loaded.
+    pub fn files(&self) -> impl Iterator<Item = &AofFile> {
+        self.base.iter().chain(&self.incrs)
+    }
+
+    /// A base file following the current one, written as RDB.
+    pub fn next_base(&self, prefix: &str) -> AofFile {
+        let seq = self.base.as_ref().map_or(1, |base| base.seq + 1);
+        AofFile { name: format!("{}.{}.base.rdb", prefix, seq), seq, kind: FileType::Base }
+    }
+
+    pub fn next_incr(&self, prefix: &str) -> AofFile {
+        let seq = self.incrs.last().map_or(1, |incr| incr.seq + 1);
+        AofFile { name: format!("{}.{}.incr.aof", prefix, seq), seq, kind: FileType::Incr }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trips_and_rejects_bad_lines() {
+        let text = "file appendonly.aof.2.base.rdb seq 2 type b\n\
+                    file appendonly.aof.1.base.rdb seq 1 type h\n\
+                    file appendonly.aof.3.incr.aof seq 3 type i\n\
+                    file appendonly.aof.4.incr.aof seq 4 type i\n";
+        let manifest = Manifest::parse(text).unwrap();
+        assert_eq!(manifest.base.as_ref().map(|base| base.seq), Some(2));
+        assert_eq!(manifest.files().map(|file| file.name.as_str()).collect::<Vec<_>>(), [
+            "appendonly.aof.2.base.rdb",
+            "appendonly.aof.3.incr.aof",
+            "appendonly.aof.4.incr.aof",
+        ]);
+        assert_eq!(manifest.encode(), text);
+        assert_eq!(manifest.next_base("appendonly.aof").name, "appendonly.aof.3.base.rdb");
+        assert_eq!(manifest.next_incr("appendonly.aof").name, "appendonly.aof.5.incr.aof");
+
+        assert!(Manifest::parse("file a seq 1\n").is_err());
+        assert!(Manifest::parse("file a seq 2 type i\nfile b seq 1 type i\n").is_err());
+        assert!(Manifest::parse("").is_err());
+    }
+}
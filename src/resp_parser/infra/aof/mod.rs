@@ -0,0 +1,163 @@
+//! The append-only file: every write as the RESP command that replays it.
+//! As in Redis 7 it is split into a base file holding the data of the last
+//! rewrite, as RDB or commands, and incremental files with the writes made
+//! since, all listed in a manifest.
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+use crate::resp_parser::domain::command_handler::CommandHandler;
+use crate::resp_parser::domain::config::Config;
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::domain::stream_chunking_service::RawCommand;
+use crate::resp_parser::infra::aof::manifest::{AofFile, FileType, Manifest};
+use crate::resp_parser::infra::aof::reader::AofReader;
+use crate::resp_parser::infra::memory::storage::Storage;
+use crate::resp_parser::infra::rdb::{self, RdbError};
+
+pub mod appender;
+pub mod check;
+pub mod manifest;
+pub mod reader;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AofError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Manifest(String),
+    #[error("Bad RDB preamble: {0}")]
+    Preamble(#[from] RdbError),
+    #[error("Unexpected end of file reading the append only file at offset {0}")]
+    Truncated(usize),
+    #[error("Reached EOF before reading EXEC for the MULTI at offset {0} of the append only file")]
+    UnfinishedTransaction(usize),
+    #[error("Bad file format reading the append only file at offset {offset}: {reason}")]
+    Format {
+        offset: usize,
+        reason: String,
+    },
+    #[error("Error replaying the command at offset {offset} of the append only file: {reason}")]
+    Command {
+        offset: usize,
+        reason: String,
+    },
+}
+
+/// Appends a command as a RESP array of bulk strings.
+pub fn encode_command(out: &mut Vec<u8>, arguments: &[&[u8]]) {
+    out.extend_from_slice(format!("*{}\r\n", arguments.len()).as_bytes());
+    for argument in arguments {
+        out.extend_from_slice(format!("${}\r\n", argument.len()).as_bytes());
+        out.extend_from_slice(argument);
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Reads the manifest in `dir/appenddirname`, first moving an append-only
+/// file written before Redis 7, a single file in `dir`, there as the base.
+pub fn find_manifest(config: &Config) -> Result<Option<Manifest>, AofError> {
+    let dir = Path::new(&config.dir).join(&config.appenddirname);
+    match fs::read_to_string(dir.join(manifest::file_name(&config.appendfilename))) {
+        Ok(text) => return Manifest::parse(&text).map(Some).map_err(AofError::Manifest),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        Err(_) => {},
+    }
+
+    let legacy = Path::new(&config.dir).join(&config.appendfilename);
+    if !legacy.is_file() {
+        return Ok(None);
+    }
+    fs::create_dir_all(&dir)?;
+    fs::rename(&legacy, dir.join(&config.appendfilename))?;
+    let manifest = Manifest {
+        base: Some(AofFile { name: config.appendfilename.clone(), seq: 1, kind: FileType::Base }),
+        ..Manifest::default()
+    };
+    appender::persist(&dir, &config.appendfilename, &manifest)?;
+    println!("Successfully migrated an old-style AOF into the AOF directory");
+    Ok(Some(manifest))
+}
+
+/// Replays the files of `manifest` through `handler`, returning the number
+/// of commands run. Only the last file may be cut short, as the others
+/// were complete when the next one was started; see `replay`.
+pub async fn load(
+    dir: &Path,
+    manifest: &Manifest,
+    storage: &Storage,
+    handler: &CommandHandler,
+    load_truncated: bool,
+) -> Result<u64, AofError> {
+    let (subscriber, _) = tokio::sync::mpsc::unbounded_channel();
+    let mut session = Session::new(0, subscriber);
+    let files: Vec<&AofFile> = manifest.files().collect();
+    let mut commands = 0;
+    for (index, file) in files.iter().enumerate() {
+        let started_at = Instant::now();
+        let is_last = index + 1 == files.len();
+        commands += replay(&dir.join(&file.name), storage, handler, &mut session, is_last && load_truncated).await?;
+        let kind = if file.kind == FileType::Base { "base" } else { "incr" };
+        println!("DB loaded from {} file {}: {:.3} seconds", kind, file.name, started_at.elapsed().as_secs_f64());
+    }
+    handler.close_session(&mut session).await;
+    Ok(commands)
+}
+
+/// Replays one file, which may start with an RDB preamble. A file cut
+/// short, including one ending inside MULTI, is an error unless
+/// `load_truncated` is set, in which case the file is truncated to its
+/// last complete command so new writes follow valid data.
+async fn replay(
+    path: &Path,
+    storage: &Storage,
+    handler: &CommandHandler,
+    session: &mut Session,
+    load_truncated: bool,
+) -> Result<u64, AofError> {
+    let data = fs::read(path)?;
+    let preamble = if data.starts_with(b"REDIS") {
+        let (_, length) = rdb::load_data(&data, storage).await?;
+        length
+    } else {
+        0
+    };
+
+    let mut reader = AofReader::new(&data, preamble);
+    let mut commands = 0;
+    // Start of the MULTI being replayed, which is where a transaction cut
+    // short has to be dropped from.
+    let mut transaction_start = None;
+    let valid_length = loop {
+        let offset = reader.offset();
+        let arguments = match reader.next_command() {
+            Ok(Some(arguments)) => arguments,
+            Ok(None) => break transaction_start,
+            Err(AofError::Truncated(offset)) => break Some(transaction_start.unwrap_or(offset)),
+            Err(e) => return Err(e),
+        };
+        let command = RespCommand::parse(RawCommand::new(arguments))
+            .map_err(|e| AofError::Command { offset, reason: e.to_string() })?;
+        match command {
+            RespCommand::Multi => transaction_start = Some(offset),
+            RespCommand::Exec => transaction_start = None,
+            _ => {},
+        }
+        handler.handle_command(command, session).await;
+        commands += 1;
+    };
+
+    if let Some(length) = valid_length {
+        if !load_truncated {
+            return Err(transaction_start.map_or(AofError::Truncated(length), AofError::UnfinishedTransaction));
+        }
+        println!("!!! Warning: short read while loading the AOF file {}!!!", path.display());
+        println!("!!! Truncating the AOF at offset {} !!!", length);
+        OpenOptions::new().write(true).open(path)?.set_len(length as u64)?;
+        println!("AOF loaded anyway because aof-load-truncated is enabled");
+        session.take_transaction();
+    }
+    Ok(commands)
+}
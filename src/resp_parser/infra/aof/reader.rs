@@ -0,0 +1,130 @@
+use bytes::Bytes;
+use crate::resp_parser::infra::aof::AofError;
+
+/// Why a command could not be read, before it is tied to an offset.
+enum Fault {
+    Truncated,
+    Format(String),
+}
+
+/// Reads the commands of an append-only file held in memory, each a RESP
+/// array of bulk strings. Errors carry the offset of the command they were
+/// found in, which is where a fixed file has to end.
+pub struct AofReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> AofReader<'a> {
+    /// Starts reading at `offset`, e.g. past an RDB preamble.
+    pub fn new(data: &'a [u8], offset: usize) -> Self {
+        Self {
+            data,
+            position: offset,
+        }
+    }
+
+    /// Bytes of valid commands read so far.
+    pub fn offset(&self) -> usize {
+        self.position
+    }
+
+    /// The next command, or `None` at the end of the file. The position
+    /// only moves past commands that were read completely.
+    pub fn next_command(&mut self) -> Result<Option<Vec<Bytes>>, AofError> {
+        loop {
+            let start = self.position;
+            let result = match self.data.get(start) {
+                None => return Ok(None),
+                // Annotations such as `#TS:1700000000` are not commands.
+                Some(b'#') => self.read_line().map(|_| None),
+                Some(_) => self.read_command().map(Some),
+            };
+            match result {
+                Ok(None) => continue,
+                Ok(Some(arguments)) => return Ok(Some(arguments)),
+                Err(fault) => {
+                    self.position = start;
+                    return Err(match fault {
+                        Fault::Truncated => AofError::Truncated(start),
+                        Fault::Format(reason) => AofError::Format { offset: start, reason },
+                    });
+                },
+            }
+        }
+    }
+
+    fn read_command(&mut self) -> Result<Vec<Bytes>, Fault> {
+        let count = self.read_number(b'*')?;
+        if count < 1 {
+            return Err(Fault::Format(format!("invalid number of arguments {}", count)));
+        }
+        (0..count).map(|_| self.read_argument()).collect()
+    }
+
+    fn read_argument(&mut self) -> Result<Bytes, Fault> {
+        let length = usize::try_from(self.read_number(b'$')?)
+            .map_err(|_| Fault::Format("invalid bulk length".to_string()))?;
+        let end = self.position + length;
+        let terminator = self.data.get(end..end + 2).ok_or(Fault::Truncated)?;
+        if terminator != b"\r\n" {
+            return Err(Fault::Format("expected \\r\\n after argument".to_string()));
+        }
+        let argument = Bytes::copy_from_slice(&self.data[self.position..end]);
+        self.position = end + 2;
+        Ok(argument)
+    }
+
+    /// A `*<n>` or `$<n>` line.
+    fn read_number(&mut self, prefix: u8) -> Result<i64, Fault> {
+        let line = self.read_line()?;
+        if line.first() != Some(&prefix) {
+            return Err(Fault::Format(format!("expected '{}'", prefix as char)));
+        }
+        std::str::from_utf8(&line[1..])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| Fault::Format(format!("invalid number '{}'", String::from_utf8_lossy(&line[1..]))))
+    }
+
+    /// A line without its CRLF.
+    fn read_line(&mut self) -> Result<&'a [u8], Fault> {
+        let rest = &self.data[self.position..];
+        let end = rest.iter().position(|byte| *byte == b'\n').ok_or(Fault::Truncated)?;
+        if end == 0 || rest[end - 1] != b'\r' {
+            return Err(Fault::Format("expected \\r\\n".to_string()));
+        }
+        self.position += end + 1;
+        Ok(&rest[..end - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_parser::infra::aof::encode_command;
+
+    #[test]
+    fn test_reads_commands_and_locates_bad_ones() {
+        let mut data = Vec::new();
+        encode_command(&mut data, &[b"SET", b"key", b"a\r\nb"]);
+        data.extend_from_slice(b"#TS:1700000000\r\n");
+        encode_command(&mut data, &[b"DEL", b"key"]);
+        let complete = data.len();
+
+        let mut reader = AofReader::new(&data, 0);
+        assert_eq!(reader.next_command().unwrap(), Some(vec![Bytes::from_static(b"SET"), Bytes::from_static(b"key"), Bytes::from_static(b"a\r\nb")]));
+        assert_eq!(reader.next_command().unwrap(), Some(vec![Bytes::from_static(b"DEL"), Bytes::from_static(b"key")]));
+        assert_eq!(reader.next_command().unwrap(), None);
+        assert_eq!(reader.offset(), complete);
+
+        data.extend_from_slice(b"*2\r\n$3\r\nDEL\r\n$3\r\nke");
+        let mut reader = AofReader::new(&data, complete);
+        assert!(matches!(reader.next_command(), Err(AofError::Truncated(offset)) if offset == complete));
+
+        data.truncate(complete);
+        data.extend_from_slice(b"*1\r\n$3\r\nDELXX");
+        let mut reader = AofReader::new(&data, complete);
+        assert!(matches!(reader.next_command(), Err(AofError::Format { offset, .. }) if offset == complete));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::resp_parser::domain::config::{parameter, Config, Parameter, PARAMETERS};
+use crate::resp_parser::infra::resp_stream_chunking_service::split_args;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Fatal error, can't open config file '{path}': {reason}")]
+    Open {
+        path: String,
+        reason: String,
+    },
+    #[error("\n*** FATAL CONFIG FILE ERROR ***\nReading the configuration file, at line {line}\n>>> '{directive}'\n{reason}")]
+    Directive {
+        line: usize,
+        directive: String,
+        reason: String,
+    },
+}
+
+/// Builds the configuration from `redis-server [/path/to/redis.conf]
+/// [--name value ...]`. Command line options are applied after the file,
+/// so they take precedence.
+pub fn from_args(arguments: impl IntoIterator<Item = String>) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    let mut saves = SaveLines::default();
+    let mut arguments = arguments.into_iter().peekable();
+    if let Some(path) = arguments.next_if(|argument| !argument.starts_with("--")) {
+        load(&path, &mut config, &mut saves, &mut HashSet::new())?;
+        // CONFIG REWRITE must find the file again after `dir` changes.
+        let path = fs::canonicalize(&path).map_or(path, |path| path.display().to_string());
+        config.config_file = Some(path);
+    }
+
+    let mut directives: Vec<Vec<String>> = Vec::new();
+    for argument in arguments {
+        match argument.strip_prefix("--") {
+            Some(name) => directives.push(vec![name.to_string()]),
+            None => match directives.last_mut() {
+                Some(directive) => directive.push(argument),
+                None => directives.push(vec![argument]),
+            },
+        }
+    }
+    for (index, directive) in directives.iter().enumerate() {
+        apply(&mut config, index + 1, &directive.join(" "), directive, &mut saves)?;
+    }
+    Ok(config)
+}
+
+/// Whether a `save` line was read already: the first one replaces the
+/// default policies and later ones add to it, as in Redis.
+#[derive(Default)]
+struct SaveLines {
+    seen: bool,
+}
+
+/// Reads a redis.conf file into `config`, following `include` directives.
+/// `loading` holds the files being read, so a file that includes itself,
+/// directly or not, is an error rather than endless recursion.
+fn load(path: &str, config: &mut Config, saves: &mut SaveLines, loading: &mut HashSet<PathBuf>) -> Result<(), ConfigError> {
+    let open_error = |e: io::Error| ConfigError::Open {
+        path: path.to_string(),
+        reason: e.to_string(),
+    };
+    let text = fs::read_to_string(path).map_err(open_error)?;
+    let canonical = fs::canonicalize(path).map_err(open_error)?;
+    loading.insert(canonical.clone());
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let directive: Vec<String> = split_args(line.as_bytes())
+            .ok_or_else(|| ConfigError::Directive {
+                line: index + 1,
+                directive: line.to_string(),
+                reason: "Unbalanced quotes in configuration line".to_string(),
+            })?
+            .iter()
+            .map(|argument| String::from_utf8_lossy(argument).to_string())
+            .collect();
+
+        if directive[0].eq_ignore_ascii_case("include") && directive.len() == 2 {
+            if fs::canonicalize(&directive[1]).is_ok_and(|included| loading.contains(&included)) {
+                return Err(ConfigError::Directive {
+                    line: index + 1,
+                    directive: line.to_string(),
+                    reason: "Config file includes itself".to_string(),
+                });
+            }
+            load(&directive[1], config, saves, loading)?;
+            continue;
+        }
+        apply(config, index + 1, line, &directive, saves)?;
+    }
+    loading.remove(&canonical);
+    Ok(())
+}
+
+/// Writes the current configuration back to the file it came from. The
+/// first line of each known parameter gets its current value and repeated
+/// lines are dropped; comments, includes and everything else keep their
+/// place. Parameters missing from the file that differ from their default
+/// are appended at the end.
+pub fn rewrite(path: &str, config: &Config) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut lines = Vec::new();
+    let mut written = Vec::new();
+    for line in text.lines() {
+        let name = split_args(line.trim().as_bytes())
+            .and_then(|arguments| arguments.first().cloned())
+            .map(|name| String::from_utf8_lossy(&name).to_string());
+        match name.as_deref().filter(|_| !line.trim().starts_with('#')).and_then(parameter) {
+            Some(parameter) if written.contains(&parameter.name) => {},
+            Some(parameter) => {
+                lines.push(directive(parameter, config));
+                written.push(parameter.name);
+            },
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    let defaults = Config::default();
+    let mut missing = PARAMETERS
+        .iter()
+        .filter(|parameter| !written.contains(&parameter.name) && (parameter.get)(config) != (parameter.get)(&defaults))
+        .peekable();
+    if missing.peek().is_some() && !lines.iter().any(|line| line == REWRITE_SIGNATURE) {
+        lines.push(REWRITE_SIGNATURE.to_string());
+    }
+    lines.extend(missing.map(|parameter| directive(parameter, config)));
+
+    // Written aside and renamed so a crash never leaves half a file.
+    let temporary = format!("{}.tmp-{}", path, std::process::id());
+    fs::write(&temporary, lines.join("\n") + "\n")?;
+    fs::rename(&temporary, path)
+}
+
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+fn directive(parameter: &Parameter, config: &Config) -> String {
+    let value = (parameter.get)(config);
+    let arguments: Vec<String> = match parameter.multiple_arguments {
+        true if !value.is_empty() => value.split_whitespace().map(quote).collect(),
+        true => vec![quote(&value)],
+        false => vec![quote(&value)],
+    };
+    format!("{} {}", parameter.name, arguments.join(" "))
+}
+
+/// Quotes a value when split_args would not read it back as is.
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value.bytes().all(|c| c.is_ascii_graphic() && !matches!(c, b'"' | b'\'' | b'\\'));
+    if plain {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.bytes() {
+        match c {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(c as char);
+            },
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            c if c == b' ' || c.is_ascii_graphic() => quoted.push(c as char),
+            c => quoted.push_str(&format!("\\x{:02x}", c)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn apply(config: &mut Config, line: usize, text: &str, directive: &[String], saves: &mut SaveLines) -> Result<(), ConfigError> {
+    let previous_saves = config.save.clone();
+    config.apply(&directive[0], &directive[1..]).map_err(|reason| ConfigError::Directive {
+        line,
+        directive: text.to_string(),
+        reason,
+    })?;
+    if directive[0].eq_ignore_ascii_case("save") {
+        // `save ""` still clears everything read before it.
+        if saves.seen && !config.save.is_empty() {
+            config.save.splice(0..0, previous_saves);
+        }
+        saves.seen = true;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_with_include_and_command_line_overrides() {
+        let dir = std::env::temp_dir().join(format!("config-file-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let included = dir.join("included.conf");
+        fs::write(&included, "maxmemory 2gb\nsave 300 10\n").unwrap();
+        let main = dir.join("redis.conf");
+        fs::write(
+            &main,
+            format!("# comment\nport 7000\nsave 900 1\n\ndbfilename \"my dump.rdb\"\ninclude {}\n", included.display()),
+        ).unwrap();
+
+        let arguments = [main.display().to_string(), "--port".to_string(), "7001".to_string()];
+        let config = from_args(arguments).unwrap();
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.dbfilename, "my dump.rdb");
+        assert_eq!(config.maxmemory, 2 * 1024 * 1024 * 1024);
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+
+        fs::write(&main, "port 7000\nport 'x\n").unwrap();
+        assert_eq!(
+            from_args([main.display().to_string()]),
+            Err(ConfigError::Directive {
+                line: 2,
+                directive: "port 'x".to_string(),
+                reason: "Unbalanced quotes in configuration line".to_string(),
+            }),
+        );
+
+        // Including a file twice is fine; including one being read is not.
+        fs::write(&main, format!("include {0}\ninclude {0}\n", included.display())).unwrap();
+        assert!(from_args([main.display().to_string()]).is_ok());
+        fs::write(&included, format!("port 7000\ninclude {}\n", main.display())).unwrap();
+        assert_eq!(
+            from_args([main.display().to_string()]),
+            Err(ConfigError::Directive {
+                line: 2,
+                directive: format!("include {}", main.display()),
+                reason: "Config file includes itself".to_string(),
+            }),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_keeps_comments_and_order() {
+        let dir = std::env::temp_dir().join(format!("config-rewrite-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("redis.conf").display().to_string();
+        fs::write(&path, "# Memory\nmaxmemory 1mb\n\n# Network\nport 7000\nmaxmemory 2mb\n").unwrap();
+
+        let mut config = from_args([path.clone()]).unwrap();
+        config.maxmemory = 1024;
+        config.dbfilename = "my dump.rdb".to_string();
+        rewrite(&path, &config).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Memory\nmaxmemory 1024\n\n# Network\nport 7000\n# Generated by CONFIG REWRITE\ndbfilename \"my dump.rdb\"\n",
+        );
+        assert_eq!(from_args([path.clone()]).unwrap().dbfilename, "my dump.rdb");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::RwLock;
+use crate::resp_parser::domain::glob_pattern::glob_match;
+use crate::resp_parser::domain::pubsub_message::PubSubMessage;
+
+pub type Subscriber = UnboundedSender<PubSubMessage>;
+
+#[derive(Default)]
+struct Channels {
+    channels: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+    patterns: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+}
+
+/// Channel and pattern subscriptions of every connection, keyed by client id.
+#[derive(Clone, Default)]
+pub struct ChannelRegistry {
+    channels: Arc<RwLock<Channels>>,
+    sharded: bool,
+}
+
+impl ChannelRegistry {
+    /// Registry for shard channels, whose messages are delivered as `smessage`.
+    pub fn sharded() -> Self {
+        Self {
+            channels: Arc::default(),
+            sharded: true,
+        }
+    }
+
+    pub async fn subscribe(&self, channel: Vec<u8>, client_id: u64, subscriber: Subscriber) {
+        let mut channels_lock = self.channels.write().await;
+        channels_lock.channels.entry(channel).or_default().insert(client_id, subscriber);
+    }
+
+    pub async fn unsubscribe(&self, channel: &[u8], client_id: u64) {
+        let mut channels_lock = self.channels.write().await;
+        Self::remove(&mut channels_lock.channels, channel, client_id);
+    }
+
+    pub async fn psubscribe(&self, pattern: Vec<u8>, client_id: u64, subscriber: Subscriber) {
+        let mut channels_lock = self.channels.write().await;
+        channels_lock.patterns.entry(pattern).or_default().insert(client_id, subscriber);
+    }
+
+    pub async fn punsubscribe(&self, pattern: &[u8], client_id: u64) {
+        let mut channels_lock = self.channels.write().await;
+        Self::remove(&mut channels_lock.patterns, pattern, client_id);
+    }
+
+    /// Delivers the payload to channel and pattern subscribers, returning how
+    /// many receivers got it.
+    pub async fn publish(&self, channel: &[u8], payload: &[u8]) -> usize {
+        let channels_lock = self.channels.read().await;
+        let mut receivers = 0;
+
+        if let Some(subscribers) = channels_lock.channels.get(channel) {
+            for subscriber in subscribers.values() {
+                let (channel, payload) = (channel.to_vec(), payload.to_vec());
+                let message = if self.sharded {
+                    PubSubMessage::ShardMessage { channel, payload }
+                } else {
+                    PubSubMessage::Message { channel, payload }
+                };
+                if subscriber.send(message).is_ok() {
+                    receivers += 1;
+                }
+            }
+        }
+
+        for (pattern, subscribers) in &channels_lock.patterns {
+            if !glob_match(pattern, channel, false) {
+                continue;
+            }
+            for subscriber in subscribers.values() {
+                let message = PubSubMessage::PatternMessage {
+                    pattern: pattern.clone(),
+                    channel: channel.to_vec(),
+                    payload: payload.to_vec(),
+                };
+                if subscriber.send(message).is_ok() {
+                    receivers += 1;
+                }
+            }
+        }
+
+        receivers
+    }
+
+    /// Channels with at least one subscriber, optionally filtered by a glob.
+    pub async fn channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        let channels_lock = self.channels.read().await;
+        channels_lock.channels
+            .keys()
+            .filter(|channel| pattern.is_none_or(|pattern| glob_match(pattern, channel, false)))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn subscriber_count(&self, channel: &[u8]) -> usize {
+        let channels_lock = self.channels.read().await;
+        channels_lock.channels.get(channel).map(|subscribers| subscribers.len()).unwrap_or(0)
+    }
+
+    pub async fn pattern_count(&self) -> usize {
+        let channels_lock = self.channels.read().await;
+        channels_lock.patterns.len()
+    }
+
+    fn remove(subscriptions: &mut HashMap<Vec<u8>, HashMap<u64, Subscriber>>, name: &[u8], client_id: u64) {
+        if let Some(subscribers) = subscriptions.get_mut(name) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                subscriptions.remove(name);
+            }
+        }
+    }
+}
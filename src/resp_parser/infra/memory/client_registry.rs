@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::resp_parser::infra::memory::channel_registry::Subscriber;
+
+/// Message queues of every open connection, keyed by client id.
+#[derive(Clone, Default)]
+pub struct ClientRegistry {
+    clients: Arc<RwLock<HashMap<u64, Subscriber>>>,
+}
+
+impl ClientRegistry {
+    pub async fn register(&self, client_id: u64, subscriber: Subscriber) {
+        let mut clients_lock = self.clients.write().await;
+        clients_lock.insert(client_id, subscriber);
+    }
+
+    pub async fn unregister(&self, client_id: u64) {
+        let mut clients_lock = self.clients.write().await;
+        clients_lock.remove(&client_id);
+    }
+
+    pub async fn subscriber(&self, client_id: u64) -> Option<Subscriber> {
+        let clients_lock = self.clients.read().await;
+        clients_lock.get(&client_id).cloned()
+    }
+}
@@ -1,18 +1,302 @@
-use crate::resp_parser::infra::memory::storage::Storage;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use crate::resp_parser::domain::keyspace_events::{EXPIRED, GENERIC, NEW, STRING};
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::infra::aof::appender::Appender;
+use crate::resp_parser::infra::memory::keyspace_notifier::KeyspaceNotifier;
+use crate::resp_parser::infra::memory::server_stats::ServerStats;
+use crate::resp_parser::infra::memory::tracking_table::TrackingTable;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, Entry, Keyspace, Storage, Value, DATABASES};
+use crate::resp_parser::infra::rdb::dump;
+use crate::resp_parser::infra::replication::Replication;
+
+/// Keys with an expiry the expire cycle samples from a database at a time.
+const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;
+/// Percentage of a sample that may have expired without sampling again.
+const ACTIVE_EXPIRE_ACCEPTABLE_STALE: usize = 10;
+/// Time an expire cycle may hold the keyspace, a quarter of the 100 ms
+/// between cycles.
+const ACTIVE_EXPIRE_TIME_BUDGET: Duration = Duration::from_millis(25);
 
 pub struct CommandRepository {
     storage: Storage,
+    keyspace_notifier: KeyspaceNotifier,
+    tracking_table: TrackingTable,
+    stats: ServerStats,
+    appender: Appender,
+    replication: Replication,
 }
 
 impl CommandRepository {
-    pub fn new(storage: Storage) -> Self {
+    pub fn new(
+        storage: Storage,
+        keyspace_notifier: KeyspaceNotifier,
+        tracking_table: TrackingTable,
+        stats: ServerStats,
+        appender: Appender,
+        replication: Replication,
+    ) -> Self {
         Self {
             storage,
+            keyspace_notifier,
+            tracking_table,
+            stats,
+            appender,
+            replication,
         }
     }
 
-    pub async fn set(&self, key: Vec<u8>, value: Vec<u8>) {
+    /// Logs a write to the append-only file and streams it to replicas.
+    fn propagate(&self, db: Option<usize>, arguments: &[&[u8]]) {
+        self.appender.feed(db, arguments);
+        self.replication.feed(db, arguments);
+    }
+
+    /// `origin` is the writing client, so NOLOOP trackers skip their own writes.
+    pub async fn set(&self, db: usize, key: Vec<u8>, value: Bytes, expires_at: Option<u64>, origin: Option<u64>) {
         let mut storage_lock = self.storage.write().await;
-        storage_lock.insert(key, value);
+        storage_lock.touch(db, &key);
+        let is_new = storage_lock.db(db).get_alive(&key, unix_time_ms()).is_none();
+        // The expiry is logged as absolute, so replaying it later does not
+        // extend it.
+        match expires_at {
+            Some(expires_at) => self.propagate(Some(db), &[b"SET".as_slice(), &key, &value, b"PXAT", expires_at.to_string().as_bytes()]),
+            None => self.propagate(Some(db), &[b"SET".as_slice(), &key, &value]),
+        }
+        storage_lock.db_mut(db).insert(key.clone(), Entry::new(Value::String(value), expires_at));
+        drop(storage_lock);
+
+        if is_new {
+            self.keyspace_notifier.notify(NEW, "new", db, &key).await;
+        }
+        self.keyspace_notifier.notify(STRING, "set", db, &key).await;
+        if expires_at.is_some() {
+            self.keyspace_notifier.notify(GENERIC, "expire", db, &key).await;
+        }
+        self.tracking_table.invalidate(&key, origin).await;
     }
-}
\ No newline at end of file
+
+    /// Removes the keys that exist, returning how many did.
+    pub async fn del(&self, db: usize, keys: &[Vec<u8>], origin: Option<u64>) -> usize {
+        let mut storage_lock = self.storage.write().await;
+        let now_ms = unix_time_ms();
+        let mut deleted = Vec::new();
+        let mut expired = Vec::new();
+        for key in keys {
+            // An expired key still counts as missing, but expires as it
+            // would on a read.
+            if self.expire_key(&mut storage_lock, db, key, now_ms) {
+                expired.push(key.as_slice());
+                continue;
+            }
+            // A replica keeps expired keys until its master deletes them,
+            // which this DEL does.
+            if let Some(entry) = storage_lock.db_mut(db).remove(key) {
+                storage_lock.touch(db, key);
+                if !entry.is_expired(now_ms) {
+                    deleted.push(key.as_slice());
+                }
+            }
+        }
+        if !deleted.is_empty() {
+            let arguments: Vec<&[u8]> = std::iter::once(b"DEL".as_slice()).chain(deleted.iter().copied()).collect();
+            self.propagate(Some(db), &arguments);
+        }
+        drop(storage_lock);
+
+        self.stats.keys_expired(expired.len() as u64);
+        for key in expired {
+            self.expired(db, key).await;
+        }
+        for key in &deleted {
+            self.keyspace_notifier.notify(GENERIC, "del", db, key).await;
+            self.tracking_table.invalidate(key, origin).await;
+        }
+        deleted.len()
+    }
+
+    /// Creates `key` from a DUMP payload. A key that exists is only
+    /// replaced with `replace`, and an expiry already passed leaves the key
+    /// deleted instead.
+    pub async fn restore(
+        &self,
+        db: usize,
+        key: Vec<u8>,
+        payload: &[u8],
+        expires_at: Option<u64>,
+        replace: bool,
+        origin: Option<u64>,
+    ) -> Result<(), RespError> {
+        let mut storage_lock = self.storage.write().await;
+        let now_ms = unix_time_ms();
+        let exists = storage_lock.db(db).get_alive(&key, now_ms).is_some();
+        if exists && !replace {
+            return Err(RespError::BusyKey);
+        }
+        let value = dump::decode(payload).map_err(|e| RespError::Err(e.to_string()))?;
+
+        if expires_at.is_some_and(|expires_at| expires_at <= now_ms) {
+            if !exists {
+                return Ok(());
+            }
+            storage_lock.db_mut(db).remove(&key);
+            storage_lock.touch(db, &key);
+            self.propagate(Some(db), &[b"DEL".as_slice(), &key]);
+            drop(storage_lock);
+
+            self.keyspace_notifier.notify(GENERIC, "del", db, &key).await;
+            self.tracking_table.invalidate(&key, origin).await;
+            return Ok(());
+        }
+
+        storage_lock.touch(db, &key);
+        // Like SET, the expiry is logged as absolute.
+        let ttl = expires_at.unwrap_or(0).to_string();
+        let mut arguments = vec![b"RESTORE".as_slice(), &key, ttl.as_bytes(), payload];
+        if replace {
+            arguments.push(b"REPLACE");
+        }
+        if expires_at.is_some() {
+            arguments.push(b"ABSTTL");
+        }
+        self.propagate(Some(db), &arguments);
+        storage_lock.db_mut(db).insert(key.clone(), Entry::new(value, expires_at));
+        drop(storage_lock);
+
+        if !exists {
+            self.keyspace_notifier.notify(NEW, "new", db, &key).await;
+        }
+        self.keyspace_notifier.notify(GENERIC, "restore", db, &key).await;
+        self.tracking_table.invalidate(&key, origin).await;
+        Ok(())
+    }
+
+    pub async fn flush_db(&self, db: usize) {
+        let mut storage_lock = self.storage.write().await;
+        storage_lock.touch_all(db);
+        let removed = storage_lock.db(db).entries().len();
+        storage_lock.mark_dirty(removed as u64);
+        storage_lock.db_mut(db).clear();
+        self.propagate(Some(db), &[b"FLUSHDB"]);
+        drop(storage_lock);
+
+        self.tracking_table.invalidate_all().await;
+    }
+
+    pub async fn swap_db(&self, first: usize, second: usize) {
+        let mut storage_lock = self.storage.write().await;
+        storage_lock.swap(first, second);
+        self.propagate(None, &[b"SWAPDB", first.to_string().as_bytes(), second.to_string().as_bytes()]);
+        drop(storage_lock);
+
+        // Tracked key names are not bound to a db, so cached values from
+        // either side may now be stale.
+        self.tracking_table.invalidate_all().await;
+    }
+
+    /// Wraps the writes of a transaction in MULTI and EXEC, so a replay
+    /// applies all of them or none.
+    pub fn begin_transaction(&self) {
+        self.propagate(None, &[b"MULTI"]);
+    }
+
+    pub fn end_transaction(&self) {
+        self.propagate(None, &[b"EXEC"]);
+    }
+
+    pub async fn watch(&self, db: usize, key: Vec<u8>) -> u64 {
+        let mut storage_lock = self.storage.write().await;
+        storage_lock.watch(db, key)
+    }
+
+    pub async fn unwatch(&self, keys: impl IntoIterator<Item = (usize, Vec<u8>)>) {
+        let mut storage_lock = self.storage.write().await;
+        for (db, key) in keys {
+            storage_lock.unwatch(db, &key);
+        }
+    }
+
+    /// Deletes the key if its TTL has passed, returning whether it did.
+    pub async fn expire_if_needed(&self, db: usize, key: &[u8]) -> bool {
+        let mut storage_lock = self.storage.write().await;
+        let expired = self.expire_key(&mut storage_lock, db, key, unix_time_ms());
+        drop(storage_lock);
+
+        if expired {
+            self.stats.keys_expired(1);
+            self.expired(db, key).await;
+        }
+        expired
+    }
+
+    /// Deletes expired keys the way Redis's active expire does, returning
+    /// how many it deleted. Each database is sampled for keys with an
+    /// expiry, again while more than a tenth of a sample had expired, until
+    /// the cycle runs out of time. A replica leaves expiry to its master.
+    pub async fn expire_cycle(&self) -> usize {
+        if self.replication.is_replica() {
+            return 0;
+        }
+        let started_at = Instant::now();
+        let mut storage_lock = self.storage.write().await;
+        let now_ms = unix_time_ms();
+        let notify = self.keyspace_notifier.is_enabled(EXPIRED) || self.tracking_table.is_active();
+        let mut expired = Vec::new();
+        let mut expired_count = 0;
+        let first_db = storage_lock.next_expire_db();
+        'databases: for db in (first_db..DATABASES).chain(0..first_db) {
+            loop {
+                let sample = storage_lock.db_mut(db).sample_expiring(ACTIVE_EXPIRE_KEYS_PER_LOOP);
+                let mut sample_expired = 0;
+                for key in &sample {
+                    if self.expire_key(&mut storage_lock, db, key, now_ms) {
+                        sample_expired += 1;
+                        if notify {
+                            expired.push((db, key.clone()));
+                        }
+                    }
+                }
+                expired_count += sample_expired;
+                if started_at.elapsed() >= ACTIVE_EXPIRE_TIME_BUDGET {
+                    storage_lock.set_next_expire_db(db + 1);
+                    break 'databases;
+                }
+                if sample_expired * 100 <= sample.len() * ACTIVE_EXPIRE_ACCEPTABLE_STALE {
+                    break;
+                }
+            }
+        }
+        drop(storage_lock);
+        self.stats.keys_expired(expired_count as u64);
+
+        for (db, key) in expired {
+            self.expired(db, &key).await;
+        }
+        expired_count
+    }
+
+    /// Announces a key `expire_key` deleted, once the lock is released.
+    async fn expired(&self, db: usize, key: &[u8]) {
+        self.keyspace_notifier.notify(EXPIRED, "expired", db, key).await;
+        self.tracking_table.invalidate(key, None).await;
+    }
+
+    /// Deleted expired keys are logged as DEL, so a replay does not depend
+    /// on when it runs. A replica keeps them, already hidden from reads,
+    /// until the DEL of its master, so its data never runs ahead of the
+    /// master's.
+    fn expire_key(&self, keyspace: &mut Keyspace, db: usize, key: &[u8], now_ms: u64) -> bool {
+        if self.replication.is_replica() {
+            return false;
+        }
+        let is_expired = keyspace.db(db).entries()
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(now_ms));
+        if is_expired {
+            keyspace.db_mut(db).remove(key);
+            keyspace.touch(db, key);
+            self.propagate(Some(db), &[b"DEL".as_slice(), key]);
+        }
+        is_expired
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::resp_parser::domain::config::Config;
+
+/// The live configuration every connection reads and CONFIG SET changes.
+#[derive(Clone, Default)]
+pub struct ConfigRegistry {
+    config: Arc<RwLock<Config>>,
+}
+
+impl ConfigRegistry {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(config)),
+        }
+    }
+
+    pub async fn snapshot(&self) -> Config {
+        self.config.read().await.clone()
+    }
+
+    /// Reads a single value without copying the whole configuration.
+    pub async fn read<T>(&self, f: impl FnOnce(&Config) -> T) -> T {
+        f(&*self.config.read().await)
+    }
+
+    /// Applies CONFIG SET pairs atomically and returns the new configuration.
+    pub async fn set(&self, pairs: &[(String, String)]) -> Result<Config, String> {
+        let mut config_lock = self.config.write().await;
+        let config = config_lock.with_values(pairs)?;
+        *config_lock = config.clone();
+        Ok(config)
+    }
+
+    /// Changes the configuration on behalf of the server itself, e.g. for
+    /// REPLICAOF, where CONFIG SET's rules do not apply.
+    pub async fn update(&self, f: impl FnOnce(&mut Config)) {
+        f(&mut *self.config.write().await);
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use crate::resp_parser::domain::keyspace_events::{KEYEVENT, KEYSPACE};
+use crate::resp_parser::infra::memory::channel_registry::ChannelRegistry;
+
+/// Publishes `__keyspace@<db>__` and `__keyevent@<db>__` notifications.
+#[derive(Clone, Default)]
+pub struct KeyspaceNotifier {
+    flags: Arc<AtomicU32>,
+    channel_registry: ChannelRegistry,
+}
+
+impl KeyspaceNotifier {
+    pub fn new(channel_registry: ChannelRegistry) -> Self {
+        Self {
+            flags: Arc::default(),
+            channel_registry,
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags.load(Ordering::Relaxed)
+    }
+
+    pub fn set_flags(&self, flags: u32) {
+        self.flags.store(flags, Ordering::Relaxed);
+    }
+
+    /// Cheap check so callers can skip collecting keys for disabled classes.
+    pub fn is_enabled(&self, class: u32) -> bool {
+        let flags = self.flags();
+        flags & class != 0 && flags & (KEYSPACE | KEYEVENT) != 0
+    }
+
+    pub async fn notify(&self, class: u32, event: &str, db: usize, key: &[u8]) {
+        if !self.is_enabled(class) {
+            return;
+        }
+        let flags = self.flags();
+        if flags & KEYSPACE != 0 {
+            let mut channel = format!("__keyspace@{}__:", db).into_bytes();
+            channel.extend_from_slice(key);
+            self.channel_registry.publish(&channel, event.as_bytes()).await;
+        }
+        if flags & KEYEVENT != 0 {
+            let channel = format!("__keyevent@{}__:{}", db, event);
+            self.channel_registry.publish(channel.as_bytes(), key).await;
+        }
+    }
+}
@@ -1,3 +1,9 @@
 pub mod command_repository;
 pub mod query_repository;
-pub mod storage;
\ No newline at end of file
+pub mod storage;
+pub mod channel_registry;
+pub mod keyspace_notifier;
+pub mod client_registry;
+pub mod tracking_table;
+pub mod config_registry;
+pub mod server_stats;
@@ -1,21 +1,79 @@
-use crate::resp_parser::infra::memory::storage::Storage;
+use bytes::Bytes;
+use crate::resp_parser::domain::keyspace_events::KEY_MISS;
+use crate::resp_parser::domain::resp_error::RespError;
+use crate::resp_parser::infra::memory::keyspace_notifier::KeyspaceNotifier;
+use crate::resp_parser::infra::memory::server_stats::ServerStats;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, DatabaseStats, Storage, Value, DATABASES};
+use crate::resp_parser::infra::memory::tracking_table::TrackingTable;
+use crate::resp_parser::infra::rdb::dump;
 
 pub struct QueryRepository {
     storage: Storage,
+    keyspace_notifier: KeyspaceNotifier,
+    tracking_table: TrackingTable,
+    stats: ServerStats,
 }
 
 impl QueryRepository {
-    pub fn new(storage: Storage) -> Self {
+    pub fn new(storage: Storage, keyspace_notifier: KeyspaceNotifier, tracking_table: TrackingTable, stats: ServerStats) -> Self {
         Self {
             storage,
+            keyspace_notifier,
+            tracking_table,
+            stats,
         }
     }
 
-    pub async fn get(&self, key: Vec<u8>) -> Option<String> {
+    /// `tracked_by` is the client whose near cache may now hold the key.
+    pub async fn get(&self, db: usize, key: Vec<u8>, tracked_by: Option<u64>) -> Result<Option<Bytes>, RespError> {
         let storage_lock = self.storage.read().await;
 
-        storage_lock.get::<Vec<u8>>(key.as_ref()).map(|value| {
-            String::from_utf8_lossy(value).to_string()
-        })
+        let value = storage_lock.db(db).get_alive(key.as_ref(), unix_time_ms()).map(|entry| entry.value.clone());
+        drop(storage_lock);
+
+        if value.is_none() {
+            self.stats.keyspace_miss();
+            self.keyspace_notifier.notify(KEY_MISS, "keymiss", db, &key).await;
+        } else {
+            self.stats.keyspace_hit();
+        }
+        if let Some(client_id) = tracked_by {
+            self.tracking_table.remember(client_id, &key).await;
+        }
+        match value {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(RespError::WrongType),
+        }
     }
-}
\ No newline at end of file
+
+    /// The value of `key` serialized as DUMP returns it.
+    pub async fn dump(&self, db: usize, key: &[u8], tracked_by: Option<u64>) -> Option<Bytes> {
+        let storage_lock = self.storage.read().await;
+        let payload = storage_lock.db(db).get_alive(key, unix_time_ms()).map(|entry| Bytes::from(dump::encode(&entry.value)));
+        drop(storage_lock);
+
+        if payload.is_none() {
+            self.stats.keyspace_miss();
+            self.keyspace_notifier.notify(KEY_MISS, "keymiss", db, key).await;
+        } else {
+            self.stats.keyspace_hit();
+        }
+        if let Some(client_id) = tracked_by {
+            self.tracking_table.remember(client_id, key).await;
+        }
+        payload
+    }
+
+    /// Key counts of every database, for INFO.
+    pub async fn database_stats(&self) -> Vec<DatabaseStats> {
+        let storage_lock = self.storage.read().await;
+        let now_ms = unix_time_ms();
+        (0..DATABASES).map(|db| storage_lock.db(db).stats(now_ms)).collect()
+    }
+
+    pub async fn version(&self, db: usize, key: &[u8]) -> u64 {
+        let storage_lock = self.storage.read().await;
+        storage_lock.version(db, key)
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Calls of one command as INFO commandstats reports them.
+#[derive(Clone, Default)]
+pub struct CommandStats {
+    pub calls: u64,
+    pub usec: u64,
+    pub rejected_calls: u64,
+    pub failed_calls: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    connections_received: AtomicU64,
+    commands_processed: AtomicU64,
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+    expired_keys: AtomicU64,
+    error_replies: AtomicU64,
+    net_input_bytes: AtomicU64,
+    net_output_bytes: AtomicU64,
+    used_memory_peak: AtomicU64,
+}
+
+struct Inner {
+    started_at: Instant,
+    run_id: String,
+    connected_clients: AtomicU64,
+    counters: Counters,
+    commands: Mutex<HashMap<&'static str, CommandStats>>,
+    errors: Mutex<HashMap<String, u64>>,
+}
+
+/// Server wide statistics for INFO, shared by every connection. CONFIG
+/// RESETSTAT clears everything but the uptime and connected clients.
+#[derive(Clone)]
+pub struct ServerStats {
+    inner: Arc<Inner>,
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                started_at: Instant::now(),
+                run_id: random_hex_id(),
+                connected_clients: AtomicU64::default(),
+                counters: Counters::default(),
+                commands: Mutex::default(),
+                errors: Mutex::default(),
+            }),
+        }
+    }
+}
+
+impl ServerStats {
+    pub fn uptime_in_seconds(&self) -> u64 {
+        self.inner.started_at.elapsed().as_secs()
+    }
+
+    /// Random identifier of this server run.
+    pub fn run_id(&self) -> &str {
+        &self.inner.run_id
+    }
+
+    pub fn client_connected(&self) {
+        self.inner.connected_clients.fetch_add(1, Ordering::Relaxed);
+        self.inner.counters.connections_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn client_disconnected(&self) {
+        self.inner.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn connected_clients(&self) -> u64 {
+        self.inner.connected_clients.load(Ordering::Relaxed)
+    }
+
+    pub fn connections_received(&self) -> u64 {
+        self.inner.counters.connections_received.load(Ordering::Relaxed)
+    }
+
+    /// Records a finished command; `failed` when it replied with an error.
+    pub fn command_called(&self, name: &'static str, usec: u64, failed: bool) {
+        self.inner.counters.commands_processed.fetch_add(1, Ordering::Relaxed);
+        let mut commands_lock = self.inner.commands.lock().unwrap();
+        let stats = commands_lock.entry(name).or_default();
+        stats.calls += 1;
+        stats.usec += usec;
+        stats.failed_calls += failed as u64;
+    }
+
+    /// Records a command refused before it ran, e.g. in subscriber mode.
+    pub fn command_rejected(&self, name: &'static str) {
+        let mut commands_lock = self.inner.commands.lock().unwrap();
+        commands_lock.entry(name).or_default().rejected_calls += 1;
+    }
+
+    pub fn commands_processed(&self) -> u64 {
+        self.inner.counters.commands_processed.load(Ordering::Relaxed)
+    }
+
+    /// Per command statistics sorted by name.
+    pub fn commands(&self) -> Vec<(&'static str, CommandStats)> {
+        let commands_lock = self.inner.commands.lock().unwrap();
+        let mut commands: Vec<_> = commands_lock.iter().map(|(name, stats)| (*name, stats.clone())).collect();
+        commands.sort_by_key(|(name, _)| *name);
+        commands
+    }
+
+    /// Records an error reply by its prefix, e.g. `ERR` or `WRONGTYPE`.
+    pub fn error_replied(&self, message: &str) {
+        self.inner.counters.error_replies.fetch_add(1, Ordering::Relaxed);
+        let prefix = message.split(' ').next().unwrap_or_default().to_string();
+        let mut errors_lock = self.inner.errors.lock().unwrap();
+        *errors_lock.entry(prefix).or_default() += 1;
+    }
+
+    pub fn error_replies(&self) -> u64 {
+        self.inner.counters.error_replies.load(Ordering::Relaxed)
+    }
+
+    /// Error reply counts by prefix, sorted by prefix.
+    pub fn errors(&self) -> Vec<(String, u64)> {
+        let errors_lock = self.inner.errors.lock().unwrap();
+        let mut errors: Vec<_> = errors_lock.iter().map(|(prefix, count)| (prefix.clone(), *count)).collect();
+        errors.sort();
+        errors
+    }
+
+    pub fn keyspace_hit(&self) {
+        self.inner.counters.keyspace_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn keyspace_miss(&self) {
+        self.inner.counters.keyspace_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn keyspace_hits(&self) -> u64 {
+        self.inner.counters.keyspace_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_misses(&self) -> u64 {
+        self.inner.counters.keyspace_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn keys_expired(&self, count: u64) {
+        self.inner.counters.expired_keys.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.inner.counters.expired_keys.load(Ordering::Relaxed)
+    }
+
+    pub fn net_input(&self, bytes: usize) {
+        self.inner.counters.net_input_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn net_output(&self, bytes: usize) {
+        self.inner.counters.net_output_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn net_input_bytes(&self) -> u64 {
+        self.inner.counters.net_input_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn net_output_bytes(&self) -> u64 {
+        self.inner.counters.net_output_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Highest memory usage seen so far, including `used_memory`.
+    pub fn used_memory_peak(&self, used_memory: u64) -> u64 {
+        self.inner.counters.used_memory_peak.fetch_max(used_memory, Ordering::Relaxed).max(used_memory)
+    }
+
+    pub fn reset(&self) {
+        let counters = &self.inner.counters;
+        for counter in [
+            &counters.connections_received,
+            &counters.commands_processed,
+            &counters.keyspace_hits,
+            &counters.keyspace_misses,
+            &counters.expired_keys,
+            &counters.error_replies,
+            &counters.net_input_bytes,
+            &counters.net_output_bytes,
+            &counters.used_memory_peak,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.inner.commands.lock().unwrap().clear();
+        self.inner.errors.lock().unwrap().clear();
+    }
+}
+
+/// System and user CPU seconds used by the process, zero where /proc is
+/// not available.
+pub fn cpu_time() -> (f64, f64) {
+    let stat = std::fs::read_to_string("/proc/self/stat").unwrap_or_default();
+    // The fields after the parenthesised command name start with the state.
+    let fields: Vec<&str> = stat.rsplit(')').next().unwrap_or_default().split_whitespace().collect();
+    let seconds = |index: usize| fields.get(index).and_then(|ticks| ticks.parse::<f64>().ok()).unwrap_or(0.0) / 100.0;
+    (seconds(12), seconds(11))
+}
+
+/// Resident set size in bytes, zero where /proc is not available.
+pub fn resident_memory() -> u64 {
+    let statm = std::fs::read_to_string("/proc/self/statm").unwrap_or_default();
+    statm.split_whitespace().nth(1).and_then(|pages| pages.parse::<u64>().ok()).unwrap_or(0) * 4096
+}
+
+/// 40 hex characters, the format of Redis run and replication ids.
+pub fn random_hex_id() -> String {
+    (0..5)
+        .map(|_| {
+            // Every RandomState is seeded differently, which is all the
+            // randomness an identifier needs.
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(Instant::now().elapsed().as_nanos());
+            format!("{:016x}", hasher.finish())[..8].to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_stats_and_reset() {
+        let stats = ServerStats::default();
+        stats.client_connected();
+        stats.command_called("get", 10, false);
+        stats.command_called("get", 20, true);
+        stats.command_rejected("set");
+        stats.error_replied("ERR unknown command");
+        assert_eq!(stats.commands_processed(), 2);
+        let commands = stats.commands();
+        assert_eq!(commands.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec!["get", "set"]);
+        assert_eq!((commands[0].1.calls, commands[0].1.usec, commands[0].1.failed_calls), (2, 30, 1));
+        assert_eq!(commands[1].1.rejected_calls, 1);
+        assert_eq!(stats.errors(), vec![("ERR".to_string(), 1)]);
+
+        stats.reset();
+        assert!(stats.commands().is_empty());
+        assert_eq!((stats.commands_processed(), stats.connections_received()), (0, 0));
+        assert_eq!(stats.connected_clients(), 1);
+        assert_eq!(random_hex_id().len(), 40);
+    }
+}
@@ -1,5 +1,351 @@
 use std::collections::HashMap;
+use bytes::Bytes;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-pub type Storage = Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>;
\ No newline at end of file
+pub const DATABASES: usize = 16;
+
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// A stored value. Only strings have commands so far; the other types come
+/// from RDB snapshots and are kept so that nothing loaded is lost.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(Bytes),
+    List(Vec<Bytes>),
+    Set(Vec<Bytes>),
+    /// Members with their scores, in the order they were loaded.
+    SortedSet(Vec<(Bytes, f64)>),
+    Hash(Vec<(Bytes, Bytes)>),
+}
+
+impl Value {
+    /// Bytes held by the elements, without any bookkeeping.
+    pub fn size(&self) -> u64 {
+        let size = match self {
+            Value::String(value) => value.len(),
+            Value::List(elements) | Value::Set(elements) => elements.iter().map(Bytes::len).sum(),
+            Value::SortedSet(members) => members.iter().map(|(member, _)| member.len() + 8).sum(),
+            Value::Hash(fields) => fields.iter().map(|(field, value)| field.len() + value.len()).sum(),
+        };
+        size as u64
+    }
+
+    /// The name TYPE reports for the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Hash(_) => "hash",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub value: Value,
+    /// Absolute expiry as unix time in milliseconds.
+    pub expires_at: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(value: Value, expires_at: Option<u64>) -> Self {
+        Self {
+            value,
+            expires_at,
+        }
+    }
+
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now_ms)
+    }
+}
+
+/// The entries of one logical database, with an index of the keys that
+/// have an expiry so the expire cycle samples those only.
+#[derive(Default)]
+pub struct Database {
+    entries: HashMap<Vec<u8>, Entry>,
+    /// Keys with an expiry, in no particular order.
+    expires: Vec<Vec<u8>>,
+    /// Position of each key of `expires`, to remove it in constant time.
+    expire_positions: HashMap<Vec<u8>, usize>,
+    /// Where the next sample of `expires` starts.
+    expire_cursor: usize,
+}
+
+/// Rough per key cost of the hash table entry and value header, used to
+/// estimate memory use as allocations are not tracked.
+const ENTRY_OVERHEAD: u64 = 64;
+
+/// Key counts of a database as INFO keyspace reports them.
+#[derive(Default)]
+pub struct DatabaseStats {
+    pub keys: u64,
+    pub expires: u64,
+    /// Average remaining TTL of the expiring keys in milliseconds.
+    pub avg_ttl: u64,
+    /// Estimated bytes used by keys and values.
+    pub memory: u64,
+}
+
+impl Database {
+    pub fn entries(&self) -> &HashMap<Vec<u8>, Entry> {
+        &self.entries
+    }
+
+    pub fn insert(&mut self, key: Vec<u8>, entry: Entry) -> Option<Entry> {
+        if entry.expires_at.is_some() {
+            if !self.expire_positions.contains_key(&key) {
+                self.expire_positions.insert(key.clone(), self.expires.len());
+                self.expires.push(key.clone());
+            }
+        } else {
+            self.unindex_expiry(&key);
+        }
+        self.entries.insert(key, entry)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Entry> {
+        self.unindex_expiry(key);
+        self.entries.remove(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.expires.clear();
+        self.expire_positions.clear();
+        self.expire_cursor = 0;
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    fn unindex_expiry(&mut self, key: &[u8]) {
+        let Some(position) = self.expire_positions.remove(key) else {
+            return;
+        };
+        self.expires.swap_remove(position);
+        if let Some(moved) = self.expires.get(position) {
+            self.expire_positions.insert(moved.clone(), position);
+        }
+    }
+
+    /// Up to `count` keys with an expiry, continuing where the previous
+    /// sample stopped, so that successive samples cover every such key.
+    pub fn sample_expiring(&mut self, count: usize) -> Vec<Vec<u8>> {
+        let count = count.min(self.expires.len());
+        if count == 0 {
+            return Vec::new();
+        }
+        let start = self.expire_cursor % self.expires.len();
+        let sample = (0..count)
+            .map(|offset| self.expires[(start + offset) % self.expires.len()].clone())
+            .collect();
+        self.expire_cursor = start + count;
+        sample
+    }
+
+    /// Returns the entry only if it is still alive at `now_ms`.
+    pub fn get_alive(&self, key: &[u8], now_ms: u64) -> Option<&Entry> {
+        self.entries.get(key).filter(|entry| !entry.is_expired(now_ms))
+    }
+
+    pub fn stats(&self, now_ms: u64) -> DatabaseStats {
+        let mut stats = DatabaseStats::default();
+        let mut total_ttl = 0;
+        for (key, entry) in &self.entries {
+            stats.keys += 1;
+            stats.memory += key.len() as u64 + entry.value.size() + ENTRY_OVERHEAD;
+            if let Some(expires_at) = entry.expires_at {
+                stats.expires += 1;
+                total_ttl += expires_at.saturating_sub(now_ms);
+            }
+        }
+        stats.avg_ttl = total_ttl.checked_div(stats.expires).unwrap_or(0);
+        stats
+    }
+}
+
+struct WatchedKey {
+    version: u64,
+    watchers: usize,
+}
+
+/// A point-in-time copy of every database, written to disk without
+/// holding the keyspace lock.
+pub struct Snapshot {
+    pub databases: Vec<Vec<(Vec<u8>, Entry)>>,
+    /// Changes made before the copy was taken.
+    pub dirty: u64,
+}
+
+/// All logical databases plus the modification versions used by WATCH.
+///
+/// Versions are only kept for keys somebody is watching, so touching an
+/// unwatched key is a single map lookup.
+pub struct Keyspace {
+    databases: Vec<Database>,
+    watched_keys: HashMap<(usize, Vec<u8>), WatchedKey>,
+    last_version: u64,
+    /// Changes since the last successful save, for the save policies.
+    dirty: u64,
+    /// The database the next expire cycle starts from, so one with many
+    /// expired keys does not starve the ones after it.
+    next_expire_db: usize,
+}
+
+impl Default for Keyspace {
+    fn default() -> Self {
+        Self {
+            databases: (0..DATABASES).map(|_| Database::default()).collect(),
+            watched_keys: HashMap::new(),
+            last_version: 0,
+            dirty: 0,
+            next_expire_db: 0,
+        }
+    }
+}
+
+impl Keyspace {
+    pub fn db(&self, index: usize) -> &Database {
+        &self.databases[index]
+    }
+
+    pub fn db_mut(&mut self, index: usize) -> &mut Database {
+        &mut self.databases[index]
+    }
+
+    /// Marks the key as modified, so any WATCH on it fails at EXEC and the
+    /// change counts towards the save policies.
+    pub fn touch(&mut self, db: usize, key: &[u8]) {
+        self.dirty += 1;
+        self.bump_version(db, key);
+    }
+
+    /// Counts changes that touch no single key, e.g. a flush.
+    pub fn mark_dirty(&mut self, changes: u64) {
+        self.dirty += changes;
+    }
+
+    pub fn dirty(&self) -> u64 {
+        self.dirty
+    }
+
+    pub fn next_expire_db(&self) -> usize {
+        self.next_expire_db
+    }
+
+    pub fn set_next_expire_db(&mut self, db: usize) {
+        self.next_expire_db = db % DATABASES;
+    }
+
+    /// Copies every database. Values are reference counted, so this costs
+    /// a key copy per entry rather than a full serialization.
+    pub fn snapshot(&self) -> Snapshot {
+        let databases = self.databases
+            .iter()
+            .map(|database| database.entries.iter().map(|(key, entry)| (key.clone(), entry.clone())).collect())
+            .collect();
+        Snapshot { databases, dirty: self.dirty }
+    }
+
+    /// Forgets the changes a successful save of `snapshot` persisted.
+    pub fn saved(&mut self, snapshot_dirty: u64) {
+        self.dirty = self.dirty.saturating_sub(snapshot_dirty);
+    }
+
+    fn bump_version(&mut self, db: usize, key: &[u8]) {
+        if self.watched_keys.is_empty() {
+            return;
+        }
+        if let Some(watched) = self.watched_keys.get_mut(&(db, key.to_vec())) {
+            self.last_version += 1;
+            watched.version = self.last_version;
+        }
+    }
+
+    /// Touches every watched key of `db` that currently holds a value.
+    pub fn touch_all(&mut self, db: usize) {
+        let keys: Vec<Vec<u8>> = self.watched_keys
+            .keys()
+            .filter(|(watched_db, key)| *watched_db == db && self.databases[db].entries.contains_key(key))
+            .map(|(_, key)| key.clone())
+            .collect();
+        for key in keys {
+            self.bump_version(db, &key);
+        }
+    }
+
+    pub fn watch(&mut self, db: usize, key: Vec<u8>) -> u64 {
+        let watched = self.watched_keys
+            .entry((db, key))
+            .or_insert(WatchedKey { version: 0, watchers: 0 });
+        watched.watchers += 1;
+        watched.version
+    }
+
+    pub fn unwatch(&mut self, db: usize, key: &[u8]) {
+        let id = (db, key.to_vec());
+        if let Some(watched) = self.watched_keys.get_mut(&id) {
+            watched.watchers -= 1;
+            if watched.watchers == 0 {
+                self.watched_keys.remove(&id);
+            }
+        }
+    }
+
+    pub fn version(&self, db: usize, key: &[u8]) -> u64 {
+        self.watched_keys
+            .get(&(db, key.to_vec()))
+            .map(|watched| watched.version)
+            .unwrap_or(0)
+    }
+
+    pub fn swap(&mut self, first: usize, second: usize) {
+        self.dirty += 1;
+        // Watched keys stay bound to the db index, so a key is modified for
+        // its watchers if it exists on either side of the swap.
+        self.touch_all(first);
+        self.touch_all(second);
+        self.databases.swap(first, second);
+        self.touch_all(first);
+        self.touch_all(second);
+    }
+}
+
+/// Shared keyspace plus the gate that serializes EXEC against other commands.
+#[derive(Clone, Default)]
+pub struct Storage {
+    keyspace: Arc<RwLock<Keyspace>>,
+    exec_gate: Arc<RwLock<()>>,
+}
+
+impl Storage {
+    pub async fn read(&self) -> RwLockReadGuard<'_, Keyspace> {
+        self.keyspace.read().await
+    }
+
+    pub async fn write(&self) -> RwLockWriteGuard<'_, Keyspace> {
+        self.keyspace.write().await
+    }
+
+    /// Held by every regular command while it runs.
+    pub async fn shared_gate(&self) -> RwLockReadGuard<'_, ()> {
+        self.exec_gate.read().await
+    }
+
+    /// Held by EXEC so a queued transaction runs without interleaving.
+    pub async fn exclusive_gate(&self) -> RwLockWriteGuard<'_, ()> {
+        self.exec_gate.write().await
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::resp_parser::domain::pubsub_message::PubSubMessage;
+use crate::resp_parser::domain::tracking::TrackingOptions;
+use crate::resp_parser::infra::memory::channel_registry::Subscriber;
+use crate::resp_parser::infra::memory::client_registry::ClientRegistry;
+
+struct TrackingClient {
+    /// Queue of the connection that receives the invalidations.
+    target: Subscriber,
+    /// Queue of the client itself, told when its REDIRECT target is gone.
+    own: Subscriber,
+    options: TrackingOptions,
+    /// Keys the client read since their last invalidation.
+    keys: HashSet<Vec<u8>>,
+}
+
+impl TrackingClient {
+    fn send(&self, message: PubSubMessage) {
+        if self.target.send(message).is_err() {
+            if let Some(redirect) = self.options.redirect {
+                let _ = self.own.send(PubSubMessage::TrackingRedirBroken { client_id: redirect });
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tracking {
+    clients: HashMap<u64, TrackingClient>,
+    /// Key name to the clients that read it since its last invalidation.
+    keys: HashMap<Vec<u8>, HashSet<u64>>,
+}
+
+impl Tracking {
+    /// Forgets who read the key, returning them.
+    fn forget(&mut self, key: &[u8]) -> HashSet<u64> {
+        let readers = self.keys.remove(key).unwrap_or_default();
+        for client_id in &readers {
+            if let Some(client) = self.clients.get_mut(client_id) {
+                client.keys.remove(key);
+            }
+        }
+        readers
+    }
+}
+
+/// Server side of client-side caching: who may hold which key.
+#[derive(Clone, Default)]
+pub struct TrackingTable {
+    tracking: Arc<RwLock<Tracking>>,
+    clients: Arc<AtomicUsize>,
+    /// Keys remembered at most, 0 for no limit.
+    max_keys: Arc<AtomicUsize>,
+    client_registry: ClientRegistry,
+}
+
+impl TrackingTable {
+    pub fn new(client_registry: ClientRegistry) -> Self {
+        Self {
+            tracking: Arc::default(),
+            clients: Arc::default(),
+            max_keys: Arc::default(),
+            client_registry,
+        }
+    }
+
+    pub fn set_max_keys(&self, max_keys: u64) {
+        self.max_keys.store(max_keys as usize, Ordering::Relaxed);
+    }
+
+    /// Cheap check so write paths can skip invalidation when nobody tracks.
+    pub fn is_active(&self) -> bool {
+        self.clients.load(Ordering::Relaxed) > 0
+    }
+
+    /// Starts tracking for the client, sending invalidations to `subscriber`
+    /// or to the REDIRECT client. Fails when the redirect client is gone.
+    pub async fn enable(&self, client_id: u64, options: TrackingOptions, subscriber: Subscriber) -> bool {
+        let target = match options.redirect {
+            Some(redirect) => match self.client_registry.subscriber(redirect).await {
+                Some(target) => target,
+                None => return false,
+            },
+            None => subscriber.clone(),
+        };
+        let mut tracking_lock = self.tracking.write().await;
+        // Enabling again changes the options but keeps the keys read so far.
+        let keys = tracking_lock.clients.remove(&client_id).map(|client| client.keys);
+        if keys.is_none() {
+            self.clients.fetch_add(1, Ordering::Relaxed);
+        }
+        let client = TrackingClient { target, own: subscriber, options, keys: keys.unwrap_or_default() };
+        tracking_lock.clients.insert(client_id, client);
+        true
+    }
+
+    pub async fn disable(&self, client_id: u64) {
+        let mut tracking_lock = self.tracking.write().await;
+        let Some(client) = tracking_lock.clients.remove(&client_id) else {
+            return;
+        };
+        self.clients.fetch_sub(1, Ordering::Relaxed);
+        for key in client.keys {
+            if let Some(readers) = tracking_lock.keys.get_mut(&key) {
+                readers.remove(&client_id);
+                if readers.is_empty() {
+                    tracking_lock.keys.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Records that the client read the key and may cache it. Past
+    /// `tracking-table-max-keys` other keys are invalidated to make room,
+    /// as their readers can no longer be told when they change.
+    pub async fn remember(&self, client_id: u64, key: &[u8]) {
+        let mut tracking_lock = self.tracking.write().await;
+        let Some(client) = tracking_lock.clients.get_mut(&client_id) else {
+            return;
+        };
+        client.keys.insert(key.to_vec());
+        tracking_lock.keys.entry(key.to_vec()).or_default().insert(client_id);
+
+        let max_keys = self.max_keys.load(Ordering::Relaxed);
+        while max_keys > 0 && tracking_lock.keys.len() > max_keys {
+            let Some(evicted) = tracking_lock.keys.keys().find(|evicted| evicted.as_slice() != key).cloned() else {
+                break;
+            };
+            for reader in tracking_lock.forget(&evicted) {
+                if let Some(client) = tracking_lock.clients.get(&reader) {
+                    client.send(PubSubMessage::Invalidate { keys: Some(vec![evicted.clone()]) });
+                }
+            }
+        }
+    }
+
+    /// Tells every client that read the key, or broadcasts on a matching
+    /// prefix, that it changed. `origin` is the writer, for NOLOOP.
+    pub async fn invalidate(&self, key: &[u8], origin: Option<u64>) {
+        if !self.is_active() {
+            return;
+        }
+        let mut tracking_lock = self.tracking.write().await;
+        let readers = tracking_lock.forget(key);
+
+        for (client_id, client) in &tracking_lock.clients {
+            if client.options.noloop && origin == Some(*client_id) {
+                continue;
+            }
+            let is_interested = if client.options.bcast {
+                client.options.prefixes.is_empty()
+                    || client.options.prefixes.iter().any(|prefix| key.starts_with(prefix))
+            } else {
+                readers.contains(client_id)
+            };
+            if is_interested {
+                client.send(PubSubMessage::Invalidate { keys: Some(vec![key.to_vec()]) });
+            }
+        }
+    }
+
+    /// Flushes invalidate everything: clients get a null key list.
+    pub async fn invalidate_all(&self) {
+        if !self.is_active() {
+            return;
+        }
+        let mut tracking_lock = self.tracking.write().await;
+        tracking_lock.keys.clear();
+        for client in tracking_lock.clients.values_mut() {
+            client.keys.clear();
+            client.send(PubSubMessage::Invalidate { keys: None });
+        }
+    }
+
+    /// Number of keys remembered for tracking clients.
+    pub async fn key_count(&self) -> usize {
+        self.tracking.read().await.keys.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_forgets_the_keys_of_disabled_clients() {
+        let tracking_table = TrackingTable::default();
+        let (subscriber, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        tracking_table.enable(1, TrackingOptions::default(), subscriber.clone()).await;
+        tracking_table.enable(2, TrackingOptions::default(), subscriber).await;
+        tracking_table.remember(1, b"a").await;
+        tracking_table.remember(1, b"b").await;
+        tracking_table.remember(2, b"b").await;
+        // Clients that do not track are not remembered.
+        tracking_table.remember(3, b"c").await;
+        assert_eq!(tracking_table.key_count().await, 2);
+
+        tracking_table.disable(1).await;
+        assert_eq!(tracking_table.key_count().await, 1);
+        tracking_table.disable(2).await;
+        assert_eq!(tracking_table.key_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalidates_keys_past_the_limit() {
+        let tracking_table = TrackingTable::default();
+        tracking_table.set_max_keys(2);
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        tracking_table.enable(1, TrackingOptions::default(), subscriber).await;
+        tracking_table.remember(1, b"a").await;
+        tracking_table.remember(1, b"b").await;
+        assert!(receiver.try_recv().is_err());
+
+        tracking_table.remember(1, b"c").await;
+        assert_eq!(tracking_table.key_count().await, 2);
+        // Either older key makes room, never the one just read.
+        match receiver.try_recv() {
+            Ok(PubSubMessage::Invalidate { keys: Some(keys) }) => assert!(keys == [b"a"] || keys == [b"b"]),
+            _ => panic!("Expected invalidation"),
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tells_the_client_its_redirect_target_is_gone() {
+        let client_registry = ClientRegistry::default();
+        let tracking_table = TrackingTable::new(client_registry.clone());
+        let (target, target_receiver) = tokio::sync::mpsc::unbounded_channel();
+        client_registry.register(2, target).await;
+        let (subscriber, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let options = TrackingOptions { redirect: Some(2), ..TrackingOptions::default() };
+        assert!(tracking_table.enable(1, options, subscriber).await);
+        tracking_table.remember(1, b"a").await;
+
+        drop(target_receiver);
+        tracking_table.invalidate(b"a", None).await;
+        assert!(matches!(receiver.try_recv(), Ok(PubSubMessage::TrackingRedirBroken { client_id: 2 })));
+    }
+}
@@ -1,2 +1,6 @@
-pub(crate) mod new_line_stream_chunking_service;
-pub mod memory;
\ No newline at end of file
+pub mod resp_stream_chunking_service;
+pub mod memory;
+pub mod config_file;
+pub mod rdb;
+pub mod aof;
+pub mod replication;
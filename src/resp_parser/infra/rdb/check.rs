@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use crate::resp_parser::infra::rdb::reader::{Item, RdbReader};
+use crate::resp_parser::infra::rdb::RdbError;
+
+/// Where reading an RDB file stopped.
+#[derive(Debug)]
+pub struct Corruption {
+    /// Offset the reader failed at.
+    pub offset: usize,
+    /// Start of the record being read, the end of the valid ones.
+    pub record_offset: usize,
+    pub error: RdbError,
+}
+
+/// What `check` found in an RDB file, as redis-check-rdb reports it.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Auxiliary fields with the offset each was read at.
+    pub aux: Vec<(usize, String, String)>,
+    /// Keys read from each database.
+    pub databases: BTreeMap<usize, u64>,
+    /// Keys read of each type.
+    pub types: BTreeMap<&'static str, u64>,
+    pub keys: u64,
+    pub expires: u64,
+    /// Keys whose expiry had passed, which loading would drop.
+    pub already_expired: u64,
+    pub last_key: Option<String>,
+    /// Bytes up to and including the checksum, once the file was read to
+    /// its end.
+    pub length: Option<usize>,
+    pub error: Option<Corruption>,
+}
+
+/// Reads the RDB file at the start of `data` through the reader the server
+/// loads snapshots with, stopping at the first corruption.
+pub fn check(data: &[u8], now_ms: u64) -> Report {
+    let mut report = Report::default();
+    let mut reader = match RdbReader::new(data) {
+        Ok(reader) => reader,
+        Err(error) => {
+            report.error = Some(Corruption { offset: 0, record_offset: 0, error });
+            return report;
+        },
+    };
+    let mut db = 0;
+    loop {
+        let record_offset = reader.offset();
+        let item = match reader.next_item() {
+            Ok(Some(item)) => item,
+            Ok(None) => {
+                report.length = Some(reader.offset());
+                return report;
+            },
+            Err(error) => {
+                report.error = Some(Corruption { offset: reader.offset(), record_offset, error });
+                return report;
+            },
+        };
+        match item {
+            Item::Aux { key, value } => report.aux.push((
+                record_offset,
+                String::from_utf8_lossy(&key).to_string(),
+                String::from_utf8_lossy(&value).to_string(),
+            )),
+            Item::SelectDb(index) => db = index,
+            Item::ResizeDb { .. } => {},
+            Item::Entry { key, value, expires_at } => {
+                report.keys += 1;
+                *report.databases.entry(db).or_default() += 1;
+                *report.types.entry(value.type_name()).or_default() += 1;
+                if let Some(expires_at) = expires_at {
+                    report.expires += 1;
+                    if expires_at <= now_ms {
+                        report.already_expired += 1;
+                    }
+                }
+                report.last_key = Some(String::from_utf8_lossy(&key).to_string());
+            },
+        }
+    }
+}
+
+impl Report {
+    /// Prints the report in the format of redis-check-rdb.
+    pub fn print(&self) {
+        for (offset, key, value) in &self.aux {
+            println!("[offset {}] AUX FIELD {} = '{}'", offset, key, value);
+        }
+        match (&self.error, self.length) {
+            (Some(corruption), _) => {
+                println!("--- RDB ERROR DETECTED ---");
+                println!("[offset {}] {}", corruption.offset, corruption.error);
+                println!("[additional info] While reading the record at offset {}", corruption.record_offset);
+                if let Some(key) = &self.last_key {
+                    println!("[additional info] Last key read: '{}'", key);
+                }
+            },
+            (None, Some(length)) => {
+                println!("[offset {}] Checksum OK", length);
+                println!("[offset {}] \\o/ RDB looks OK! \\o/", length);
+            },
+            (None, None) => {},
+        }
+        println!("[info] {} keys read", self.keys);
+        println!("[info] {} expires", self.expires);
+        println!("[info] {} already expired", self.already_expired);
+        for (db, keys) in &self.databases {
+            println!("[info] db {}: {} keys", db, keys);
+        }
+        for (kind, keys) in &self.types {
+            println!("[info] {} {} keys", keys, kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_parser::infra::memory::storage::{Entry, Snapshot, Value};
+    use crate::resp_parser::infra::rdb::writer::encode;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_counts_keys_and_locates_the_first_corruption() {
+        let databases = vec![vec![
+            (b"a".to_vec(), Entry::new(Value::String(Bytes::from_static(b"1")), None)),
+            (b"b".to_vec(), Entry::new(Value::List(vec![Bytes::from_static(b"x")]), Some(1_000))),
+            (b"c".to_vec(), Entry::new(Value::String(Bytes::from_static(b"3")), Some(5_000))),
+        ]];
+        let data = encode(&Snapshot { databases, dirty: 0 }, 2_000, false);
+
+        let report = check(&data, 2_000);
+        assert!(report.error.is_none());
+        assert_eq!(report.length, Some(data.len()));
+        assert_eq!((report.keys, report.expires, report.already_expired), (3, 2, 1));
+        assert_eq!(report.types.get("string"), Some(&2));
+        assert_eq!(report.databases.get(&0), Some(&3));
+
+        let report = check(&data[..data.len() - 12], 2_000);
+        let corruption = report.error.unwrap();
+        assert!(matches!(corruption.error, RdbError::UnexpectedEof));
+        assert!(corruption.offset >= corruption.record_offset);
+        assert!(report.keys < 3);
+    }
+}
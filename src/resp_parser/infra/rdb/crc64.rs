@@ -0,0 +1,37 @@
+/// The Jones polynomial in reflected form, as Redis uses for RDB files and
+/// DUMP payloads.
+const POLYNOMIAL: u64 = 0x95ac_9329_ac4b_c9b5;
+
+const TABLE: [u64; 256] = table();
+
+const fn table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// Continues a checksum over `data`; start from 0.
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    data.iter().fold(crc, |crc, byte| TABLE[((crc ^ *byte as u64) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_value() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+        assert_eq!(crc64(crc64(0, b"1234"), b"56789"), 0xe9c6_d914_c4b8_d9ca);
+    }
+}
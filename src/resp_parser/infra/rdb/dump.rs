@@ -0,0 +1,74 @@
+//! The payload DUMP returns and RESTORE takes: a value as it is written in
+//! an RDB file, followed by the RDB version as two little endian bytes and
+//! a CRC64 of everything before it, so keys move to and from real Redis.
+
+use crate::resp_parser::infra::memory::storage::Value;
+use crate::resp_parser::infra::rdb::crc64::crc64;
+use crate::resp_parser::infra::rdb::reader::RdbReader;
+use crate::resp_parser::infra::rdb::{writer, RDB_VERSION, SAVE_VERSION};
+
+/// Why RESTORE refuses a payload, as the message it replies with.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PayloadError {
+    #[error("DUMP payload version or checksum are wrong")]
+    Footer,
+    #[error("Bad data format")]
+    Format,
+}
+
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = vec![writer::value_type(value)];
+    writer::write_value(&mut out, value);
+    out.extend_from_slice(&(SAVE_VERSION as u16).to_le_bytes());
+    let checksum = crc64(0, &out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// Reads a payload written by any Redis whose RDB version this server
+/// reads. The value has to fill the payload exactly.
+pub fn decode(payload: &[u8]) -> Result<Value, PayloadError> {
+    if payload.len() < 11 {
+        return Err(PayloadError::Footer);
+    }
+    let (body, checksum) = payload.split_at(payload.len() - 8);
+    let version = u16::from_le_bytes([body[body.len() - 2], body[body.len() - 1]]) as u32;
+    if version > RDB_VERSION || crc64(0, body).to_le_bytes() != checksum {
+        return Err(PayloadError::Footer);
+    }
+
+    let value = &body[1..body.len() - 2];
+    let mut reader = RdbReader::values(value, version);
+    match reader.read_value(body[0]) {
+        Ok(decoded) if reader.offset() == value.len() => Ok(decoded),
+        _ => Err(PayloadError::Format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use super::*;
+
+    #[test]
+    fn test_payloads_round_trip_and_match_redis() {
+        // `DUMP mykey` of the integer 10 on Redis 6, from the DUMP docs.
+        let redis = b"\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n";
+        assert_eq!(decode(redis), Ok(Value::String(Bytes::from_static(b"10"))));
+        assert_eq!(&encode(&Value::String(Bytes::from_static(b"10")))[..3], b"\x00\xc0\n");
+
+        let hash = Value::Hash(vec![(Bytes::from_static(b"field"), Bytes::from_static(b"value"))]);
+        let mut payload = encode(&hash);
+        assert_eq!(decode(&payload), Ok(hash));
+
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert_eq!(decode(&payload), Err(PayloadError::Footer));
+
+        let mut extra = vec![0, 1, b'a', b'b'];
+        extra.extend_from_slice(&(SAVE_VERSION as u16).to_le_bytes());
+        let checksum = crc64(0, &extra);
+        extra.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(decode(&extra), Err(PayloadError::Format));
+    }
+}
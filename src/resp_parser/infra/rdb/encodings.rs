@@ -0,0 +1,202 @@
+//! The compact containers Redis serializes small collections as. Each
+//! decoder returns the elements in order, integers as their decimal text,
+//! or `None` when the blob is corrupt.
+
+use bytes::Bytes;
+
+fn integer(value: i64) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+fn u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Sign extends the low `bits` of `value`.
+fn signed(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Little endian integer of `width` bytes at `at`.
+fn uint_le(data: &[u8], at: usize, width: usize) -> Option<u64> {
+    let bytes = data.get(at..at + width)?;
+    Some(bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u64))
+}
+
+/// Ziplist: `zlbytes zltail zllen entry... 0xff`, each entry prefixed by
+/// the previous entry's length and its own encoding.
+pub fn ziplist(data: &[u8]) -> Option<Vec<Bytes>> {
+    if u32_le(data, 0)? as usize != data.len() {
+        return None;
+    }
+    let mut elements = Vec::new();
+    let mut position = 10;
+    loop {
+        match *data.get(position)? {
+            0xff => break,
+            0xfe => position += 5,
+            _ => position += 1,
+        }
+        let encoding = *data.get(position)?;
+        let (element, size) = match encoding >> 6 {
+            0b00 => {
+                let length = (encoding & 0x3f) as usize;
+                (Bytes::copy_from_slice(data.get(position + 1..position + 1 + length)?), 1 + length)
+            },
+            0b01 => {
+                let length = (((encoding & 0x3f) as usize) << 8) | *data.get(position + 1)? as usize;
+                (Bytes::copy_from_slice(data.get(position + 2..position + 2 + length)?), 2 + length)
+            },
+            0b10 => {
+                let length = u32::from_be_bytes(data.get(position + 1..position + 5)?.try_into().ok()?) as usize;
+                (Bytes::copy_from_slice(data.get(position + 5..position + 5 + length)?), 5 + length)
+            },
+            _ => match encoding {
+                0xc0 => (integer(signed(uint_le(data, position + 1, 2)?, 16)), 3),
+                0xd0 => (integer(signed(uint_le(data, position + 1, 4)?, 32)), 5),
+                0xe0 => (integer(uint_le(data, position + 1, 8)? as i64), 9),
+                0xf0 => (integer(signed(uint_le(data, position + 1, 3)?, 24)), 4),
+                0xfe => (integer(signed(uint_le(data, position + 1, 1)?, 8)), 2),
+                0xf1..=0xfd => (integer((encoding & 0x0f) as i64 - 1), 1),
+                _ => return None,
+            },
+        };
+        elements.push(element);
+        position += size;
+    }
+    (elements.len() == u16_le(data, 8)? as usize || u16_le(data, 8)? == u16::MAX).then_some(elements)
+}
+
+/// Listpack: `total-bytes num-elements entry... 0xff`, each entry followed
+/// by a back length so it can be walked from either end.
+pub fn listpack(data: &[u8]) -> Option<Vec<Bytes>> {
+    if u32_le(data, 0)? as usize != data.len() {
+        return None;
+    }
+    let mut elements = Vec::new();
+    let mut position = 6;
+    loop {
+        let encoding = *data.get(position)?;
+        let (element, size) = if encoding == 0xff {
+            break;
+        } else if encoding & 0x80 == 0 {
+            (integer((encoding & 0x7f) as i64), 1)
+        } else if encoding & 0xc0 == 0x80 {
+            let length = (encoding & 0x3f) as usize;
+            (Bytes::copy_from_slice(data.get(position + 1..position + 1 + length)?), 1 + length)
+        } else if encoding & 0xe0 == 0xc0 {
+            let value = (((encoding & 0x1f) as u64) << 8) | *data.get(position + 1)? as u64;
+            (integer(signed(value, 13)), 2)
+        } else if encoding & 0xf0 == 0xe0 {
+            let length = (((encoding & 0x0f) as usize) << 8) | *data.get(position + 1)? as usize;
+            (Bytes::copy_from_slice(data.get(position + 2..position + 2 + length)?), 2 + length)
+        } else {
+            match encoding {
+                0xf0 => {
+                    let length = u32_le(data, position + 1)? as usize;
+                    (Bytes::copy_from_slice(data.get(position + 5..position + 5 + length)?), 5 + length)
+                },
+                0xf1 => (integer(signed(uint_le(data, position + 1, 2)?, 16)), 3),
+                0xf2 => (integer(signed(uint_le(data, position + 1, 3)?, 24)), 4),
+                0xf3 => (integer(signed(uint_le(data, position + 1, 4)?, 32)), 5),
+                0xf4 => (integer(uint_le(data, position + 1, 8)? as i64), 9),
+                _ => return None,
+            }
+        };
+        elements.push(element);
+        position += size + back_length_size(size);
+    }
+    let count = u16_le(data, 4)?;
+    (elements.len() == count as usize || count == u16::MAX).then_some(elements)
+}
+
+/// Bytes the back length of an entry of `size` bytes takes.
+fn back_length_size(size: usize) -> usize {
+    match size {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+/// Intset: `encoding length integer...`, integers of 2, 4 or 8 bytes.
+pub fn intset(data: &[u8]) -> Option<Vec<Bytes>> {
+    let width = u32_le(data, 0)? as usize;
+    let length = u32_le(data, 4)? as usize;
+    if !matches!(width, 2 | 4 | 8) || data.len() != 8 + width * length {
+        return None;
+    }
+    (0..length)
+        .map(|index| Some(integer(signed(uint_le(data, 8 + index * width, width)?, width as u32 * 8))))
+        .collect()
+}
+
+/// Zipmap, the hash encoding before ziplists: alternating fields and values
+/// where each value may be followed by unused bytes.
+pub fn zipmap(data: &[u8]) -> Option<Vec<Bytes>> {
+    let mut elements = Vec::new();
+    let mut position = 1;
+    loop {
+        let length = match *data.get(position)? {
+            0xff => break,
+            0xfe => {
+                position += 4;
+                u32_le(data, position - 3)? as usize
+            },
+            length => length as usize,
+        };
+        position += 1;
+        // Values carry a free byte count; fields do not.
+        let free = match elements.len() % 2 {
+            1 => {
+                position += 1;
+                *data.get(position - 1)? as usize
+            },
+            _ => 0,
+        };
+        elements.push(Bytes::copy_from_slice(data.get(position..position + length)?));
+        position += length + free;
+    }
+    elements.len().is_multiple_of(2).then_some(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(elements: Option<Vec<Bytes>>) -> Vec<String> {
+        elements.unwrap().iter().map(|element| String::from_utf8_lossy(element).to_string()).collect()
+    }
+
+    #[test]
+    fn test_decoders() {
+        // ziplist of "ab", 12 (immediate), -2 (int8) and 300 (int16).
+        let mut ziplist_data = vec![0, 0, 0, 0, 0, 0, 0, 0, 4, 0];
+        ziplist_data.extend([0, 0x02, b'a', b'b', 4, 0xfd, 2, 0xfe, 0xfe, 3, 0xc0, 0x2c, 0x01, 0xff]);
+        let length = ziplist_data.len() as u32;
+        ziplist_data[..4].copy_from_slice(&length.to_le_bytes());
+        assert_eq!(texts(ziplist(&ziplist_data)), ["ab", "12", "-2", "300"]);
+
+        // listpack of "ab", 5 (7 bit), -1 (13 bit) and 70000 (int24).
+        let mut listpack_data = vec![0, 0, 0, 0, 4, 0];
+        listpack_data.extend([0x82, b'a', b'b', 3, 0x05, 1, 0xdf, 0xff, 2, 0xf2, 0x70, 0x11, 0x01, 4, 0xff]);
+        let length = listpack_data.len() as u32;
+        listpack_data[..4].copy_from_slice(&length.to_le_bytes());
+        assert_eq!(texts(listpack(&listpack_data)), ["ab", "5", "-1", "70000"]);
+        listpack_data[4] = 3;
+        assert_eq!(listpack(&listpack_data), None);
+
+        assert_eq!(texts(intset(&[2, 0, 0, 0, 2, 0, 0, 0, 0xff, 0xff, 7, 0])), ["-1", "7"]);
+        assert_eq!(intset(&[2, 0, 0, 0, 3, 0, 0, 0, 0, 0]), None);
+
+        let zipmap_data = [1, 1, b'f', 2, 1, b'v', b'w', 0, 0xff];
+        assert_eq!(texts(zipmap(&zipmap_data)), ["f", "vw"]);
+    }
+}
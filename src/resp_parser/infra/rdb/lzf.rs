@@ -0,0 +1,56 @@
+/// Most bytes one byte of LZF data decompresses to.
+const MAX_EXPANSION: usize = 88;
+
+/// Decompresses LZF data into exactly `length` bytes, or `None` when the
+/// data is corrupt.
+pub fn decompress(input: &[u8], length: usize) -> Option<Vec<u8>> {
+    // `length` comes from the data, so only as much is reserved as `input`
+    // can expand to: a back reference of three bytes yields at most 264.
+    let mut output = Vec::with_capacity(length.min(input.len().saturating_mul(MAX_EXPANSION)));
+    let mut position = 0;
+    while position < input.len() {
+        let control = input[position] as usize;
+        position += 1;
+        if control < 32 {
+            // A literal run of control + 1 bytes.
+            let run = input.get(position..position + control + 1)?;
+            if output.len() + run.len() > length {
+                return None;
+            }
+            output.extend_from_slice(run);
+            position += control + 1;
+            continue;
+        }
+
+        // A back reference, which may overlap the bytes it produces.
+        let mut run = control >> 5;
+        if run == 7 {
+            run += *input.get(position)? as usize;
+            position += 1;
+        }
+        let offset = ((control & 0x1f) << 8) + *input.get(position)? as usize + 1;
+        position += 1;
+        let start = output.len().checked_sub(offset)?;
+        if output.len() + run + 2 > length {
+            return None;
+        }
+        for index in start..start + run + 2 {
+            output.push(output[index]);
+        }
+    }
+    (output.len() == length).then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literals_and_back_references() {
+        // "abc" as a literal, then 6 bytes copied from 3 back.
+        assert_eq!(decompress(&[0x02, b'a', b'b', b'c', 0x80, 0x02], 9), Some(b"abcabcabc".to_vec()));
+        assert_eq!(decompress(&[0x02, b'a', b'b', b'c', 0x80, 0x02], 8), None);
+        assert_eq!(decompress(&[0x80, 0x02], 4), None);
+        assert_eq!(decompress(&[0x02, b'a', b'b', b'c', 0x80, 0x02], 1 << 62), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! The RDB snapshot format Redis persists its keyspace in.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, Entry, Storage, DATABASES};
+use crate::resp_parser::infra::rdb::reader::{Item, RdbReader};
+
+pub mod check;
+pub mod crc64;
+pub mod dump;
+pub mod encodings;
+pub mod lzf;
+pub mod reader;
+pub mod snapshotter;
+pub mod writer;
+
+/// The newest format version this server reads, that of Redis 7.4.
+pub const RDB_VERSION: u32 = 12;
+/// Files are written in the format of Redis 7.2, the version INFO reports.
+pub const SAVE_VERSION: u32 = 11;
+
+/// Opcodes that may appear where an object type is expected.
+pub mod opcode {
+    pub const FUNCTION2: u8 = 0xf5;
+    pub const FUNCTION_PRE_GA: u8 = 0xf6;
+    pub const MODULE_AUX: u8 = 0xf7;
+    pub const IDLE: u8 = 0xf8;
+    pub const FREQ: u8 = 0xf9;
+    pub const AUX: u8 = 0xfa;
+    pub const RESIZEDB: u8 = 0xfb;
+    pub const EXPIRETIME_MS: u8 = 0xfc;
+    pub const EXPIRETIME: u8 = 0xfd;
+    pub const SELECTDB: u8 = 0xfe;
+    pub const EOF: u8 = 0xff;
+}
+
+/// Object types, each a value type in one of its encodings.
+pub mod object_type {
+    pub const STRING: u8 = 0;
+    pub const LIST: u8 = 1;
+    pub const SET: u8 = 2;
+    pub const ZSET: u8 = 3;
+    pub const HASH: u8 = 4;
+    pub const ZSET_2: u8 = 5;
+    pub const HASH_ZIPMAP: u8 = 9;
+    pub const LIST_ZIPLIST: u8 = 10;
+    pub const SET_INTSET: u8 = 11;
+    pub const ZSET_ZIPLIST: u8 = 12;
+    pub const HASH_ZIPLIST: u8 = 13;
+    pub const LIST_QUICKLIST: u8 = 14;
+    pub const HASH_LISTPACK: u8 = 16;
+    pub const ZSET_LISTPACK: u8 = 17;
+    pub const LIST_QUICKLIST_2: u8 = 18;
+    pub const SET_LISTPACK: u8 = 20;
+    #[cfg(test)]
+    pub const STREAM_LISTPACKS_3: u8 = 21;
+
+    /// Names of the types the format has but this server cannot hold.
+    pub fn unsupported_name(kind: u8) -> Option<&'static str> {
+        match kind {
+            6 | 7 => Some("module"),
+            15 | 19 | 21 => Some("stream"),
+            22..=25 => Some("hash with field expiration"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RdbError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Wrong signature trying to load DB from file")]
+    Signature,
+    #[error("Can't handle RDB format version {0}")]
+    Version(u32),
+    #[error("Unexpected EOF reading RDB file")]
+    UnexpectedEof,
+    #[error("Wrong RDB checksum expected: ({expected:016x}) got: ({actual:016x})")]
+    Checksum {
+        expected: u64,
+        actual: u64,
+    },
+    #[error("Unsupported RDB object type {name} ({kind}) for key '{key}'")]
+    UnsupportedType {
+        kind: u8,
+        name: &'static str,
+        key: String,
+    },
+    #[error("{0} in RDB files are not supported")]
+    Unsupported(&'static str),
+    #[error("Unknown RDB object type {0}")]
+    UnknownType(u8),
+    #[error("Bad RDB file: {0}")]
+    Corrupt(String),
+}
+
+/// Keys read from a snapshot.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Loaded {
+    pub keys: u64,
+    /// Keys that expired while the snapshot was on disk and were dropped.
+    pub expired: u64,
+    /// The database the replication stream after the snapshot has
+    /// selected, when a master sent it.
+    pub stream_db: Option<usize>,
+}
+
+/// Loads an RDB file into `storage`. Anything the file holds that this
+/// server cannot represent is an error rather than being dropped.
+pub async fn load(path: &Path, storage: &Storage) -> Result<Loaded, RdbError> {
+    let data = fs::read(path)?;
+    load_data(&data, storage).await.map(|(loaded, _)| loaded)
+}
+
+/// Loads the RDB file at the start of `data`, returning its length too so
+/// whatever follows it, e.g. the commands of an append-only file, can be
+/// read next.
+pub async fn load_data(data: &[u8], storage: &Storage) -> Result<(Loaded, usize), RdbError> {
+    let mut reader = RdbReader::new(data)?;
+    let mut keyspace = storage.write().await;
+    let now_ms = unix_time_ms();
+    let mut loaded = Loaded::default();
+    let mut db = 0;
+    while let Some(item) = reader.next_item()? {
+        match item {
+            Item::Aux { key, value } if key.as_ref() == b"redis-ver" => {
+                println!("Loading RDB produced by version {}", String::from_utf8_lossy(&value));
+            },
+            Item::Aux { key, value } if key.as_ref() == b"repl-stream-db" => {
+                loaded.stream_db = std::str::from_utf8(&value).ok()
+                    .and_then(|db| db.parse().ok())
+                    .filter(|db| *db < DATABASES);
+            },
+            Item::Aux { .. } => {},
+            Item::SelectDb(index) if index >= DATABASES => {
+                return Err(RdbError::Corrupt(format!(
+                    "Data file was created with a Redis server configured to handle more than {} databases",
+                    DATABASES,
+                )));
+            },
+            Item::SelectDb(index) => db = index,
+            // Only a hint, so a corrupt one must not exhaust memory.
+            Item::ResizeDb { keys, .. } => keyspace.db_mut(db).reserve(keys.min(1 << 20) as usize),
+            Item::Entry { expires_at, .. } if expires_at.is_some_and(|expires_at| expires_at <= now_ms) => {
+                loaded.expired += 1;
+            },
+            Item::Entry { key, value, expires_at } => {
+                keyspace.db_mut(db).insert(key, Entry::new(value, expires_at));
+                loaded.keys += 1;
+            },
+        }
+    }
+    Ok((loaded, reader.offset()))
+}
+
+/// Writes a file next to `path` and renames it into place, so a crash
+/// never leaves a half written snapshot behind.
+pub fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!("temp-{}-{}", std::process::id(), file_name));
+    let result = fs::File::create(&temporary)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
@@ -0,0 +1,429 @@
+use bytes::Bytes;
+use crate::resp_parser::infra::memory::storage::Value;
+use crate::resp_parser::infra::rdb::crc64::crc64;
+use crate::resp_parser::infra::rdb::{encodings, lzf, opcode, object_type, RdbError, RDB_VERSION};
+
+/// One record of an RDB file, in file order.
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    /// Metadata such as `redis-ver` or `ctime`.
+    Aux {
+        key: Bytes,
+        value: Bytes,
+    },
+    SelectDb(usize),
+    /// Size hints for the database that follows.
+    ResizeDb {
+        keys: u64,
+        expires: u64,
+    },
+    Entry {
+        key: Vec<u8>,
+        value: Value,
+        /// Absolute expiry as unix time in milliseconds.
+        expires_at: Option<u64>,
+    },
+}
+
+/// Reads an RDB file held in memory. The checksum is verified once the
+/// end of file opcode is reached.
+pub struct RdbReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    version: u32,
+    finished: bool,
+}
+
+impl<'a> RdbReader<'a> {
+    /// Checks the `REDIS0011` style header.
+    pub fn new(data: &'a [u8]) -> Result<Self, RdbError> {
+        if data.len() < 9 || &data[..5] != b"REDIS" {
+            return Err(RdbError::Signature);
+        }
+        let version = std::str::from_utf8(&data[5..9])
+            .ok()
+            .and_then(|version| version.parse().ok())
+            .ok_or(RdbError::Signature)?;
+        if !(1..=RDB_VERSION).contains(&version) {
+            return Err(RdbError::Version(version));
+        }
+        Ok(Self {
+            data,
+            position: 9,
+            version,
+            finished: false,
+        })
+    }
+
+    /// Reads bare values in the format of `version`, e.g. a DUMP payload,
+    /// which has no header.
+    pub fn values(data: &'a [u8], version: u32) -> Self {
+        Self {
+            data,
+            position: 0,
+            version,
+            finished: false,
+        }
+    }
+
+    /// Bytes read so far, the whole file once `next_item` returned `None`.
+    pub fn offset(&self) -> usize {
+        self.position
+    }
+
+    /// The next record, or `None` after the end of file opcode.
+    pub fn next_item(&mut self) -> Result<Option<Item>, RdbError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let mut expires_at = None;
+        loop {
+            match self.read_u8()? {
+                opcode::EXPIRETIME_MS => expires_at = Some(u64::from_le_bytes(self.read_array()?)),
+                opcode::EXPIRETIME => expires_at = Some(u32::from_le_bytes(self.read_array()?) as u64 * 1000),
+                // Eviction hints, which mean nothing without eviction.
+                opcode::FREQ => {
+                    self.read_u8()?;
+                },
+                opcode::IDLE => {
+                    self.read_length()?;
+                },
+                opcode::AUX => {
+                    let key = self.read_string()?;
+                    let value = self.read_string()?;
+                    return Ok(Some(Item::Aux { key, value }));
+                },
+                opcode::RESIZEDB => {
+                    let keys = self.read_length()?;
+                    let expires = self.read_length()?;
+                    return Ok(Some(Item::ResizeDb { keys, expires }));
+                },
+                opcode::SELECTDB => {
+                    let index = self.read_length()?;
+                    return Ok(Some(Item::SelectDb(index as usize)));
+                },
+                opcode::MODULE_AUX => return Err(RdbError::Unsupported("Module auxiliary data")),
+                opcode::FUNCTION_PRE_GA | opcode::FUNCTION2 => return Err(RdbError::Unsupported("Function libraries")),
+                opcode::EOF => {
+                    self.verify_checksum()?;
+                    self.finished = true;
+                    return Ok(None);
+                },
+                kind => {
+                    let key = self.read_string()?.to_vec();
+                    let value = self.read_value(kind).map_err(|error| match error {
+                        RdbError::UnsupportedType { kind, name, .. } => RdbError::UnsupportedType {
+                            kind,
+                            name,
+                            key: String::from_utf8_lossy(&key).to_string(),
+                        },
+                        error => error,
+                    })?;
+                    return Ok(Some(Item::Entry { key, value, expires_at }));
+                },
+            }
+        }
+    }
+
+    /// A value of the given object type.
+    pub fn read_value(&mut self, kind: u8) -> Result<Value, RdbError> {
+        let value = match kind {
+            object_type::STRING => Value::String(self.read_string()?),
+            object_type::LIST => Value::List(self.read_strings()?),
+            object_type::SET => Value::Set(self.read_strings()?),
+            object_type::ZSET => {
+                let mut members = Vec::new();
+                for _ in 0..self.read_length()? {
+                    let member = self.read_string()?;
+                    members.push((member, self.read_string_double()?));
+                }
+                Value::SortedSet(members)
+            },
+            object_type::ZSET_2 => {
+                let mut members = Vec::new();
+                for _ in 0..self.read_length()? {
+                    let member = self.read_string()?;
+                    members.push((member, f64::from_le_bytes(self.read_array()?)));
+                }
+                Value::SortedSet(members)
+            },
+            object_type::HASH => {
+                let mut fields = Vec::new();
+                for _ in 0..self.read_length()? {
+                    let field = self.read_string()?;
+                    fields.push((field, self.read_string()?));
+                }
+                Value::Hash(fields)
+            },
+            object_type::HASH_ZIPMAP => Value::Hash(pairs(self.read_blob(encodings::zipmap, "zipmap")?)?),
+            object_type::LIST_ZIPLIST => Value::List(self.read_blob(encodings::ziplist, "ziplist")?),
+            object_type::SET_INTSET => Value::Set(self.read_blob(encodings::intset, "intset")?),
+            object_type::ZSET_ZIPLIST => Value::SortedSet(scores(self.read_blob(encodings::ziplist, "ziplist")?)?),
+            object_type::HASH_ZIPLIST => Value::Hash(pairs(self.read_blob(encodings::ziplist, "ziplist")?)?),
+            object_type::LIST_QUICKLIST => {
+                let mut elements = Vec::new();
+                for _ in 0..self.read_length()? {
+                    elements.extend(self.read_blob(encodings::ziplist, "ziplist")?);
+                }
+                Value::List(elements)
+            },
+            object_type::LIST_QUICKLIST_2 => {
+                let mut elements = Vec::new();
+                for _ in 0..self.read_length()? {
+                    match self.read_length()? {
+                        QUICKLIST_NODE_PLAIN => elements.push(self.read_string()?),
+                        QUICKLIST_NODE_PACKED => elements.extend(self.read_blob(encodings::listpack, "listpack")?),
+                        container => return Err(RdbError::Corrupt(format!("Unknown quicklist node container {}", container))),
+                    }
+                }
+                Value::List(elements)
+            },
+            object_type::HASH_LISTPACK => Value::Hash(pairs(self.read_blob(encodings::listpack, "listpack")?)?),
+            object_type::ZSET_LISTPACK => Value::SortedSet(scores(self.read_blob(encodings::listpack, "listpack")?)?),
+            object_type::SET_LISTPACK => Value::Set(self.read_blob(encodings::listpack, "listpack")?),
+            kind => {
+                let name = object_type::unsupported_name(kind).ok_or(RdbError::UnknownType(kind))?;
+                return Err(RdbError::UnsupportedType { kind, name, key: String::new() });
+            },
+        };
+        Ok(value)
+    }
+
+    fn verify_checksum(&mut self) -> Result<(), RdbError> {
+        // Checksums were added in version 5.
+        if self.version < 5 {
+            return Ok(());
+        }
+        let actual = crc64(0, &self.data[..self.position]);
+        let expected = u64::from_le_bytes(self.read_array()?);
+        // Files saved with `rdbchecksum no` carry a zero checksum.
+        if expected != 0 && expected != actual {
+            return Err(RdbError::Checksum { expected, actual });
+        }
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, RdbError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        Ok(self.read_bytes(N)?.try_into().expect("read_bytes returns exactly N bytes"))
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], RdbError> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.data.len()).ok_or(RdbError::UnexpectedEof)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// A length: 6 or 14 bits, or 32 or 64 bits big endian.
+    fn read_length(&mut self) -> Result<u64, RdbError> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            0b00 => Ok((first & 0x3f) as u64),
+            0b01 => Ok((((first & 0x3f) as u64) << 8) | self.read_u8()? as u64),
+            _ => match first {
+                0x80 => Ok(u32::from_be_bytes(self.read_array()?) as u64),
+                0x81 => Ok(u64::from_be_bytes(self.read_array()?)),
+                _ => Err(RdbError::Corrupt(format!("Unknown length encoding {:#04x}", first))),
+            },
+        }
+    }
+
+    /// A string, stored plainly, as an integer or LZF compressed.
+    fn read_string(&mut self) -> Result<Bytes, RdbError> {
+        let first = self.data.get(self.position).copied().ok_or(RdbError::UnexpectedEof)?;
+        if first >> 6 != 0b11 {
+            let length = self.read_length()?;
+            return Ok(Bytes::copy_from_slice(self.read_bytes(length as usize)?));
+        }
+        self.position += 1;
+        let integer = match first & 0x3f {
+            0 => i8::from_le_bytes(self.read_array()?) as i64,
+            1 => i16::from_le_bytes(self.read_array()?) as i64,
+            2 => i32::from_le_bytes(self.read_array()?) as i64,
+            3 => {
+                let compressed_length = self.read_length()? as usize;
+                let length = self.read_length()? as usize;
+                let compressed = self.read_bytes(compressed_length)?;
+                let string = lzf::decompress(compressed, length)
+                    .ok_or_else(|| RdbError::Corrupt("Invalid LZF compressed string".to_string()))?;
+                return Ok(Bytes::from(string));
+            },
+            encoding => return Err(RdbError::Corrupt(format!("Unknown string encoding {}", encoding))),
+        };
+        Ok(Bytes::from(integer.to_string()))
+    }
+
+    fn read_strings(&mut self) -> Result<Vec<Bytes>, RdbError> {
+        (0..self.read_length()?).map(|_| self.read_string()).collect()
+    }
+
+    /// A score of the original sorted set encoding: its text with a one
+    /// byte length, where 253 to 255 stand for nan and the infinities.
+    fn read_string_double(&mut self) -> Result<f64, RdbError> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            length => {
+                let text = self.read_bytes(length as usize)?;
+                score(text)
+            },
+        }
+    }
+
+    /// A string holding one of the compact container encodings.
+    fn read_blob(&mut self, decode: fn(&[u8]) -> Option<Vec<Bytes>>, name: &str) -> Result<Vec<Bytes>, RdbError> {
+        let blob = self.read_string()?;
+        decode(&blob).ok_or_else(|| RdbError::Corrupt(format!("Invalid {} encoding", name)))
+    }
+}
+
+/// Quicklist nodes holding a single large element, or a listpack.
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+const QUICKLIST_NODE_PACKED: u64 = 2;
+
+fn pairs(elements: Vec<Bytes>) -> Result<Vec<(Bytes, Bytes)>, RdbError> {
+    if !elements.len().is_multiple_of(2) {
+        return Err(RdbError::Corrupt("Odd number of elements in a hash or sorted set".to_string()));
+    }
+    let mut elements = elements.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(first), Some(second)) = (elements.next(), elements.next()) {
+        pairs.push((first, second));
+    }
+    Ok(pairs)
+}
+
+fn scores(elements: Vec<Bytes>) -> Result<Vec<(Bytes, f64)>, RdbError> {
+    pairs(elements)?.into_iter().map(|(member, text)| Ok((member, score(&text)?))).collect()
+}
+
+fn score(text: &[u8]) -> Result<f64, RdbError> {
+    std::str::from_utf8(text)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| RdbError::Corrupt(format!("Invalid score '{}'", String::from_utf8_lossy(text))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds RDB files for the tests.
+    struct Writer(Vec<u8>);
+
+    impl Writer {
+        fn new(version: &str) -> Self {
+            Writer(format!("REDIS{}", version).into_bytes())
+        }
+
+        fn bytes(mut self, bytes: &[u8]) -> Self {
+            self.0.extend_from_slice(bytes);
+            self
+        }
+
+        fn string(self, string: &[u8]) -> Self {
+            self.bytes(&[string.len() as u8]).bytes(string)
+        }
+
+        /// A listpack blob of short strings, as a string.
+        fn listpack(self, elements: &[&[u8]]) -> Self {
+            let mut blob = vec![0, 0, 0, 0, elements.len() as u8, 0];
+            for element in elements {
+                blob.push(0x80 | element.len() as u8);
+                blob.extend_from_slice(element);
+                blob.push(element.len() as u8 + 1);
+            }
+            blob.push(0xff);
+            let length = blob.len() as u32;
+            blob[..4].copy_from_slice(&length.to_le_bytes());
+            self.string(&blob)
+        }
+
+        fn finish(self) -> Vec<u8> {
+            let mut data = self.bytes(&[opcode::EOF]).0;
+            let checksum = crc64(0, &data);
+            data.extend_from_slice(&checksum.to_le_bytes());
+            data
+        }
+    }
+
+    fn items(data: &[u8]) -> Result<Vec<Item>, RdbError> {
+        let mut reader = RdbReader::new(data)?;
+        let mut items = Vec::new();
+        while let Some(item) = reader.next_item()? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    fn entry(key: &str, value: Value, expires_at: Option<u64>) -> Item {
+        Item::Entry { key: key.as_bytes().to_vec(), value, expires_at }
+    }
+
+    fn bytes(elements: &[&str]) -> Vec<Bytes> {
+        elements.iter().map(|element| Bytes::copy_from_slice(element.as_bytes())).collect()
+    }
+
+    #[test]
+    fn test_reads_every_supported_encoding() {
+        let data = Writer::new("0011")
+            .bytes(&[opcode::AUX]).string(b"redis-ver").string(b"7.2.0")
+            .bytes(&[opcode::SELECTDB, 0, opcode::RESIZEDB, 8, 1])
+            .bytes(&[object_type::STRING]).string(b"plain").string(b"value")
+            .bytes(&[object_type::STRING]).string(b"number").bytes(&[0xc1, 0x39, 0x30])
+            // "abcabcabc" compressed to 6 bytes.
+            .bytes(&[object_type::STRING]).string(b"lzf").bytes(&[0xc3, 6, 9, 0x02, b'a', b'b', b'c', 0x80, 0x02])
+            .bytes(&[opcode::EXPIRETIME_MS]).bytes(&1_700_000_000_000u64.to_le_bytes())
+            .bytes(&[opcode::IDLE, 5, object_type::LIST_QUICKLIST_2]).string(b"list")
+            .bytes(&[2, 2]).listpack(&[b"a", b"b"]).bytes(&[1]).string(b"c")
+            .bytes(&[object_type::SET_INTSET]).string(b"set").string(&[2, 0, 0, 0, 1, 0, 0, 0, 7, 0])
+            .bytes(&[object_type::HASH_LISTPACK]).string(b"hash").listpack(&[b"f", b"v"])
+            .bytes(&[object_type::ZSET_LISTPACK]).string(b"zset").listpack(&[b"m", b"1.5"])
+            .bytes(&[object_type::ZSET_2]).string(b"zset2").bytes(&[1]).string(b"m").bytes(&2.5f64.to_le_bytes())
+            .bytes(&[opcode::SELECTDB, 1, object_type::SET]).string(b"other").bytes(&[1]).string(b"x")
+            .finish();
+
+        assert_eq!(
+            items(&data).unwrap(),
+            vec![
+                Item::Aux { key: Bytes::from_static(b"redis-ver"), value: Bytes::from_static(b"7.2.0") },
+                Item::SelectDb(0),
+                Item::ResizeDb { keys: 8, expires: 1 },
+                entry("plain", Value::String(Bytes::from_static(b"value")), None),
+                entry("number", Value::String(Bytes::from_static(b"12345")), None),
+                entry("lzf", Value::String(Bytes::from_static(b"abcabcabc")), None),
+                entry("list", Value::List(bytes(&["a", "b", "c"])), Some(1_700_000_000_000)),
+                entry("set", Value::Set(bytes(&["7"])), None),
+                entry("hash", Value::Hash(vec![(Bytes::from_static(b"f"), Bytes::from_static(b"v"))]), None),
+                entry("zset", Value::SortedSet(vec![(Bytes::from_static(b"m"), 1.5)]), None),
+                entry("zset2", Value::SortedSet(vec![(Bytes::from_static(b"m"), 2.5)]), None),
+                Item::SelectDb(1),
+                entry("other", Value::Set(bytes(&["x"])), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_files() {
+        let data = Writer::new("0011").bytes(&[object_type::STRING]).string(b"k").string(b"v").finish();
+        let mut corrupted = data.clone();
+        corrupted[13] = b'x';
+        assert!(matches!(items(&corrupted), Err(RdbError::Checksum { .. })));
+        assert!(matches!(items(&data[..data.len() - 3]), Err(RdbError::UnexpectedEof)));
+        assert!(matches!(items(b"REDIS0013"), Err(RdbError::Version(13))));
+        assert!(matches!(items(b"RUBY00011"), Err(RdbError::Signature)));
+
+        let stream = Writer::new("0011").bytes(&[object_type::STREAM_LISTPACKS_3]).string(b"events").finish();
+        assert_eq!(
+            items(&stream).unwrap_err().to_string(),
+            "Unsupported RDB object type stream (21) for key 'events'",
+        );
+        let unknown = Writer::new("0011").bytes(&[42]).string(b"k").finish();
+        assert!(matches!(items(&unknown), Err(RdbError::UnknownType(42))));
+    }
+}
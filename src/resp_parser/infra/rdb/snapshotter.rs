@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::resp_parser::infra::memory::config_registry::ConfigRegistry;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, Storage};
+use crate::resp_parser::infra::rdb::{write_file, writer};
+
+/// Seconds to wait after a failed background save before the save
+/// policies try again.
+const RETRY_DELAY: u64 = 5;
+
+/// Snapshot bookkeeping as INFO persistence reports it.
+#[derive(Clone)]
+pub struct SaveState {
+    /// Unix time in seconds of the last successful save, or of startup.
+    pub last_save: u64,
+    pub last_attempt: u64,
+    pub last_status_ok: bool,
+    pub in_progress: bool,
+    /// A BGSAVE SCHEDULE waiting for the running save to finish.
+    pub scheduled: bool,
+    /// Duration of the last background save, -1 before the first.
+    pub last_bgsave_seconds: i64,
+    pub saves: u64,
+}
+
+pub enum BackgroundSave {
+    Started,
+    Scheduled,
+}
+
+/// Writes the keyspace to `dir/dbfilename`, in the foreground for SAVE and
+/// on a blocking thread for BGSAVE and the save policies.
+///
+/// A background save copies the keyspace under the read lock and writes
+/// the copy, so writers only wait for the copy rather than the whole dump;
+/// forking, Redis' way of getting a point-in-time view, is not safe with a
+/// multi threaded runtime.
+#[derive(Clone)]
+pub struct Snapshotter {
+    storage: Storage,
+    config_registry: ConfigRegistry,
+    state: Arc<Mutex<SaveState>>,
+}
+
+impl Snapshotter {
+    pub fn new(storage: Storage, config_registry: ConfigRegistry) -> Self {
+        let state = SaveState {
+            last_save: unix_time_ms() / 1000,
+            last_attempt: 0,
+            last_status_ok: true,
+            in_progress: false,
+            scheduled: false,
+            last_bgsave_seconds: -1,
+            saves: 0,
+        };
+        Self {
+            storage,
+            config_registry,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub fn state(&self) -> SaveState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Saves before replying, with the caller holding off other commands.
+    pub async fn save(&self) -> Result<(), String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.in_progress {
+                return Err("Background save already in progress".to_string());
+            }
+            // Keeps the save policies from starting a save meanwhile.
+            state.in_progress = true;
+        }
+        let path = self.path().await;
+        let snapshot = self.storage.read().await.snapshot();
+        let data = writer::encode(&snapshot, unix_time_ms(), false);
+        let result = write_file(&path, &data);
+        self.finish(&path, snapshot.dirty, result.is_ok()).await;
+        result.map_err(|e| format!("Failed saving the DB: {}", e))
+    }
+
+    /// Starts a save in the background. With `schedule` a save already
+    /// running is not an error: another one follows it.
+    pub async fn background_save(&self, schedule: bool) -> Result<BackgroundSave, String> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.in_progress {
+                if !schedule {
+                    return Err("Background save already in progress".to_string());
+                }
+                state.scheduled = true;
+                return Ok(BackgroundSave::Scheduled);
+            }
+            state.in_progress = true;
+            state.scheduled = false;
+        }
+
+        let path = self.path().await;
+        let snapshot = self.storage.read().await.snapshot();
+        let snapshotter = self.clone();
+        tokio::spawn(async move {
+            let started_at = Instant::now();
+            let dirty = snapshot.dirty;
+            let written_to = path.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                write_file(&written_to, &writer::encode(&snapshot, unix_time_ms(), false))
+            }).await;
+            let is_ok = matches!(result, Ok(Ok(())));
+            match result {
+                Ok(Ok(())) => println!("Background saving terminated with success"),
+                Ok(Err(e)) => println!("Background saving error: {}", e),
+                Err(e) => println!("Background saving failed: {}", e),
+            }
+            snapshotter.state.lock().unwrap().last_bgsave_seconds = started_at.elapsed().as_secs() as i64;
+            snapshotter.finish(&path, dirty, is_ok).await;
+        });
+        Ok(BackgroundSave::Started)
+    }
+
+    /// Starts a background save when one was scheduled or a save policy
+    /// is due. Called periodically.
+    pub async fn cron(&self) {
+        let state = self.state();
+        if state.in_progress {
+            return;
+        }
+        if state.scheduled {
+            let _ = self.background_save(false).await;
+            return;
+        }
+
+        let policies = self.config_registry.read(|config| config.save.clone()).await;
+        let dirty = self.storage.read().await.dirty();
+        let now = unix_time_ms() / 1000;
+        // A failing disk is not hammered with a save every cycle.
+        if !state.last_status_ok && now.saturating_sub(state.last_attempt) < RETRY_DELAY {
+            return;
+        }
+        let due = policies
+            .iter()
+            .find(|(seconds, changes)| dirty >= *changes && now.saturating_sub(state.last_save) >= *seconds);
+        if let Some((seconds, changes)) = due {
+            println!("{} changes in {} seconds. Saving...", changes, seconds);
+            let _ = self.background_save(false).await;
+        }
+    }
+
+    /// Changes not yet in a snapshot on disk.
+    pub async fn changes_since_last_save(&self) -> u64 {
+        self.storage.read().await.dirty()
+    }
+
+    async fn path(&self) -> PathBuf {
+        self.config_registry.read(|config| Path::new(&config.dir).join(&config.dbfilename)).await
+    }
+
+    async fn finish(&self, path: &Path, dirty: u64, is_ok: bool) {
+        if is_ok {
+            self.storage.write().await.saved(dirty);
+            println!("DB saved on disk: {}", path.display());
+        }
+        let now = unix_time_ms() / 1000;
+        let mut state = self.state.lock().unwrap();
+        state.in_progress = false;
+        state.last_attempt = now;
+        state.last_status_ok = is_ok;
+        if is_ok {
+            state.last_save = now;
+            state.saves += 1;
+        }
+    }
+}
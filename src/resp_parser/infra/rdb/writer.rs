@@ -0,0 +1,162 @@
+use crate::resp_parser::domain::info::REDIS_VERSION;
+use crate::resp_parser::infra::memory::storage::{Snapshot, Value};
+use crate::resp_parser::infra::rdb::crc64::crc64;
+use crate::resp_parser::infra::rdb::{object_type, opcode, SAVE_VERSION};
+
+/// Serializes a snapshot into a complete RDB file, checksum included.
+/// Values use the plain encodings, which every Redis version loads.
+/// `aof_base` marks the preamble of an append-only file.
+pub fn encode(snapshot: &Snapshot, now_ms: u64, aof_base: bool) -> Vec<u8> {
+    encode_with_aux(snapshot, now_ms, aof_base, &[])
+}
+
+/// Like `encode`, with `extra` auxiliary fields after the usual ones, e.g.
+/// the replication fields of a snapshot sent to a replica.
+pub fn encode_with_aux(snapshot: &Snapshot, now_ms: u64, aof_base: bool, extra: &[(&str, String)]) -> Vec<u8> {
+    let mut out = format!("REDIS{:04}", SAVE_VERSION).into_bytes();
+    let aux = [
+        ("redis-ver", REDIS_VERSION.to_string()),
+        ("redis-bits", usize::BITS.to_string()),
+        ("ctime", (now_ms / 1000).to_string()),
+        ("aof-base", (aof_base as u8).to_string()),
+    ];
+    for (key, value) in aux.iter().chain(extra) {
+        out.push(opcode::AUX);
+        write_string(&mut out, key.as_bytes());
+        write_string(&mut out, value.as_bytes());
+    }
+
+    for (db, entries) in snapshot.databases.iter().enumerate().filter(|(_, entries)| !entries.is_empty()) {
+        out.push(opcode::SELECTDB);
+        write_length(&mut out, db as u64);
+        out.push(opcode::RESIZEDB);
+        write_length(&mut out, entries.len() as u64);
+        write_length(&mut out, entries.iter().filter(|(_, entry)| entry.expires_at.is_some()).count() as u64);
+        for (key, entry) in entries {
+            if let Some(expires_at) = entry.expires_at {
+                out.push(opcode::EXPIRETIME_MS);
+                out.extend_from_slice(&expires_at.to_le_bytes());
+            }
+            out.push(value_type(&entry.value));
+            write_string(&mut out, key);
+            write_value(&mut out, &entry.value);
+        }
+    }
+
+    out.push(opcode::EOF);
+    let checksum = crc64(0, &out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// The object type `write_value` writes the value as.
+pub fn value_type(value: &Value) -> u8 {
+    match value {
+        Value::String(_) => object_type::STRING,
+        Value::List(_) => object_type::LIST,
+        Value::Set(_) => object_type::SET,
+        Value::SortedSet(_) => object_type::ZSET_2,
+        Value::Hash(_) => object_type::HASH,
+    }
+}
+
+pub fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::String(value) => write_string(out, value),
+        Value::List(elements) | Value::Set(elements) => {
+            write_length(out, elements.len() as u64);
+            for element in elements {
+                write_string(out, element);
+            }
+        },
+        Value::SortedSet(members) => {
+            write_length(out, members.len() as u64);
+            for (member, score) in members {
+                write_string(out, member);
+                out.extend_from_slice(&score.to_le_bytes());
+            }
+        },
+        Value::Hash(fields) => {
+            write_length(out, fields.len() as u64);
+            for (field, value) in fields {
+                write_string(out, field);
+                write_string(out, value);
+            }
+        },
+    }
+}
+
+fn write_length(out: &mut Vec<u8>, length: u64) {
+    match length {
+        0..=0x3f => out.push(length as u8),
+        0x40..=0x3fff => out.extend_from_slice(&[0x40 | (length >> 8) as u8, length as u8]),
+        0x4000..=0xffff_ffff => {
+            out.push(0x80);
+            out.extend_from_slice(&(length as u32).to_be_bytes());
+        },
+        _ => {
+            out.push(0x81);
+            out.extend_from_slice(&length.to_be_bytes());
+        },
+    }
+}
+
+/// Writes short decimal integers in their integer encoding, like Redis.
+fn write_string(out: &mut Vec<u8>, string: &[u8]) {
+    let integer = std::str::from_utf8(string)
+        .ok()
+        .filter(|text| text.len() <= 11)
+        .and_then(|text| text.parse::<i32>().ok())
+        .filter(|integer| integer.to_string().as_bytes() == string);
+    match integer {
+        Some(integer) if i8::try_from(integer).is_ok() => out.extend_from_slice(&[0xc0, integer as i8 as u8]),
+        Some(integer) if i16::try_from(integer).is_ok() => {
+            out.push(0xc1);
+            out.extend_from_slice(&(integer as i16).to_le_bytes());
+        },
+        Some(integer) => {
+            out.push(0xc2);
+            out.extend_from_slice(&integer.to_le_bytes());
+        },
+        None => {
+            write_length(out, string.len() as u64);
+            out.extend_from_slice(string);
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use super::*;
+    use crate::resp_parser::infra::memory::storage::Entry;
+    use crate::resp_parser::infra::rdb::reader::{Item, RdbReader};
+
+    #[test]
+    fn test_snapshot_round_trips_through_the_reader() {
+        let long = Bytes::from(vec![b'x'; 20_000]);
+        let values = vec![
+            (b"string".to_vec(), Entry::new(Value::String(long.clone()), Some(1_700_000_000_000))),
+            (b"number".to_vec(), Entry::new(Value::String(Bytes::from_static(b"-40000")), None)),
+            (b"padded".to_vec(), Entry::new(Value::String(Bytes::from_static(b"007")), None)),
+            (b"list".to_vec(), Entry::new(Value::List(vec![Bytes::from_static(b"a"), long]), None)),
+            (b"zset".to_vec(), Entry::new(Value::SortedSet(vec![(Bytes::from_static(b"m"), -1.5)]), None)),
+            (b"hash".to_vec(), Entry::new(Value::Hash(vec![(Bytes::from_static(b"f"), Bytes::from_static(b"1"))]), None)),
+        ];
+        let mut databases = vec![Vec::new(); 3];
+        databases[2] = values.clone();
+        let data = encode(&Snapshot { databases, dirty: 0 }, 0, false);
+
+        let mut reader = RdbReader::new(&data).unwrap();
+        let mut entries = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            match item {
+                Item::SelectDb(db) => assert_eq!(db, 2),
+                Item::Entry { key, value, expires_at } => entries.push((key, value, expires_at)),
+                _ => {},
+            }
+        }
+        let expected: Vec<_> = values.into_iter().map(|(key, entry)| (key, entry.value, entry.expires_at)).collect();
+        assert_eq!(entries, expected);
+    }
+}
@@ -0,0 +1,631 @@
+//! Replication: a master sends each replica a snapshot of its data and then
+//! streams every write it propagates, which the replica applies to stay an
+//! exact copy. Offsets count the bytes of that stream, so both sides can
+//! tell how far a replica got, and a replica that reconnects continues from
+//! the backlog of recent writes instead of loading a new snapshot.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use bytes::Bytes;
+use tokio::sync::{mpsc, watch};
+use crate::resp_parser::infra::aof::{encode_command, AofError};
+use crate::resp_parser::infra::memory::server_stats::random_hex_id;
+use crate::resp_parser::infra::memory::storage::{unix_time_ms, Storage};
+use crate::resp_parser::infra::rdb::{writer, RdbError};
+
+pub mod replica;
+
+/// Smallest backlog kept whatever `repl-backlog-size` says, as in Redis.
+const MIN_BACKLOG_SIZE: usize = 16 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplicationError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Timeout connecting to the MASTER...")]
+    Timeout,
+    #[error("Connection with master lost.")]
+    Closed,
+    #[error("{0}")]
+    Handshake(String),
+    #[error("Failed trying to load the MASTER synchronization DB from socket: {0}")]
+    Rdb(#[from] RdbError),
+    #[error("Protocol error in the replication stream from the master: {0}")]
+    Stream(#[from] AofError),
+}
+
+/// Host and port of a master.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MasterAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+/// How far the link to the master got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkState {
+    /// Connecting or going through the handshake.
+    Connecting,
+    /// Receiving the master's snapshot.
+    Transfer,
+    /// Applying the master's stream of writes.
+    Connected,
+}
+
+/// A replica attached to this server.
+struct Attached {
+    client_id: u64,
+    ip: String,
+    port: u16,
+    sender: mpsc::UnboundedSender<Bytes>,
+    ack_offset: u64,
+    /// The offset the replica fsynced to its append-only file, `None`
+    /// while it reports none.
+    aof_offset: Option<u64>,
+    last_ack: Instant,
+}
+
+/// The link to the master this server replicates.
+struct Link {
+    master: MasterAddress,
+    state: LinkState,
+    last_io: Instant,
+    /// When the link was last lost, `None` while it is up.
+    down_since: Option<Instant>,
+}
+
+/// The latest bytes of the stream, a circular buffer of at most `size`
+/// bytes ending at the current offset.
+struct Backlog {
+    data: VecDeque<u8>,
+    size: usize,
+}
+
+impl Backlog {
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        let excess = self.data.len().saturating_sub(self.size);
+        self.data.drain(..excess);
+    }
+}
+
+struct State {
+    replid: String,
+    /// The id of the history this server followed before the current one,
+    /// which is the same up to `second_offset`.
+    replid2: String,
+    offset: u64,
+    /// First offset that is not part of the `replid2` history, the
+    /// `second_repl_offset` of INFO.
+    second_offset: Option<u64>,
+    /// Database the streamed commands run in, so SELECT is only sent when
+    /// it changes.
+    selected_db: Option<usize>,
+    backlog: Option<Backlog>,
+    backlog_size: usize,
+    replicas: Vec<Attached>,
+    link: Option<Link>,
+    /// `replica-read-only`.
+    read_only: bool,
+    /// `replica-serve-stale-data`.
+    serve_stale_data: bool,
+}
+
+impl State {
+    /// Appends `data` to the stream: it counts in the offset, goes to the
+    /// backlog and to the attached replicas.
+    fn append(&mut self, data: Bytes) {
+        self.offset += data.len() as u64;
+        if let Some(backlog) = self.backlog.as_mut() {
+            backlog.push(&data);
+        }
+        // Replicas whose connection closed are dropped here.
+        self.replicas.retain(|replica| replica.sender.send(data.clone()).is_ok());
+    }
+
+    /// Starts keeping the backlog, from the current offset on.
+    fn create_backlog(&mut self) {
+        if self.backlog.is_none() {
+            self.backlog = Some(Backlog { data: VecDeque::new(), size: self.backlog_size });
+        }
+    }
+
+    /// Bytes a replica that applied everything before `offset`, counting
+    /// from 1, needs to catch up, when the backlog still has them.
+    fn backlog_from(&self, offset: u64) -> Option<Vec<u8>> {
+        let backlog = self.backlog.as_ref()?;
+        let first = self.offset + 1 - backlog.data.len() as u64;
+        if offset < first || offset > self.offset + 1 {
+            return None;
+        }
+        Some(backlog.data.range((offset - first) as usize..).copied().collect())
+    }
+
+    /// Starts a new history: the current one becomes `replid2`, shared by
+    /// anyone who followed it up to this offset.
+    fn shift_replid(&mut self, replid: String) {
+        self.replid2 = std::mem::replace(&mut self.replid, replid);
+        self.second_offset = Some(self.offset + 1);
+    }
+}
+
+/// A replica's entry in INFO replication.
+pub struct ReplicaInfo {
+    pub ip: String,
+    pub port: u16,
+    pub offset: u64,
+    pub lag_seconds: u64,
+}
+
+/// The link to the master as INFO replication reports it.
+pub struct LinkInfo {
+    pub master: MasterAddress,
+    pub state: LinkState,
+    pub last_io_seconds: u64,
+    pub down_seconds: Option<u64>,
+}
+
+/// The backlog as INFO replication reports it.
+pub struct BacklogInfo {
+    pub size: usize,
+    pub first_byte_offset: u64,
+    pub histlen: usize,
+}
+
+/// Replication bookkeeping as INFO replication reports it.
+pub struct ReplicationState {
+    pub replid: String,
+    pub replid2: String,
+    pub offset: u64,
+    pub second_offset: Option<u64>,
+    pub backlog: Option<BacklogInfo>,
+    pub replicas: Vec<ReplicaInfo>,
+    /// Set while this server is a replica.
+    pub link: Option<LinkInfo>,
+}
+
+/// How PSYNC resumes a replica.
+pub enum Resync {
+    /// The replica loads a snapshot, as an RDB file, taken at `offset`.
+    Full {
+        replid: String,
+        offset: u64,
+        rdb: Vec<u8>,
+    },
+    /// The replica already has the data; it gets the part of the backlog
+    /// it missed.
+    Partial {
+        replid: String,
+        backlog: Vec<u8>,
+    },
+}
+
+/// What PSYNC hands a replica: how it catches up and the stream of writes
+/// that follows.
+pub struct Attachment {
+    pub resync: Resync,
+    pub stream: mpsc::UnboundedReceiver<Bytes>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    /// The master to replicate, which the task running the link watches.
+    master: watch::Sender<Option<MasterAddress>>,
+    /// Notified of every REPLCONF ACK, which WAIT waits on.
+    acks: watch::Sender<()>,
+}
+
+/// The replication role of this server and the replicas attached to it.
+///
+/// Writes are fed while the keyspace lock is held, like the append-only
+/// file's, so the stream has them in the order they were applied and a
+/// snapshot taken under the same lock falls between two of them.
+#[derive(Clone)]
+pub struct Replication {
+    storage: Storage,
+    inner: Arc<Inner>,
+}
+
+impl Replication {
+    pub fn new(storage: Storage) -> Self {
+        let state = State {
+            replid: random_hex_id(),
+            replid2: "0".repeat(40),
+            offset: 0,
+            second_offset: None,
+            selected_db: None,
+            backlog: None,
+            backlog_size: MIN_BACKLOG_SIZE,
+            replicas: Vec::new(),
+            link: None,
+            read_only: true,
+            serve_stale_data: true,
+        };
+        Self {
+            storage,
+            inner: Arc::new(Inner {
+                state: Mutex::new(state),
+                master: watch::channel(None).0,
+                acks: watch::channel(()).0,
+            }),
+        }
+    }
+
+    pub fn set_backlog_size(&self, size: u64) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.backlog_size = usize::try_from(size).unwrap_or(usize::MAX).max(MIN_BACKLOG_SIZE);
+        let size = state.backlog_size;
+        if let Some(backlog) = state.backlog.as_mut() {
+            backlog.size = size;
+            backlog.trim();
+        }
+    }
+
+    pub fn state(&self) -> ReplicationState {
+        let state = self.inner.state.lock().unwrap();
+        ReplicationState {
+            replid: state.replid.clone(),
+            replid2: state.replid2.clone(),
+            offset: state.offset,
+            second_offset: state.second_offset,
+            backlog: state.backlog.as_ref().map(|backlog| BacklogInfo {
+                size: backlog.size,
+                first_byte_offset: state.offset + 1 - backlog.data.len() as u64,
+                histlen: backlog.data.len(),
+            }),
+            replicas: state.replicas
+                .iter()
+                .map(|replica| ReplicaInfo {
+                    ip: replica.ip.clone(),
+                    port: replica.port,
+                    offset: replica.ack_offset,
+                    lag_seconds: replica.last_ack.elapsed().as_secs(),
+                })
+                .collect(),
+            link: state.link.as_ref().map(|link| LinkInfo {
+                master: link.master.clone(),
+                state: link.state,
+                last_io_seconds: link.last_io.elapsed().as_secs(),
+                down_seconds: link.down_since.map(|since| since.elapsed().as_secs()),
+            }),
+        }
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.inner.state.lock().unwrap().link.is_some()
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.inner.state.lock().unwrap().read_only = read_only;
+    }
+
+    pub fn set_serve_stale_data(&self, serve_stale_data: bool) {
+        self.inner.state.lock().unwrap().serve_stale_data = serve_stale_data;
+    }
+
+    /// Whether this server's clients may not write: it is a replica and
+    /// `replica-read-only` is on.
+    pub fn is_read_only(&self) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        state.link.is_some() && state.read_only
+    }
+
+    /// Whether this server's clients may only run commands that make sense
+    /// on stale data: its link to the master is down and
+    /// `replica-serve-stale-data` is off.
+    pub fn is_stale(&self) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        !state.serve_stale_data && state.link.as_ref().is_some_and(|link| link.state != LinkState::Connected)
+    }
+
+    /// Streams a write made in `db`, or in no database in particular, to
+    /// the attached replicas and the backlog. A replica does not stream
+    /// writes itself: what it applies comes from its master.
+    pub fn feed(&self, db: Option<usize>, arguments: &[&[u8]]) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.link.is_some() || (state.replicas.is_empty() && state.backlog.is_none()) {
+            return;
+        }
+        let mut data = Vec::new();
+        if let Some(db) = db.filter(|db| state.selected_db != Some(*db)) {
+            encode_command(&mut data, &[b"SELECT", db.to_string().as_bytes()]);
+            state.selected_db = Some(db);
+        }
+        encode_command(&mut data, arguments);
+        state.append(Bytes::from(data));
+    }
+
+    /// Attaches a replica that sent PSYNC with the history it has, `replid`
+    /// up to just before `offset`. It continues from the backlog when that
+    /// history is this server's, or was up to `offset`; otherwise it gets
+    /// a snapshot, and its stream starts at the same point.
+    pub async fn attach(&self, client_id: u64, ip: String, port: u16, replid: &str, offset: i64) -> Attachment {
+        let keyspace = self.storage.read().await;
+        let (sender, stream) = mpsc::unbounded_channel();
+        let (replid, offset, stream_db) = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.replicas.retain(|replica| replica.client_id != client_id);
+            let attached = Attached {
+                client_id,
+                ip,
+                port,
+                sender,
+                ack_offset: 0,
+                aof_offset: None,
+                last_ack: Instant::now(),
+            };
+
+            let shares_history = replid == state.replid
+                || (replid == state.replid2 && state.second_offset.is_some_and(|second| offset as u64 <= second));
+            let backlog = u64::try_from(offset).ok()
+                .filter(|_| shares_history)
+                .and_then(|offset| state.backlog_from(offset));
+            if let Some(backlog) = backlog {
+                state.replicas.push(Attached { ack_offset: offset as u64 - 1, ..attached });
+                let resync = Resync::Partial { replid: state.replid.clone(), backlog };
+                return Attachment { resync, stream };
+            }
+
+            state.replicas.push(attached);
+            state.create_backlog();
+            // A master selects the database again with its next write. A
+            // replica relays the stream as it is, so the snapshot tells the
+            // replica which database the stream has selected.
+            if state.link.is_none() {
+                state.selected_db = None;
+            }
+            (state.replid.clone(), state.offset, state.selected_db)
+        };
+        let snapshot = keyspace.snapshot();
+        drop(keyspace);
+
+        let mut aux = vec![("repl-id", replid.clone()), ("repl-offset", offset.to_string())];
+        if let Some(db) = stream_db {
+            aux.push(("repl-stream-db", db.to_string()));
+        }
+        let rdb = tokio::task::spawn_blocking(move || writer::encode_with_aux(&snapshot, unix_time_ms(), false, &aux))
+            .await
+            .expect("encoding the snapshot does not panic");
+        Attachment { resync: Resync::Full { replid, offset, rdb }, stream }
+    }
+
+    pub fn detach(&self, client_id: u64) {
+        self.inner.state.lock().unwrap().replicas.retain(|replica| replica.client_id != client_id);
+    }
+
+    /// Records the offsets a replica reported with REPLCONF ACK: the one
+    /// it applied and, when its append-only file is on, the one it fsynced.
+    pub fn acknowledge(&self, client_id: u64, offset: u64, aof_offset: Option<u64>) {
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some(replica) = state.replicas.iter_mut().find(|replica| replica.client_id == client_id) {
+            replica.ack_offset = offset;
+            replica.aof_offset = aof_offset;
+            replica.last_ack = Instant::now();
+        }
+        drop(state);
+        self.inner.acks.send_replace(());
+    }
+
+    /// How many replicas acknowledged `offset`, and how many fsynced it.
+    pub fn acknowledged(&self, offset: u64) -> (usize, usize) {
+        let state = self.inner.state.lock().unwrap();
+        let applied = state.replicas.iter().filter(|replica| replica.ack_offset >= offset).count();
+        let fsynced = state.replicas.iter().filter(|replica| replica.aof_offset.is_some_and(|aof| aof >= offset)).count();
+        (applied, fsynced)
+    }
+
+    /// Asks the replicas to acknowledge what they applied right away,
+    /// rather than on their next periodic REPLCONF ACK.
+    pub fn request_acks(&self) {
+        if self.inner.state.lock().unwrap().replicas.is_empty() {
+            return;
+        }
+        self.feed(None, &[b"REPLCONF", b"GETACK", b"*"]);
+    }
+
+    pub fn watch_acks(&self) -> watch::Receiver<()> {
+        self.inner.acks.subscribe()
+    }
+
+    /// Replicates `master` from now on, or with `None` makes this server a
+    /// master again. Returns false when it already replicates `master`.
+    pub fn set_master(&self, master: Option<MasterAddress>) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.link.as_ref().map(|link| &link.master) == master.as_ref() {
+            return false;
+        }
+        match &master {
+            Some(master) => {
+                state.link = Some(Link {
+                    master: master.clone(),
+                    state: LinkState::Connecting,
+                    last_io: Instant::now(),
+                    down_since: Some(Instant::now()),
+                });
+                // The replicas reconnect, and continue where they were if
+                // the new master shares this server's history.
+                state.replicas.clear();
+            },
+            None => {
+                state.link = None;
+                // Writes accepted from now on start a history of their own.
+                // Replicas of the former master share everything before
+                // them, so they can continue from this server's backlog.
+                state.shift_replid(random_hex_id());
+                state.selected_db = None;
+            },
+        }
+        drop(state);
+        self.inner.master.send_replace(master);
+        true
+    }
+
+    /// The configured master, which changes with REPLICAOF.
+    pub fn watch_master(&self) -> watch::Receiver<Option<MasterAddress>> {
+        self.inner.master.subscribe()
+    }
+
+    fn update_link(&self, f: impl FnOnce(&mut Link)) {
+        if let Some(link) = self.inner.state.lock().unwrap().link.as_mut() {
+            f(link);
+        }
+    }
+
+    pub fn set_link_state(&self, link_state: LinkState) {
+        self.update_link(|link| {
+            link.state = link_state;
+            link.last_io = Instant::now();
+            match link_state {
+                LinkState::Connected => link.down_since = None,
+                _ => {
+                    link.down_since.get_or_insert_with(Instant::now);
+                },
+            }
+        });
+    }
+
+    /// The history to ask the master to continue: the replication id and
+    /// the next offset, or `None` when there is nothing to continue.
+    pub fn resume_point(&self) -> Option<(String, u64)> {
+        let state = self.inner.state.lock().unwrap();
+        (state.offset > 0).then(|| (state.replid.clone(), state.offset + 1))
+    }
+
+    /// Adopts the history of the master after loading its snapshot, whose
+    /// stream continues in `db`. The backlog starts over at `offset`, so
+    /// replicas of this server can continue from it, even after a
+    /// promotion.
+    pub fn synchronized(&self, replid: String, offset: u64, db: usize) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.replid = replid;
+        state.selected_db = Some(db);
+        state.replid2 = "0".repeat(40);
+        state.second_offset = None;
+        state.offset = offset;
+        state.backlog = None;
+        state.create_backlog();
+        drop(state);
+        self.set_link_state(LinkState::Connected);
+    }
+
+    /// Continues the stream after the master accepted a partial
+    /// resynchronization. A master that was promoted since announces its
+    /// new replication id, which is adopted like the master did.
+    pub fn continued(&self, replid: Option<String>) {
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some(replid) = replid.filter(|replid| *replid != state.replid) {
+            state.shift_replid(replid);
+        }
+        state.create_backlog();
+        drop(state);
+        self.set_link_state(LinkState::Connected);
+    }
+
+    /// Counts `data`, applied from the master's stream, in the offset and
+    /// passes it on unchanged, to the backlog and to the replicas of this
+    /// replica, so all of them share the master's offsets. `db` is the
+    /// database the stream has selected after it.
+    pub fn processed(&self, data: &[u8], db: usize) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.append(Bytes::copy_from_slice(data));
+        state.selected_db = Some(db);
+        if let Some(link) = state.link.as_mut() {
+            link.last_io = Instant::now();
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.inner.state.lock().unwrap().offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_parser::infra::memory::storage::{Entry, Value};
+
+    fn full(attachment: &Attachment) -> (&str, u64, &[u8]) {
+        match &attachment.resync {
+            Resync::Full { replid, offset, rdb } => (replid, *offset, rdb),
+            Resync::Partial { .. } => panic!("Expected a full resync"),
+        }
+    }
+
+    fn partial(attachment: &Attachment) -> (&str, &[u8]) {
+        match &attachment.resync {
+            Resync::Partial { replid, backlog } => (replid, backlog),
+            Resync::Full { .. } => panic!("Expected a partial resync"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streams_writes_after_the_snapshot() {
+        let storage = Storage::default();
+        storage.write().await.db_mut(0).insert(b"a".to_vec(), Entry::new(Value::String(Bytes::from_static(b"1")), None));
+        let replication = Replication::new(storage.clone());
+        replication.feed(Some(0), &[b"SET", b"ignored", b"1"]);
+        assert_eq!(replication.offset(), 0);
+
+        let mut attachment = replication.attach(7, "127.0.0.1".to_string(), 6380, "?", -1).await;
+        let (replid, offset, rdb) = full(&attachment);
+        assert_eq!((replid.len(), offset), (40, 0));
+        assert!(rdb.starts_with(b"REDIS"));
+
+        replication.feed(Some(2), &[b"SET", b"b", b"2"]);
+        replication.feed(Some(2), &[b"DEL", b"b"]);
+        let mut expected = Vec::new();
+        encode_command(&mut expected, &[b"SELECT", b"2"]);
+        encode_command(&mut expected, &[b"SET", b"b", b"2"]);
+        let first = attachment.stream.try_recv().unwrap();
+        assert_eq!(first.as_ref(), expected.as_slice());
+        let second = attachment.stream.try_recv().unwrap();
+        assert_eq!(replication.offset(), (first.len() + second.len()) as u64);
+
+        replication.acknowledge(7, 42, None);
+        assert_eq!(replication.state().replicas[0].offset, 42);
+        assert_eq!((replication.acknowledged(42), replication.acknowledged(43)), ((1, 0), (0, 0)));
+        replication.acknowledge(7, 42, Some(40));
+        assert_eq!((replication.acknowledged(40), replication.acknowledged(41)), ((1, 1), (1, 0)));
+
+        // Becoming a replica drops the replicas, which closes their stream.
+        assert!(replication.set_master(Some(MasterAddress { host: "127.0.0.1".to_string(), port: 6379 })));
+        assert!(!replication.set_master(Some(MasterAddress { host: "127.0.0.1".to_string(), port: 6379 })));
+        assert!(replication.state().replicas.is_empty());
+        assert!(attachment.stream.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_continues_from_the_backlog_across_a_promotion() {
+        let replication = Replication::new(Storage::default());
+        let attachment = replication.attach(1, String::new(), 0, "?", -1).await;
+        let (replid, _, _) = full(&attachment);
+        let replid = replid.to_string();
+        replication.feed(Some(0), &[b"SET", b"a", b"1"]);
+        let applied = replication.offset();
+        replication.feed(Some(0), &[b"SET", b"b", b"2"]);
+        let mut missed = Vec::new();
+        encode_command(&mut missed, &[b"SET", b"b", b"2"]);
+
+        let attachment = replication.attach(1, String::new(), 0, &replid, applied as i64 + 1).await;
+        assert_eq!(partial(&attachment), (replid.as_str(), missed.as_slice()));
+        // Offsets ahead of the master, or another history, need a snapshot.
+        full(&replication.attach(1, String::new(), 0, &replid, replication.offset() as i64 + 2).await);
+        full(&replication.attach(1, String::new(), 0, &"f".repeat(40), 1).await);
+
+        // A promoted server keeps serving replicas of its former master,
+        // under a new id, until they pass the point it was promoted at.
+        replication.set_master(Some(MasterAddress { host: "127.0.0.1".to_string(), port: 6379 }));
+        replication.set_master(None);
+        let state = replication.state();
+        assert_eq!((state.replid2.as_str(), state.second_offset), (replid.as_str(), Some(state.offset + 1)));
+        let attachment = replication.attach(2, String::new(), 0, &replid, state.offset as i64 + 1).await;
+        assert_eq!(partial(&attachment), (state.replid.as_str(), &[][..]));
+        full(&replication.attach(2, String::new(), 0, &replid, state.offset as i64 + 2).await);
+    }
+}
@@ -0,0 +1,397 @@
+//! The replica side of a link: the handshake with the master, loading its
+//! snapshot and then applying its stream of writes.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time;
+use crate::resp_parser::domain::command_handler::{CommandHandler, CommandHandlerResultStatus};
+use crate::resp_parser::domain::resp_command::RespCommand;
+use crate::resp_parser::domain::session::Session;
+use crate::resp_parser::domain::stream_chunking_service::RawCommand;
+use crate::resp_parser::infra::aof::appender::Appender;
+use crate::resp_parser::infra::aof::reader::AofReader;
+use crate::resp_parser::infra::aof::{encode_command, AofError};
+use crate::resp_parser::infra::memory::storage::{Storage, DATABASES};
+use crate::resp_parser::infra::memory::tracking_table::TrackingTable;
+use crate::resp_parser::infra::rdb;
+use crate::resp_parser::infra::replication::{LinkState, MasterAddress, Replication, ReplicationError};
+
+/// Silence after which the master is taken to be gone. Masters ping their
+/// replicas well within it.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the master is told the offset applied, with REPLCONF ACK.
+const ACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The connection to the master with what was read and not used yet.
+struct Connection {
+    stream: TcpStream,
+    buffer: BytesMut,
+}
+
+impl Connection {
+    async fn connect(master: &MasterAddress) -> Result<Self, ReplicationError> {
+        let stream = time::timeout(TIMEOUT, TcpStream::connect((master.host.as_str(), master.port)))
+            .await
+            .map_err(|_| ReplicationError::Timeout)??;
+        Ok(Self {
+            stream,
+            buffer: BytesMut::new(),
+        })
+    }
+
+    async fn send(&mut self, arguments: &[&[u8]]) -> Result<(), ReplicationError> {
+        let mut data = Vec::new();
+        encode_command(&mut data, arguments);
+        self.stream.write_all(&data).await?;
+        Ok(())
+    }
+
+    /// Reads more of what the master sent into the buffer.
+    async fn fill(&mut self) -> Result<(), ReplicationError> {
+        time::timeout(TIMEOUT, self.read()).await.map_err(|_| ReplicationError::Timeout)?
+    }
+
+    /// Like `fill`, without a timeout.
+    async fn read(&mut self) -> Result<(), ReplicationError> {
+        match self.stream.read_buf(&mut self.buffer).await {
+            Ok(0) => Err(ReplicationError::Closed),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The next line, without its terminator.
+    async fn read_line(&mut self) -> Result<String, ReplicationError> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line = self.buffer.split_to(end + 1);
+                return Ok(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Sends a handshake command and reads its one-line reply.
+    async fn request(&mut self, arguments: &[&[u8]]) -> Result<String, ReplicationError> {
+        self.send(arguments).await?;
+        self.read_line().await
+    }
+
+    /// The master's snapshot: a bulk string without the trailing CRLF or,
+    /// when the master writes it straight to the socket, data ended by the
+    /// 40 byte mark announced as `$EOF:<mark>`.
+    async fn read_rdb(&mut self) -> Result<Vec<u8>, ReplicationError> {
+        let header = loop {
+            // Newlines keep the connection alive while the master prepares
+            // the snapshot.
+            let line = self.read_line().await?;
+            if !line.is_empty() {
+                break line;
+            }
+        };
+        if let Some(mark) = header.strip_prefix("$EOF:") {
+            let mark = mark.as_bytes().to_vec();
+            if mark.len() != 40 {
+                return Err(ReplicationError::Handshake(format!("Bad EOF mark from MASTER: '{}'", header)));
+            }
+            let mut searched = 0;
+            loop {
+                if let Some(position) = self.buffer[searched..].windows(mark.len()).position(|window| window == mark) {
+                    let rdb = self.buffer.split_to(searched + position).to_vec();
+                    self.buffer.advance(mark.len());
+                    return Ok(rdb);
+                }
+                searched = self.buffer.len().saturating_sub(mark.len() - 1);
+                self.fill().await?;
+            }
+        }
+        let length = header
+            .strip_prefix('$')
+            .and_then(|length| length.parse::<usize>().ok())
+            .ok_or_else(|| ReplicationError::Handshake(format!(
+                "Bad protocol from MASTER, the first byte is not '$' (we received '{}')",
+                header,
+            )))?;
+        while self.buffer.len() < length {
+            self.fill().await?;
+        }
+        Ok(self.buffer.split_to(length).to_vec())
+    }
+}
+
+/// What a replica needs to replicate its master. The session the stream
+/// runs in outlives each connection, so a partial resynchronization
+/// continues in the database the stream had selected.
+pub struct ReplicaLink {
+    replication: Replication,
+    storage: Storage,
+    handler: CommandHandler,
+    tracking_table: TrackingTable,
+    appender: Appender,
+    session: Session,
+    /// Positions in the append-only file paired with the offset of the
+    /// stream written up to them, until they are fsynced.
+    fsync_marks: VecDeque<(u64, u64)>,
+    /// The offset of the stream known to be fsynced, reported as FACK.
+    aof_offset: u64,
+}
+
+impl ReplicaLink {
+    pub fn new(
+        replication: Replication,
+        storage: Storage,
+        handler: CommandHandler,
+        tracking_table: TrackingTable,
+        appender: Appender,
+    ) -> Self {
+        let (subscriber, _) = tokio::sync::mpsc::unbounded_channel();
+        Self {
+            replication,
+            storage,
+            handler,
+            tracking_table,
+            appender,
+            session: Session::new_master_link(subscriber),
+            fsync_marks: VecDeque::new(),
+            aof_offset: 0,
+        }
+    }
+
+    /// Replicates `master` until the link fails: the handshake, a partial
+    /// resynchronization when the master still has what this server
+    /// missed or a full one otherwise, and then the stream of writes.
+    /// `listening_port` is the port this server accepts clients on, which
+    /// the master reports.
+    pub async fn run(&mut self, master: &MasterAddress, listening_port: u16) -> Result<(), ReplicationError> {
+        self.replication.set_link_state(LinkState::Connecting);
+        println!("Connecting to MASTER {}:{}", master.host, master.port);
+        let mut connection = Connection::connect(master).await?;
+        println!("MASTER <-> REPLICA sync started");
+
+        let pong = connection.request(&[b"PING"]).await?;
+        if pong.starts_with('-') {
+            return Err(ReplicationError::Handshake(format!("Error reply to PING from master: '{}'", pong)));
+        }
+        let port = listening_port.to_string();
+        let options: [&[&[u8]]; 2] = [
+            &[b"REPLCONF", b"listening-port", port.as_bytes()],
+            &[b"REPLCONF", b"capa", b"eof", b"capa", b"psync2"],
+        ];
+        for arguments in options {
+            let reply = connection.request(arguments).await?;
+            if reply.starts_with('-') {
+                println!("(Non critical) Master does not understand REPLCONF {}: {}", String::from_utf8_lossy(arguments[1]), reply);
+            }
+        }
+
+        let reply = match self.replication.resume_point() {
+            Some((replid, offset)) => {
+                println!("Trying a partial resynchronization (request {}:{}).", replid, offset);
+                connection.request(&[b"PSYNC", replid.as_bytes(), offset.to_string().as_bytes()]).await?
+            },
+            None => {
+                println!("Partial resynchronization not possible (no cached master)");
+                connection.request(&[b"PSYNC", b"?", b"-1"]).await?
+            },
+        };
+        if let Some(rest) = reply.strip_prefix("+CONTINUE") {
+            let replid = Some(rest.trim()).filter(|replid| !replid.is_empty()).map(str::to_string);
+            self.replication.continued(replid);
+            println!("Successful partial resynchronization with master.");
+        } else if let Some(rest) = reply.strip_prefix("+FULLRESYNC ") {
+            let (replid, offset) = rest
+                .split_once(' ')
+                .and_then(|(replid, offset)| Some((replid.to_string(), offset.parse::<u64>().ok()?)))
+                .ok_or_else(|| ReplicationError::Handshake(format!("Unexpected reply to PSYNC from master: {}", reply)))?;
+            println!("Full resync from master: {}:{}", replid, offset);
+
+            self.replication.set_link_state(LinkState::Transfer);
+            let rdb = connection.read_rdb().await?;
+            println!("MASTER <-> REPLICA sync: receiving {} bytes from master", rdb.len());
+            let stream_db = load(&rdb, &self.storage, &self.tracking_table, &self.appender).await?;
+            // A master selects the database with its next write, a replica
+            // relaying its own master's stream tells which one it has.
+            self.session.select(stream_db.unwrap_or(0));
+            self.replication.synchronized(replid, offset, self.session.db());
+            self.fsync_marks.clear();
+            self.mark_written();
+            println!("MASTER <-> REPLICA sync: Finished with success");
+        } else if reply.starts_with("-NOMASTERLINK") || reply.starts_with("-LOADING") {
+            return Err(ReplicationError::Handshake(format!(
+                "Master is currently unable to PSYNC but should be in the future: {}",
+                reply,
+            )));
+        } else {
+            return Err(ReplicationError::Handshake(format!("Unexpected reply to PSYNC from master: {}", reply)));
+        }
+
+        let result = self.apply_stream(&mut connection).await;
+        // A transaction cut short is sent again from its MULTI.
+        self.handler.close_session(&mut self.session).await;
+        result
+    }
+
+    /// Applies the master's stream of writes until the link fails. REPLCONF
+    /// GETACK is answered with the offset applied before it, and the master
+    /// is sent the offset every second anyway. The bytes of a transaction
+    /// only count once its EXEC ran, so a replica that loses the link in the
+    /// middle asks for all of it again.
+    async fn apply_stream(&mut self, connection: &mut Connection) -> Result<(), ReplicationError> {
+        let mut pending = Vec::new();
+        let mut acks = time::interval(ACK_INTERVAL);
+        let mut last_read = Instant::now();
+        loop {
+            let mut reader = AofReader::new(&connection.buffer, 0);
+            let mut commands = Vec::new();
+            loop {
+                let start = reader.offset();
+                match reader.next_command() {
+                    Ok(Some(arguments)) => commands.push((start..reader.offset(), arguments)),
+                    Ok(None) | Err(AofError::Truncated(_)) => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let consumed = reader.offset();
+            let data = connection.buffer.split_to(consumed).freeze();
+
+            for (span, arguments) in commands {
+                if is_getack(&arguments) {
+                    self.send_ack(connection).await?;
+                    self.count(&data[span], &mut pending);
+                } else {
+                    self.apply(arguments, &data[span], &mut pending).await;
+                }
+            }
+            self.appender.flush();
+            self.mark_written();
+
+            loop {
+                tokio::select! {
+                    result = connection.read() => {
+                        result?;
+                        last_read = Instant::now();
+                        break;
+                    },
+                    _ = acks.tick() => {
+                        if last_read.elapsed() >= TIMEOUT {
+                            return Err(ReplicationError::Timeout);
+                        }
+                        self.send_ack(connection).await?;
+                    },
+                }
+            }
+        }
+    }
+
+    /// Runs a command of the stream like a client's, holding the gate the
+    /// same way, and counts its bytes before letting go of it, so a replica
+    /// of this server attaching meanwhile gets a snapshot that matches the
+    /// offset. Errors are only logged: the master already applied the
+    /// command.
+    async fn apply(&mut self, arguments: Vec<Bytes>, data: &[u8], pending: &mut Vec<u8>) {
+        let command = match RespCommand::parse(RawCommand::new(arguments)) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("== CRITICAL == This replica failed to parse a command from its master: {}", e);
+                self.count(data, pending);
+                return;
+            },
+        };
+        let result = if matches!(command, RespCommand::Exec) {
+            let _gate = self.storage.exclusive_gate().await;
+            let result = self.handler.handle_command(command, &mut self.session).await;
+            self.count(data, pending);
+            result
+        } else {
+            let _gate = self.storage.shared_gate().await;
+            let result = self.handler.handle_command(command, &mut self.session).await;
+            self.count(data, pending);
+            result
+        };
+        if let CommandHandlerResultStatus::Error(error) = result.get_status() {
+            println!("== CRITICAL == This replica failed to apply a command from its master: '{}'", error);
+        }
+    }
+
+    /// Counts the bytes of a command applied, unless it is part of a
+    /// transaction still open.
+    fn count(&self, data: &[u8], pending: &mut Vec<u8>) {
+        pending.extend_from_slice(data);
+        if !self.session.is_in_transaction() {
+            self.replication.processed(pending, self.session.db());
+            pending.clear();
+        }
+    }
+
+    /// Tells the master the offset applied and, while the append-only file
+    /// is on, the offset fsynced to it.
+    async fn send_ack(&mut self, connection: &mut Connection) -> Result<(), ReplicationError> {
+        let offset = self.replication.offset().to_string();
+        if !self.appender.state().enabled {
+            return connection.send(&[b"REPLCONF", b"ACK", offset.as_bytes()]).await;
+        }
+        let fsynced = *self.appender.watch_fsynced().borrow();
+        while let Some((_, offset)) = self.fsync_marks.front().copied().filter(|(written, _)| *written <= fsynced) {
+            self.aof_offset = offset;
+            self.fsync_marks.pop_front();
+        }
+        let aof_offset = self.aof_offset.to_string();
+        connection.send(&[b"REPLCONF", b"ACK", offset.as_bytes(), b"FACK", aof_offset.as_bytes()]).await
+    }
+
+    /// Pairs the end of the append-only file with the offset applied, once
+    /// the stream up to it is written.
+    fn mark_written(&mut self) {
+        if !self.appender.state().enabled {
+            return;
+        }
+        let mark = (self.appender.written(), self.replication.offset());
+        if self.fsync_marks.back() != Some(&mark) {
+            self.fsync_marks.push_back(mark);
+        }
+    }
+}
+
+/// Replaces all data with the master's snapshot, returning the database
+/// its stream has selected when it says. The append-only file is
+/// restarted, so its new base is the loaded data.
+async fn load(
+    rdb: &[u8],
+    storage: &Storage,
+    tracking_table: &TrackingTable,
+    appender: &Appender,
+) -> Result<Option<usize>, ReplicationError> {
+    let _gate = storage.exclusive_gate().await;
+    let appendonly = appender.state().enabled;
+    if appendonly {
+        appender.stop();
+    }
+    let mut keyspace = storage.write().await;
+    for db in 0..DATABASES {
+        keyspace.touch_all(db);
+        keyspace.db_mut(db).clear();
+    }
+    drop(keyspace);
+    tracking_table.invalidate_all().await;
+
+    let loaded = rdb::load_data(rdb, storage).await;
+    if appendonly {
+        if let Err(e) = appender.start().await {
+            eprintln!("Failed enabling the AOF after successful master synchronization: {}", e);
+        }
+    }
+    let (loaded, _) = loaded?;
+    println!("Done loading RDB, keys loaded: {}, keys expired: {}.", loaded.keys, loaded.expired);
+    Ok(loaded.stream_db)
+}
+
+fn is_getack(arguments: &[Bytes]) -> bool {
+    matches!(
+        arguments,
+        [command, option, ..] if command.eq_ignore_ascii_case(b"REPLCONF") && option.eq_ignore_ascii_case(b"GETACK")
+    )
+}
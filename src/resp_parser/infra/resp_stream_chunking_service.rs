@@ -0,0 +1,413 @@
+use std::ops::Range;
+use bytes::{Bytes, BytesMut};
+use crate::resp_parser::domain::stream_chunking_service::{RawCommand, StreamChunkingService, StreamChunkingServiceError};
+
+/// Largest inline request accepted before a newline shows up.
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
+/// Same limits Redis applies to multibulk requests.
+const MAX_MULTIBULK_LENGTH: i64 = 1024 * 1024;
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+/// Deepest nesting of arrays accepted, so a request cannot grow the parse
+/// state without bound.
+const MAX_NESTING_DEPTH: usize = 128;
+
+/// Splits the byte stream of a connection into requests.
+///
+/// Requests are either RESP arrays or inline commands such as `PING\r\n`
+/// typed from telnet. Array elements may be any scalar type or nested arrays,
+/// which are flattened into the argument list; bulk strings are sliced out of
+/// the read buffer without copying. An array that is not complete yet is
+/// kept parsed up to its last complete element, so each read only parses
+/// the bytes that arrived with it.
+pub struct RespStreamChunkingService {
+    buffer: BytesMut,
+    partial: Option<PartialRequest>,
+}
+
+/// The parsed part of a request array at the start of the buffer.
+#[derive(Default)]
+struct PartialRequest {
+    /// Position right after the last complete element.
+    pos: usize,
+    /// Elements still expected by each open array, innermost last.
+    remaining: Vec<usize>,
+    /// Ranges of the scalar elements parsed so far.
+    ranges: Vec<Range<usize>>,
+}
+
+/// One element of a request array, without the elements of an array.
+enum Element {
+    Array(usize),
+    Scalar(Range<usize>),
+}
+
+impl StreamChunkingService for RespStreamChunkingService {
+    fn new() -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            partial: None,
+        }
+    }
+
+    fn next(&mut self, buffer: &[u8]) -> Result<Vec<RawCommand>, StreamChunkingServiceError> {
+        self.buffer.extend_from_slice(buffer);
+        let mut commands = Vec::new();
+
+        loop {
+            match self.parse_next() {
+                Ok(Some(Some(command))) => commands.push(command),
+                // Null and empty arrays are consumed without producing a command.
+                Ok(Some(None)) => continue,
+                Ok(None) => break,
+                Err(e) => {
+                    if commands.is_empty() {
+                        return Err(e);
+                    }
+                    break;
+                }
+            }
+        }
+
+        if commands.is_empty() {
+            Err(StreamChunkingServiceError::IncompleteCommand)
+        } else {
+            Ok(commands)
+        }
+    }
+}
+
+impl RespStreamChunkingService {
+    /// `Ok(None)` when more bytes are needed, `Ok(Some(None))` when a request
+    /// without arguments was consumed.
+    fn parse_next(&mut self) -> Result<Option<Option<RawCommand>>, StreamChunkingServiceError> {
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+        if self.partial.is_none() && self.buffer[0] != b'*' {
+            return self.parse_inline();
+        }
+
+        let partial = self.partial.get_or_insert_with(PartialRequest::default);
+        if !parse_elements(&self.buffer, partial)? {
+            return Ok(None);
+        }
+        let PartialRequest { pos, ranges, .. } = self.partial.take().unwrap_or_default();
+        let request = self.buffer.split_to(pos).freeze();
+        if ranges.is_empty() {
+            return Ok(Some(None));
+        }
+        let arguments = ranges.into_iter().map(|range| request.slice(range)).collect();
+        Ok(Some(Some(RawCommand::new(arguments))))
+    }
+
+    fn parse_inline(&mut self) -> Result<Option<Option<RawCommand>>, StreamChunkingServiceError> {
+        let newline = match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(pos) => pos,
+            None if self.buffer.len() > MAX_INLINE_LENGTH => return Err(invalid_format("too big inline request")),
+            None => return Ok(None),
+        };
+        let line = self.buffer.split_to(newline + 1);
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        let arguments = split_args(line).ok_or_else(|| invalid_format("unbalanced quotes in request"))?;
+        if arguments.is_empty() {
+            return Ok(Some(None));
+        }
+        Ok(Some(Some(RawCommand::new(arguments))))
+    }
+}
+
+/// Continues parsing the request array at the start of `buffer` from where
+/// `partial` stopped. Returns whether the request is complete.
+fn parse_elements(buffer: &[u8], partial: &mut PartialRequest) -> Result<bool, StreamChunkingServiceError> {
+    loop {
+        if partial.pos > 0 && partial.remaining.is_empty() {
+            return Ok(true);
+        }
+        let is_array = buffer.get(partial.pos) == Some(&b'*');
+        if is_array && partial.remaining.len() == MAX_NESTING_DEPTH {
+            return Err(invalid_format("too many nested aggregates"));
+        }
+        let (element, next) = match parse_element(buffer, partial.pos)? {
+            Some(element) => element,
+            None => return Ok(false),
+        };
+        if let Some(remaining) = partial.remaining.last_mut() {
+            *remaining -= 1;
+        }
+        match element {
+            Element::Array(0) => {},
+            Element::Array(count) => partial.remaining.push(count),
+            Element::Scalar(range) => partial.ranges.push(range),
+        }
+        partial.pos = next;
+        while partial.remaining.last() == Some(&0) {
+            partial.remaining.pop();
+        }
+    }
+}
+
+/// Parses the element starting at `pos`, returning it with the position
+/// right after it, or `None` until the buffer holds all of it. The
+/// elements of an array follow it.
+fn parse_element(buffer: &[u8], pos: usize) -> Result<Option<(Element, usize)>, StreamChunkingServiceError> {
+    let (line, next) = match read_line(buffer, pos) {
+        Some(line) => line,
+        None if buffer.len() - pos > MAX_INLINE_LENGTH => return Err(invalid_format("too big count string")),
+        None => return Ok(None),
+    };
+    let prefix = buffer[pos];
+    let content = line.start + 1..line.end;
+
+    match prefix {
+        b'*' => {
+            let count = parse_length(&buffer[content], MAX_MULTIBULK_LENGTH)
+                .ok_or_else(|| invalid_format("invalid multibulk length"))?;
+            // A null array has no elements.
+            Ok(Some((Element::Array(count.unwrap_or(0)), next)))
+        },
+        b'$' => {
+            let length = parse_length(&buffer[content], MAX_BULK_LENGTH)
+                .ok_or_else(|| invalid_format("invalid bulk length"))?;
+            let length = match length {
+                Some(length) => length,
+                // A null bulk string stands for an empty argument.
+                None => return Ok(Some((Element::Scalar(next..next), next))),
+            };
+            if buffer.len() < next + length + 2 {
+                return Ok(None);
+            }
+            if &buffer[next + length..next + length + 2] != b"\r\n" {
+                return Err(invalid_format("invalid bulk length"));
+            }
+            Ok(Some((Element::Scalar(next..next + length), next + length + 2)))
+        },
+        b'+' | b':' => Ok(Some((Element::Scalar(content), next))),
+        _ => Err(invalid_format(format!("expected '$', got '{}'", prefix as char))),
+    }
+}
+
+/// Range of the line starting at `pos` without its CRLF, plus the position
+/// of the next line.
+fn read_line(buffer: &[u8], pos: usize) -> Option<(Range<usize>, usize)> {
+    buffer[pos..]
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .map(|len| (pos..pos + len, pos + len + 2))
+}
+
+/// Parses an array or bulk length, where `Some(None)` means null.
+fn parse_length(digits: &[u8], max: i64) -> Option<Option<usize>> {
+    let length = std::str::from_utf8(digits).ok()?.parse::<i64>().ok()?;
+    match length {
+        -1 => Some(None),
+        0.. if length <= max => Some(Some(length as usize)),
+        _ => None,
+    }
+}
+
+fn invalid_format(reason: impl Into<String>) -> StreamChunkingServiceError {
+    StreamChunkingServiceError::InvalidFormat(reason.into())
+}
+
+/// Splits an inline request on whitespace, honouring double quotes with
+/// escapes and single quotes the same way redis-cli does. `None` on
+/// unbalanced quotes.
+pub fn split_args(line: &[u8]) -> Option<Vec<Bytes>> {
+    let mut arguments = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        while pos < line.len() && line[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == line.len() {
+            return Some(arguments);
+        }
+
+        let mut argument = Vec::new();
+        let quote = match line[pos] {
+            b'"' | b'\'' => {
+                pos += 1;
+                Some(line[pos - 1])
+            },
+            _ => None,
+        };
+        loop {
+            let Some(&c) = line.get(pos) else {
+                if quote.is_some() {
+                    return None;
+                }
+                break;
+            };
+            match quote {
+                None if c.is_ascii_whitespace() => break,
+                None => argument.push(c),
+                Some(b'"') if c == b'\\' && line.get(pos + 1).is_some() => {
+                    pos += 1;
+                    let escaped = line[pos];
+                    let hex = line.get(pos + 1..pos + 3)
+                        .and_then(|hex| std::str::from_utf8(hex).ok())
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                    match (escaped, hex) {
+                        (b'x', Some(byte)) => {
+                            argument.push(byte);
+                            pos += 2;
+                        },
+                        (b'n', _) => argument.push(b'\n'),
+                        (b'r', _) => argument.push(b'\r'),
+                        (b't', _) => argument.push(b'\t'),
+                        (b'b', _) => argument.push(0x08),
+                        (b'a', _) => argument.push(0x07),
+                        _ => argument.push(escaped),
+                    }
+                },
+                Some(b'\'') if c == b'\\' && line.get(pos + 1) == Some(&b'\'') => {
+                    pos += 1;
+                    argument.push(b'\'');
+                },
+                Some(quote) if c == quote => {
+                    // A closing quote must be followed by a space or the end.
+                    if line.get(pos + 1).is_some_and(|next| !next.is_ascii_whitespace()) {
+                        return None;
+                    }
+                    pos += 1;
+                    break;
+                },
+                Some(_) => argument.push(c),
+            }
+            pos += 1;
+        }
+        arguments.push(Bytes::from(argument));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(command: &RawCommand) -> Vec<Bytes> {
+        command.clone().into_arguments()
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 1);
+        assert_eq!(arguments(&command[0]), vec![b"GET".as_slice(), b"key"]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_invalid_format() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*2\r\n$3\r\nGET\r\n$4\r\nkey\r\n";
+        let result = service.next(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_empty_buffer() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"";
+        let result = service.next(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_half_command_and_then_complete() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n*3\r\n$3\r\nSET\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 1);
+        assert_eq!(arguments(&command[0]), vec![b"GET".as_slice(), b"key"]);
+        // adding the rest of the command
+        let input = b"$3\r\nkey\r\n$1\r\nS\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 1);
+        assert_eq!(arguments(&command[0]), vec![b"SET".as_slice(), b"key", b"S"]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_resumes_after_the_last_complete_element() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*2\r\n*2\r\n$3\r\nSET\r\n$1\r\nk\r\n$5\r\nvalue\r\n";
+        for (i, byte) in input[..input.len() - 1].iter().enumerate() {
+            assert!(matches!(service.next(&[*byte]), Err(StreamChunkingServiceError::IncompleteCommand)));
+            if i == 23 {
+                // Both elements of the nested array are parsed, the value is not.
+                let partial = service.partial.as_ref().unwrap();
+                assert_eq!((partial.pos, partial.remaining.as_slice(), partial.ranges.len()), (24, &[1][..], 2));
+            }
+        }
+        let command = service.next(&input[input.len() - 1..]).unwrap();
+        assert_eq!(arguments(&command[0]), vec![b"SET".as_slice(), b"k", b"value"]);
+        assert!(service.partial.is_none() && service.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_ping() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*1\r\n$4\r\nPING\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 1);
+        assert_eq!(arguments(&command[0]), vec![b"PING".as_slice()]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_binary_safe() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$6\r\n\xff\r\n\x00ab\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(arguments(&command[0]), vec![b"SET".as_slice(), b"k", b"\xff\r\n\x00ab"]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_null_and_empty_arrays() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*-1\r\n*0\r\n*2\r\n$4\r\nECHO\r\n$-1\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 1);
+        assert_eq!(arguments(&command[0]), vec![b"ECHO".as_slice(), b""]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_nested_types() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"*3\r\n+SET\r\n*1\r\n$1\r\nk\r\n:10\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(arguments(&command[0]), vec![b"SET".as_slice(), b"k", b"10"]);
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_nesting_limit() {
+        let mut service = RespStreamChunkingService::new();
+        let mut input = b"*1\r\n".repeat(MAX_NESTING_DEPTH);
+        input.extend_from_slice(b"$4\r\nPING\r\n");
+        let command = service.next(&input).unwrap();
+        assert_eq!(arguments(&command[0]), vec![b"PING".as_slice()]);
+
+        let input = b"*1\r\n".repeat(100_000);
+        assert!(matches!(
+            service.next(&input),
+            Err(StreamChunkingServiceError::InvalidFormat(reason)) if reason == "too many nested aggregates"
+        ));
+    }
+
+    #[test]
+    fn test_resp_stream_chunking_service_inline() {
+        let mut service = RespStreamChunkingService::new();
+        let input = b"PING\r\n\r\nset  key \"a b\\r\\n\\x41\"\nECHO 'it\\'s'\r\n";
+        let command = service.next(input).unwrap();
+        assert_eq!(command.len(), 3);
+        assert_eq!(arguments(&command[0]), vec![b"PING".as_slice()]);
+        assert_eq!(arguments(&command[1]), vec![b"set".as_slice(), b"key", b"a b\r\nA"]);
+        assert_eq!(arguments(&command[2]), vec![b"ECHO".as_slice(), b"it's"]);
+        assert!(matches!(
+            service.next(b"GET \"key\n"),
+            Err(StreamChunkingServiceError::InvalidFormat(reason)) if reason == "unbalanced quotes in request"
+        ));
+    }
+}
@@ -6,7 +6,7 @@ use std::{thread, time};
 /// Helper to start the server as a background process
 fn start_server() -> Child {
     Command::new("cargo")
-        .arg("run")
+        .args(["run", "--bin", "redis-starter-rust"])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
@@ -37,5 +37,6 @@ fn test_ping() {
 
     // Kill the server
     let _ = child.kill();
+    let _ = child.wait();
 }
 